@@ -0,0 +1,681 @@
+//! Andamento: tesserae laid in rows that follow flow lines, instead of on a rigid grid.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    edges::{self, EdgeDetectionSettings},
+    generator::{self, GenerationSettings},
+    mosaic::{BaseImage, FreeTessera, Mosaic, RectangleInMm, Tessera},
+    tessera_shape::{self, TesseraShape},
+    tile_palette::TilePalette,
+};
+
+/// A flow line, drawn by hand or found on the edges of the image.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct GuideCurve {
+    /// [horizontal, vertical] in mm from the top left corner of the mosaic.
+    pub points: Vec<[f32; 2]>,
+}
+
+/// How to lay the tesserae along the guide curves.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct AndamentoSettings {
+    /// Rows laid on each side of every curve, echoing it. The curve itself gets one more.
+    pub rows_on_each_side: usize,
+    /// Fill the rest of the mosaic with straight rows, around the curved ones.
+    pub fill_background: bool,
+}
+
+impl Default for AndamentoSettings {
+    fn default() -> Self {
+        Self {
+            rows_on_each_side: 3,
+            fill_background: true,
+        }
+    }
+}
+
+// Tesserae overlapping less than this (in mm) are taken as touching.
+const OVERLAP_TOLERANCE_MM: f32 = 0.05;
+// When a tessera does not fit, the next try is this further along the row.
+const SEARCH_STEP_MM: f32 = 1.0;
+// Shorter edges are not worth a curve.
+const MIN_POINTS_IN_GUIDE_CURVE: usize = 4;
+// Edges weaker than this fraction of the strongest one are ignored.
+const GUIDE_CURVE_EDGE_THRESHOLD: f32 = 0.3;
+
+/// Lays free tesserae along the curves, in order, so the first curves take precedence.
+/// Tesserae are only laid whole, inside the mosaic dimensions, and where they do not overlap
+/// the tesserae already laid. Colors are picked like in [`generator::generate_mosaic_from_image`].
+pub fn generate_andamento_mosaic(
+    base_image: Option<BaseImage>,
+    settings: &GenerationSettings,
+    andamento: &AndamentoSettings,
+    guide_curves: &[GuideCurve],
+    tile_palette: Option<&TilePalette>,
+) -> Mosaic {
+    let mut laid = LaidTesserae::new(settings);
+    let row_spacing = (settings.tessera_size.vertical + settings.gap_between_tesserae) as f32;
+    for curve in guide_curves {
+        let points = without_repeated_points(&curve.points);
+        if points.len() < 2 {
+            continue;
+        }
+        for row in get_row_offsets(andamento.rows_on_each_side) {
+            laid.lay_along(&offset_polyline(&points, row as f32 * row_spacing));
+        }
+    }
+    if andamento.fill_background {
+        let mut y = settings.tessera_size.vertical as f32 / 2.0;
+        while y < settings.mosaic_dimensions_mm[1] as f32 {
+            laid.lay_along(&[[0.0, y], [settings.mosaic_dimensions_mm[0] as f32, y]]);
+            y += row_spacing;
+        }
+    }
+    laid.into_mosaic(base_image, settings, tile_palette)
+}
+
+/// Opus vermiculatum: a single row of free tesserae along every contour found on the image,
+/// and the rows of the grid around them. Grid tesserae that would overlap the outlines are
+/// cut narrower to fit, down to half their width, or left out. The grid keeps its place:
+/// the cells left out, and the space beside the cut ones, stay in the rows as empty cells.
+pub fn generate_outlined_mosaic(
+    base_image: Option<BaseImage>,
+    settings: &GenerationSettings,
+    edge_detection: &EdgeDetectionSettings,
+    tile_palette: Option<&TilePalette>,
+) -> Mosaic {
+    let mut laid = LaidTesserae::new(settings);
+    if let Some(image) = &base_image {
+        for contour in edges::find_contours(image, settings.mosaic_dimensions_mm, edge_detection) {
+            let points = without_repeated_points(&contour);
+            if points.len() >= 2 {
+                laid.lay_along(&points);
+            }
+        }
+    }
+    let layout = generator::get_layout(settings);
+    // Without fitting to the edges, the grid may go past them.
+    for (origin, size) in layout.iter().flatten() {
+        laid.bounds[0] = laid.bounds[0].max((origin[0] + size.horizontal) as f32);
+        laid.bounds[1] = laid.bounds[1].max((origin[1] + size.vertical) as f32);
+    }
+    let gap = settings.gap_between_tesserae;
+    let color_for = |origin: [usize; 2], size: RectangleInMm| {
+        generator::get_color_for_area(base_image.as_deref(), origin, size, settings, tile_palette)
+    };
+    let mut rows = Vec::new();
+    for row in layout {
+        let mut tesserae = Vec::new();
+        for (origin, size) in row {
+            match laid.cut_to_fit(origin, size) {
+                None => tesserae.push(Tessera {
+                    color: color_for(origin, size),
+                    size,
+                    shape: TesseraShape::Empty,
+                }),
+                Some((left, cut)) => {
+                    let color = color_for([left, origin[1]], cut);
+                    // The gap goes between the empty space and the cut tessera, as between any two cells.
+                    let space = |width: usize| Tessera {
+                        color,
+                        size: RectangleInMm {
+                            horizontal: width - gap,
+                            vertical: size.vertical,
+                        },
+                        shape: TesseraShape::Empty,
+                    };
+                    if left > origin[0] {
+                        tesserae.push(space(left - origin[0]));
+                    }
+                    tesserae.push(Tessera {
+                        color,
+                        size: cut,
+                        shape: TesseraShape::Whole,
+                    });
+                    if left + cut.horizontal < origin[0] + size.horizontal {
+                        tesserae.push(space(origin[0] + size.horizontal - left - cut.horizontal));
+                    }
+                }
+            }
+        }
+        rows.push(tesserae);
+    }
+    let mut mosaic = laid.into_mosaic(base_image, settings, tile_palette);
+    for row in rows {
+        mosaic.add_a_row_of_tesserae(row);
+    }
+    mosaic
+}
+
+/// Flow lines along the strongest edges of the image, stretched over the mosaic.
+/// The image is looked at in cells of step_mm, so bigger steps ignore smaller details.
+pub fn guide_curves_from_image_edges(
+    image: &image::Rgba32FImage,
+    mosaic_dimensions_mm: [usize; 2],
+    step_mm: f32,
+) -> Vec<GuideCurve> {
+    // Without blur and with a single threshold, every edge cell stands on its own.
+    let settings = EdgeDetectionSettings {
+        cell_size_mm: step_mm,
+        high_threshold: GUIDE_CURVE_EDGE_THRESHOLD,
+        low_threshold: GUIDE_CURVE_EDGE_THRESHOLD,
+        min_cells_in_contour: MIN_POINTS_IN_GUIDE_CURVE,
+        blur: false,
+    };
+    edges::find_contours(image, mosaic_dimensions_mm, &settings)
+        .into_iter()
+        .map(|points| GuideCurve { points })
+        .collect()
+}
+
+// The curve itself, then alternate sides, outwards: 0, 1, -1, 2, -2...
+fn get_row_offsets(rows_on_each_side: usize) -> Vec<isize> {
+    let mut offsets = vec![0];
+    for row in 1..=rows_on_each_side as isize {
+        offsets.push(row);
+        offsets.push(-row);
+    }
+    offsets
+}
+
+fn without_repeated_points(points: &[[f32; 2]]) -> Vec<[f32; 2]> {
+    let mut kept: Vec<[f32; 2]> = Vec::new();
+    for point in points {
+        match kept.last() {
+            Some(last) if distance(*last, *point) < 0.01 => (),
+            _ => kept.push(*point),
+        }
+    }
+    kept
+}
+
+fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+    ((b[0] - a[0]).powi(2) + (b[1] - a[1]).powi(2)).sqrt()
+}
+
+// Left hand normal of the segment, as seen on screen (with y growing downwards).
+fn normal(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    let length = distance(a, b).max(f32::EPSILON);
+    [(b[1] - a[1]) / length, -(b[0] - a[0]) / length]
+}
+
+// A parallel line at the given distance, positive on the left hand side.
+// Sharp bends come out crossed, their tesserae are discarded as overlapping.
+fn offset_polyline(points: &[[f32; 2]], offset: f32) -> Vec<[f32; 2]> {
+    if offset == 0.0 {
+        return points.to_vec();
+    }
+    (0..points.len())
+        .map(|i| {
+            let before = normal(points[i.saturating_sub(1)], points[i.max(1)]);
+            let after = normal(
+                points[i.min(points.len() - 2)],
+                points[(i + 1).min(points.len() - 1)],
+            );
+            let sum = [before[0] + after[0], before[1] + after[1]];
+            let length = (sum[0] * sum[0] + sum[1] * sum[1]).sqrt();
+            if length < f32::EPSILON {
+                return [
+                    points[i][0] + after[0] * offset,
+                    points[i][1] + after[1] * offset,
+                ];
+            }
+            let bisector = [sum[0] / length, sum[1] / length];
+            // So both segments end up at the same distance, but not too far on sharp bends.
+            let miter = offset / (bisector[0] * after[0] + bisector[1] * after[1]).max(0.5);
+            [
+                points[i][0] + bisector[0] * miter,
+                points[i][1] + bisector[1] * miter,
+            ]
+        })
+        .collect()
+}
+
+// The point at a distance along the polyline, and the direction there, in degrees.
+fn get_point_and_direction_at(points: &[[f32; 2]], distance_along: f32) -> ([f32; 2], f32) {
+    let mut remaining = distance_along;
+    let last_segment = points.len() - 2;
+    for (index, pair) in points.windows(2).enumerate() {
+        let length = distance(pair[0], pair[1]);
+        if remaining <= length || index == last_segment {
+            let t = if length > 0.0 {
+                remaining / length
+            } else {
+                0.0
+            };
+            return (
+                [
+                    pair[0][0] + (pair[1][0] - pair[0][0]) * t,
+                    pair[0][1] + (pair[1][1] - pair[0][1]) * t,
+                ],
+                (pair[1][1] - pair[0][1])
+                    .atan2(pair[1][0] - pair[0][0])
+                    .to_degrees(),
+            );
+        }
+        remaining -= length;
+    }
+    (points[0], 0.0)
+}
+
+// The tesserae laid so far, with a coarse spatial index to look only at the near ones.
+struct LaidTesserae {
+    tessera_size: RectangleInMm,
+    gap: usize,
+    bounds: [f32; 2],
+    cell_size: f32,
+    // Center, rotation and size of every tessera.
+    placements: Vec<([f32; 2], f32, RectangleInMm)>,
+    // Outlines grown by half the gap on every side, by cell.
+    outlines: std::collections::HashMap<[i32; 2], Vec<Vec<[f32; 2]>>>,
+}
+
+impl LaidTesserae {
+    fn new(settings: &GenerationSettings) -> Self {
+        let gap = settings.gap_between_tesserae;
+        Self {
+            tessera_size: settings.tessera_size,
+            gap,
+            bounds: [
+                settings.mosaic_dimensions_mm[0] as f32,
+                settings.mosaic_dimensions_mm[1] as f32,
+            ],
+            cell_size: (settings
+                .tessera_size
+                .horizontal
+                .max(settings.tessera_size.vertical)
+                + gap) as f32,
+            placements: Vec::new(),
+            outlines: std::collections::HashMap::new(),
+        }
+    }
+
+    fn lay_along(&mut self, polyline: &[[f32; 2]]) {
+        let length: f32 = polyline.windows(2).map(|p| distance(p[0], p[1])).sum();
+        let width = self.tessera_size.horizontal as f32;
+        let mut along = 0.0;
+        while along + width <= length {
+            let (center, direction) = get_point_and_direction_at(polyline, along + width / 2.0);
+            if self.try_to_take(center, direction, self.tessera_size) {
+                self.placements.push((center, direction, self.tessera_size));
+                along += width + self.gap as f32;
+            } else {
+                along += SEARCH_STEP_MM;
+            }
+        }
+    }
+
+    // Narrower and narrower, on either side of the place, down to half the width.
+    // The left side and the size of the cut that fits, if any. It is not a free tessera.
+    // What is cut off must be wider than the gap, to be left as an empty cell beside it.
+    fn cut_to_fit(
+        &mut self,
+        origin: [usize; 2],
+        size: RectangleInMm,
+    ) -> Option<(usize, RectangleInMm)> {
+        let min_width = (size.horizontal / 2).max(1);
+        for width in (min_width..=size.horizontal).rev() {
+            if width < size.horizontal && size.horizontal - width <= self.gap {
+                continue;
+            }
+            for left in [origin[0], origin[0] + size.horizontal - width] {
+                let center = [
+                    left as f32 + width as f32 / 2.0,
+                    origin[1] as f32 + size.vertical as f32 / 2.0,
+                ];
+                let cut = RectangleInMm {
+                    horizontal: width,
+                    vertical: size.vertical,
+                };
+                if self.try_to_take(center, 0.0, cut) {
+                    return Some((left, cut));
+                }
+            }
+        }
+        None
+    }
+
+    // Keeps the place for the tessera if it is free.
+    fn try_to_take(
+        &mut self,
+        center: [f32; 2],
+        rotation_degrees: f32,
+        size: RectangleInMm,
+    ) -> bool {
+        let outline_of = |size: RectangleInMm| {
+            FreeTessera {
+                tessera: Tessera {
+                    color: palette::Oklch::new(0.0, 0.0, 0.0),
+                    size,
+                    shape: TesseraShape::Whole,
+                },
+                center_mm: center,
+                rotation_degrees,
+            }
+            .get_polygon()
+        };
+        let outline = outline_of(size);
+        if outline.iter().any(|[x, y]| {
+            *x < -OVERLAP_TOLERANCE_MM
+                || *y < -OVERLAP_TOLERANCE_MM
+                || *x > self.bounds[0] + OVERLAP_TOLERANCE_MM
+                || *y > self.bounds[1] + OVERLAP_TOLERANCE_MM
+        }) {
+            return false;
+        }
+        let grown = outline_of(RectangleInMm {
+            horizontal: size.horizontal + self.gap,
+            vertical: size.vertical + self.gap,
+        });
+        let cell = [
+            (center[0] / self.cell_size).floor() as i32,
+            (center[1] / self.cell_size).floor() as i32,
+        ];
+        for dx in -2..=2 {
+            for dy in -2..=2 {
+                if let Some(others) = self.outlines.get(&[cell[0] + dx, cell[1] + dy]) {
+                    if others.iter().any(|other| {
+                        tessera_shape::convex_polygons_overlap(&grown, other, OVERLAP_TOLERANCE_MM)
+                    }) {
+                        return false;
+                    }
+                }
+            }
+        }
+        self.outlines.entry(cell).or_default().push(grown);
+        true
+    }
+
+    // Colors are picked like in generator::generate_mosaic_from_image.
+    fn into_mosaic(
+        self,
+        base_image: Option<BaseImage>,
+        settings: &GenerationSettings,
+        tile_palette: Option<&TilePalette>,
+    ) -> Mosaic {
+        let mut mosaic = Mosaic::new(base_image, settings.tessera_size);
+        for (center_mm, rotation_degrees, size) in self.placements {
+            let origin = [
+                (center_mm[0] - size.horizontal as f32 / 2.0).max(0.0) as usize,
+                (center_mm[1] - size.vertical as f32 / 2.0).max(0.0) as usize,
+            ];
+            let color = generator::get_color_for_area(
+                mosaic.get_base_image(),
+                origin,
+                size,
+                settings,
+                tile_palette,
+            );
+            mosaic.add_free_tessera(FreeTessera {
+                tessera: Tessera {
+                    color,
+                    size,
+                    shape: TesseraShape::Whole,
+                },
+                center_mm,
+                rotation_degrees,
+            });
+        }
+        mosaic
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        generator::settings_for,
+        materials::{MaterialsReport, MaterialsSettings},
+    };
+
+    fn only_along_the_curves(rows_on_each_side: usize) -> AndamentoSettings {
+        AndamentoSettings {
+            rows_on_each_side,
+            fill_background: false,
+        }
+    }
+
+    #[test]
+    fn rows_are_laid_on_the_curve_then_on_alternate_sides() {
+        assert_eq!(get_row_offsets(2), vec![0, 1, -1, 2, -2]);
+    }
+
+    #[test]
+    fn a_straight_curve_gets_a_straight_row_of_whole_tesserae() {
+        let curve = GuideCurve {
+            points: vec![[0.0, 15.0], [100.0, 15.0]],
+        };
+        let mosaic = generate_andamento_mosaic(
+            None,
+            &settings_for([100, 30]),
+            &only_along_the_curves(0),
+            &[curve],
+            None,
+        );
+        assert_eq!(mosaic.get_number_of_rows(), 0);
+        let free_tesserae = mosaic.get_free_tesserae();
+        assert_eq!(free_tesserae.len(), 9);
+        for (index, free_tessera) in free_tesserae.iter().enumerate() {
+            assert!(free_tessera.rotation_degrees.abs() < 0.001);
+            assert!((free_tessera.center_mm[0] - (5.0 + 11.0 * index as f32)).abs() < 0.001);
+            assert!((free_tessera.center_mm[1] - 15.0).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn tesserae_follow_the_direction_of_the_curve() {
+        let curve = GuideCurve {
+            points: vec![[50.0, 0.0], [50.0, 100.0]],
+        };
+        let mosaic = generate_andamento_mosaic(
+            None,
+            &settings_for([100, 100]),
+            &only_along_the_curves(1),
+            &[curve],
+            None,
+        );
+        assert_eq!(mosaic.get_free_tesserae().len(), 27);
+        assert!(mosaic
+            .get_free_tesserae()
+            .iter()
+            .all(|free_tessera| (free_tessera.rotation_degrees - 90.0).abs() < 0.001));
+    }
+
+    #[test]
+    fn tesserae_do_not_overlap_nor_go_out_of_the_mosaic() {
+        let circle = GuideCurve {
+            points: (0..=36)
+                .map(|i| {
+                    let angle = (i as f32 * 10.0).to_radians();
+                    [60.0 + 30.0 * angle.cos(), 50.0 + 30.0 * angle.sin()]
+                })
+                .collect(),
+        };
+        let mosaic = generate_andamento_mosaic(
+            None,
+            &settings_for([120, 100]),
+            &AndamentoSettings::default(),
+            &[circle],
+            None,
+        );
+        let outlines: Vec<Vec<[f32; 2]>> = mosaic
+            .get_free_tesserae()
+            .iter()
+            .map(|free_tessera| free_tessera.get_polygon())
+            .collect();
+        assert!(outlines.len() > 40);
+        for (index, outline) in outlines.iter().enumerate() {
+            assert!(outline
+                .iter()
+                .all(|[x, y]| *x > -0.1 && *y > -0.1 && *x < 120.1 && *y < 100.1));
+            for other in &outlines[index + 1..] {
+                assert!(!tessera_shape::convex_polygons_overlap(outline, other, 0.1));
+            }
+        }
+    }
+
+    #[test]
+    fn outlines_get_one_row_and_the_grid_is_cut_around_them() {
+        let image = image::Rgba32FImage::from_fn(100, 100, |x, _y| {
+            let l = if x < 47 { 0.0 } else { 1.0 };
+            image::Rgba([l, l, l, 1.0])
+        });
+        let settings = settings_for([100, 100]);
+        let edge_detection = EdgeDetectionSettings {
+            cell_size_mm: 5.0,
+            ..EdgeDetectionSettings::default()
+        };
+        let mosaic = generate_outlined_mosaic(
+            Some(BaseImage::new(image)),
+            &settings,
+            &edge_detection,
+            None,
+        );
+        let free_tesserae = mosaic.get_free_tesserae();
+        let along_the_contour = free_tesserae
+            .iter()
+            .filter(|free_tessera| (free_tessera.rotation_degrees.abs() - 90.0).abs() < 1.0)
+            .count();
+        assert!(along_the_contour >= 7);
+        assert_eq!(free_tesserae.len(), along_the_contour);
+        // The grid keeps its place, with the tesserae next to the contour cut.
+        assert_eq!(mosaic.get_number_of_rows(), 9);
+        let grid_outlines: Vec<Vec<[f32; 2]>> = mosaic
+            .get_tesserae_with_placements(settings.gap_between_tesserae)
+            .into_iter()
+            .filter(|(_position, tessera, _placement)| tessera.shape != TesseraShape::Empty)
+            .map(|(_position, tessera, placement)| {
+                tessera
+                    .shape
+                    .get_polygon(&tessera.size)
+                    .iter()
+                    .map(|[x, y]| {
+                        [
+                            x + placement.origin[0] as f32,
+                            y + placement.origin[1] as f32,
+                        ]
+                    })
+                    .collect()
+            })
+            .collect();
+        assert!(mosaic
+            .get_contents()
+            .iter()
+            .flatten()
+            .any(|tessera| tessera.shape == TesseraShape::Whole && tessera.size.horizontal < 10));
+        let free_outlines: Vec<Vec<[f32; 2]>> = free_tesserae
+            .iter()
+            .map(|free_tessera| free_tessera.get_polygon())
+            .collect();
+        for (index, outline) in free_outlines.iter().enumerate() {
+            for other in free_outlines[index + 1..].iter().chain(&grid_outlines) {
+                assert!(!tessera_shape::convex_polygons_overlap(outline, other, 0.1));
+            }
+        }
+    }
+
+    #[test]
+    fn the_grid_keeps_its_place_when_tesserae_are_left_out() {
+        let image = image::Rgba32FImage::from_fn(100, 100, |_x, y| {
+            let l = if y < 52 { 0.0 } else { 1.0 };
+            image::Rgba([l, l, l, 1.0])
+        });
+        let settings = settings_for([100, 100]);
+        let edge_detection = EdgeDetectionSettings {
+            cell_size_mm: 5.0,
+            ..EdgeDetectionSettings::default()
+        };
+        let mosaic = generate_outlined_mosaic(
+            Some(BaseImage::new(image)),
+            &settings,
+            &edge_detection,
+            None,
+        );
+        // Across the contour, the cells left out stay empty, the rest keep their place and size.
+        let gap = settings.gap_between_tesserae;
+        let blank = generator::generate_blank_mosaic(palette::Oklch::new(1.0, 0.0, 0.0), &settings);
+        assert_eq!(
+            mosaic.get_overall_size_in_mm(gap),
+            blank.get_overall_size_in_mm(gap)
+        );
+        let empty_cells = mosaic
+            .get_contents()
+            .iter()
+            .flatten()
+            .filter(|tessera| tessera.shape == TesseraShape::Empty)
+            .count();
+        assert!(empty_cells > 0);
+        let blank_placements = blank.get_tesserae_with_placements(gap);
+        for (_position, tessera, placement) in mosaic.get_tesserae_with_placements(gap) {
+            if tessera.shape == TesseraShape::Empty {
+                continue;
+            }
+            assert_eq!(tessera.shape, TesseraShape::Whole);
+            let right = placement.origin[0] + placement.size.horizontal;
+            assert!(blank_placements.iter().any(|(_position, _tessera, cell)| {
+                cell.origin[1] == placement.origin[1]
+                    && cell.size.vertical == placement.size.vertical
+                    && cell.origin[0] <= placement.origin[0]
+                    && right <= cell.origin[0] + cell.size.horizontal
+            }));
+        }
+    }
+
+    #[test]
+    fn the_materials_of_an_outlined_mosaic_cover_the_shapes_of_its_tesserae() {
+        let image = image::Rgba32FImage::from_fn(100, 100, |x, y| {
+            let l = if x + y < 110 { 0.0 } else { 1.0 };
+            image::Rgba([l, l, l, 1.0])
+        });
+        let mosaic = generate_outlined_mosaic(
+            Some(BaseImage::new(image)),
+            &settings_for([100, 100]),
+            &EdgeDetectionSettings {
+                cell_size_mm: 5.0,
+                ..EdgeDetectionSettings::default()
+            },
+            None,
+        );
+        let report = MaterialsReport::new(
+            &mosaic,
+            &TilePalette::default(),
+            &MaterialsSettings {
+                waste_percentage: 0.0,
+                ..MaterialsSettings::default()
+            },
+        );
+        let area_square_meters: f32 = report
+            .lines
+            .iter()
+            .map(|line| line.area_square_meters)
+            .sum();
+        let shapes_area: f32 = mosaic
+            .get_all_tesserae()
+            .map(|tessera| tessera.shape.get_area(&tessera.size))
+            .sum();
+        assert!(shapes_area > 0.0);
+        assert!((area_square_meters * 1_000_000.0 - shapes_area).abs() < 1.0);
+        assert_eq!(report.get_total_cuts(), 0);
+        assert!(report.get_total_tesserae() < mosaic.get_all_tesserae().count());
+    }
+
+    #[test]
+    fn curves_are_found_along_the_edges_of_the_image() {
+        let image = image::Rgba32FImage::from_fn(200, 100, |x, _y| {
+            if x < 100 {
+                image::Rgba([0.0, 0.0, 0.0, 1.0])
+            } else {
+                image::Rgba([1.0, 1.0, 1.0, 1.0])
+            }
+        });
+        let curves = guide_curves_from_image_edges(&image, [100, 50], 5.0);
+        assert_eq!(curves.len(), 1);
+        let points = &curves[0].points;
+        assert!(points.iter().all(|[x, _y]| (*x - 50.0).abs() <= 5.0));
+        let heights: Vec<f32> = points.iter().map(|[_x, y]| *y).collect();
+        let span = heights.iter().cloned().fold(f32::MIN, f32::max)
+            - heights.iter().cloned().fold(f32::MAX, f32::min);
+        assert!(span > 30.0);
+    }
+}
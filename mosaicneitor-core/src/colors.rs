@@ -0,0 +1,58 @@
+//! Conversions between 8 bit sRGB, as used by files and screens, and Oklch, as used by the mosaic.
+
+use palette::convert::FromColor;
+
+/// From [red, green, blue], 0 to 255.
+pub fn oklch_from_srgb8(srgb: [u8; 3]) -> palette::Oklch {
+    let color: palette::Srgb<f32> = palette::Srgb::new(srgb[0], srgb[1], srgb[2]).into_format();
+    palette::Oklch::from_color(color)
+}
+
+/// To [red, green, blue], 0 to 255. Colors out of the sRGB gamut are clamped.
+pub fn srgb8_from_oklch(color: palette::Oklch) -> [u8; 3] {
+    let srgb: palette::Srgb<u8> = palette::Srgb::from_color(color).into_format();
+    [srgb.red, srgb.green, srgb.blue]
+}
+
+/// Euclidean distance in Oklab, a good enough approximation of perceived color difference.
+pub fn oklab_distance(a: palette::Oklch, b: palette::Oklch) -> f32 {
+    distance_in_oklab(oklab_from_oklch(a), oklab_from_oklch(b))
+}
+
+/// To [l, a, b], to do arithmetic on colors, as averaging them or spreading errors.
+pub fn oklab_from_oklch(color: palette::Oklch) -> [f32; 3] {
+    let oklab = palette::Oklab::from_color(color);
+    [oklab.l, oklab.a, oklab.b]
+}
+
+/// From [l, a, b].
+pub fn oklch_from_oklab(oklab: [f32; 3]) -> palette::Oklch {
+    palette::Oklch::from_color(palette::Oklab::new(oklab[0], oklab[1], oklab[2]))
+}
+
+/// Like [`oklab_distance`], for colors already in [l, a, b].
+pub fn distance_in_oklab(a: [f32; 3], b: [f32; 3]) -> f32 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn srgb_survives_a_round_trip_through_oklch() {
+        for srgb in [[0, 0, 0], [255, 255, 255], [12, 200, 77], [255, 0, 0]] {
+            assert_eq!(srgb8_from_oklch(oklch_from_srgb8(srgb)), srgb);
+            let oklab = oklab_from_oklch(oklch_from_srgb8(srgb));
+            assert_eq!(srgb8_from_oklch(oklch_from_oklab(oklab)), srgb);
+        }
+    }
+
+    #[test]
+    fn oklab_distance_is_zero_for_equal_colors_and_one_from_black_to_white() {
+        let white = oklch_from_srgb8([255, 255, 255]);
+        let black = oklch_from_srgb8([0, 0, 0]);
+        assert!(oklab_distance(white, white) < 0.0001);
+        assert!((oklab_distance(white, black) - 1.0).abs() < 0.001);
+    }
+}
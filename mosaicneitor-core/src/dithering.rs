@@ -0,0 +1,246 @@
+//! Dithering, to keep the gradients of the image when the tesserae are restricted to a few colors.
+
+use crate::{
+    colors::{distance_in_oklab, oklab_from_oklch, oklch_from_oklab},
+    mosaic::Mosaic,
+    tile_palette::TilePalette,
+};
+
+/// How the difference between the color of a tessera and its tile color is spread.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DitheringMethod {
+    /// Every tessera gets the nearest tile color, gradients turn into flat bands.
+    None,
+    /// The whole error goes to the next tessera and to the row below.
+    FloydSteinberg,
+    /// Only three quarters of the error is spread, a bit further. More contrast, less noise.
+    Atkinson,
+    /// A fixed 4x4 pattern of thresholds, regular like a cross stitch.
+    Ordered,
+}
+
+impl DitheringMethod {
+    /// Every method, to list them on menus.
+    pub const ALL: [DitheringMethod; 4] = [
+        DitheringMethod::None,
+        DitheringMethod::FloydSteinberg,
+        DitheringMethod::Atkinson,
+        DitheringMethod::Ordered,
+    ];
+}
+
+/// Dithering method, and how much of it.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DitheringSettings {
+    /// How to spread the error.
+    pub method: DitheringMethod,
+    /// From 0 (same as no dithering) to 1 (the whole error).
+    pub strength: f32,
+}
+
+impl Default for DitheringSettings {
+    fn default() -> Self {
+        Self {
+            method: DitheringMethod::None,
+            strength: 1.0,
+        }
+    }
+}
+
+// Rows down, columns right and share of the error, for the error diffusion methods.
+const FLOYD_STEINBERG: [(usize, isize, f32); 4] = [
+    (0, 1, 7.0 / 16.0),
+    (1, -1, 3.0 / 16.0),
+    (1, 0, 5.0 / 16.0),
+    (1, 1, 1.0 / 16.0),
+];
+const ATKINSON: [(usize, isize, f32); 6] = [
+    (0, 1, 1.0 / 8.0),
+    (0, 2, 1.0 / 8.0),
+    (1, -1, 1.0 / 8.0),
+    (1, 0, 1.0 / 8.0),
+    (1, 1, 1.0 / 8.0),
+    (2, 0, 1.0 / 8.0),
+];
+const BAYER_4X4: [[f32; 4]; 4] = [
+    [0.0, 8.0, 2.0, 10.0],
+    [12.0, 4.0, 14.0, 6.0],
+    [3.0, 11.0, 1.0, 9.0],
+    [15.0, 7.0, 13.0, 5.0],
+];
+
+/// A tile color for every tessera, in the order of [`Mosaic::get_all_tesserae`], dithered in Oklab.
+/// The error goes to the neighbours on the grid, by row and column. Free tesserae have no
+/// neighbours, they get the nearest tile color. With an empty palette, the colors are kept.
+pub fn dither_to_palette(
+    mosaic: &Mosaic,
+    tile_palette: &TilePalette,
+    settings: &DitheringSettings,
+) -> Vec<palette::Oklch> {
+    let tile_colors: Vec<[f32; 3]> = tile_palette
+        .entries
+        .iter()
+        .map(|entry| oklab_from_oklch(entry.get_color()))
+        .collect();
+    if tile_colors.is_empty() {
+        return mosaic
+            .get_all_tesserae()
+            .map(|tessera| tessera.color)
+            .collect();
+    }
+    let nearest = |color: [f32; 3]| {
+        *tile_colors
+            .iter()
+            .min_by(|a, b| distance_in_oklab(**a, color).total_cmp(&distance_in_oklab(**b, color)))
+            .unwrap_or(&color)
+    };
+    let strength = settings.strength.clamp(0.0, 1.0);
+    let mut grid: Vec<Vec<[f32; 3]>> = mosaic
+        .get_contents()
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|tessera| oklab_from_oklch(tessera.color))
+                .collect()
+        })
+        .collect();
+    // Ordered dithering moves the colors up to half the distance between neighbouring tile colors.
+    let spread = get_typical_spacing(&tile_colors);
+
+    let mut result = Vec::new();
+    for row in 0..grid.len() {
+        for column in 0..grid[row].len() {
+            let color = grid[row][column];
+            let chosen = match settings.method {
+                DitheringMethod::Ordered => {
+                    let threshold = (BAYER_4X4[row % 4][column % 4] + 0.5) / 16.0 - 0.5;
+                    nearest(color.map(|value| value + threshold * spread * strength))
+                }
+                _ => nearest(color),
+            };
+            let diffusion: &[(usize, isize, f32)] = match settings.method {
+                DitheringMethod::FloydSteinberg => &FLOYD_STEINBERG,
+                DitheringMethod::Atkinson => &ATKINSON,
+                _ => &[],
+            };
+            let error: [f32; 3] = std::array::from_fn(|axis| color[axis] - chosen[axis]);
+            for (down, right, share) in diffusion {
+                let target_column = column as isize + right;
+                if target_column < 0 {
+                    continue;
+                }
+                if let Some(target) = grid
+                    .get_mut(row + down)
+                    .and_then(|target_row| target_row.get_mut(target_column as usize))
+                {
+                    for (value, error) in target.iter_mut().zip(error) {
+                        *value += error * share * strength;
+                    }
+                }
+            }
+            result.push(oklch_from_oklab(chosen));
+        }
+    }
+    for free_tessera in mosaic.get_free_tesserae() {
+        result.push(oklch_from_oklab(nearest(oklab_from_oklch(
+            free_tessera.tessera.color,
+        ))));
+    }
+    result
+}
+
+// Mean distance from every tile color to the nearest other one.
+fn get_typical_spacing(tile_colors: &[[f32; 3]]) -> f32 {
+    if tile_colors.len() < 2 {
+        return 0.0;
+    }
+    let total: f32 = tile_colors
+        .iter()
+        .enumerate()
+        .map(|(index, color)| {
+            tile_colors
+                .iter()
+                .enumerate()
+                .filter(|(other_index, _other)| *other_index != index)
+                .map(|(_other_index, other)| distance_in_oklab(*color, *other))
+                .fold(f32::MAX, f32::min)
+        })
+        .sum();
+    total / tile_colors.len() as f32
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{colors, mosaic::a_mosaic_of_colors};
+
+    fn black_and_white() -> TilePalette {
+        let mut tile_palette = TilePalette::default();
+        tile_palette.add_entry([0, 0, 0]);
+        tile_palette.add_entry([255, 255, 255]);
+        tile_palette
+    }
+
+    // 8x8 tesserae, all of the same grey.
+    fn a_grey_mosaic(srgb: [u8; 3]) -> Mosaic {
+        a_mosaic_of_colors([10, 10], vec![vec![colors::oklch_from_srgb8(srgb); 8]; 8])
+    }
+
+    fn count_of_white(colors: &[palette::Oklch]) -> usize {
+        colors
+            .iter()
+            .filter(|color| colors::srgb8_from_oklch(**color) == [255, 255, 255])
+            .count()
+    }
+
+    #[test]
+    fn without_dithering_every_tessera_gets_the_nearest_color() {
+        let settings = DitheringSettings::default();
+        let colors = dither_to_palette(
+            &a_grey_mosaic([140, 140, 140]),
+            &black_and_white(),
+            &settings,
+        );
+        assert_eq!(colors.len(), 64);
+        assert_eq!(count_of_white(&colors), 64);
+    }
+
+    #[test]
+    fn every_method_mixes_both_colors_for_a_middle_grey() {
+        // Oklab lightness of about one half.
+        let mosaic = a_grey_mosaic([99, 99, 99]);
+        for method in [
+            DitheringMethod::FloydSteinberg,
+            DitheringMethod::Atkinson,
+            DitheringMethod::Ordered,
+        ] {
+            let settings = DitheringSettings {
+                method,
+                strength: 1.0,
+            };
+            let colors = dither_to_palette(&mosaic, &black_and_white(), &settings);
+            let whites = count_of_white(&colors);
+            assert!((16..=48).contains(&whites), "{method:?}: {whites} white");
+            assert!(colors.iter().all(|color| {
+                let srgb = colors::srgb8_from_oklch(*color);
+                srgb == [0, 0, 0] || srgb == [255, 255, 255]
+            }));
+        }
+    }
+
+    #[test]
+    fn no_strength_is_the_same_as_no_dithering() {
+        let mosaic = a_grey_mosaic([110, 110, 110]);
+        let nearest = dither_to_palette(&mosaic, &black_and_white(), &DitheringSettings::default());
+        for method in DitheringMethod::ALL {
+            let settings = DitheringSettings {
+                method,
+                strength: 0.0,
+            };
+            assert_eq!(
+                dither_to_palette(&mosaic, &black_and_white(), &settings),
+                nearest
+            );
+        }
+    }
+}
@@ -0,0 +1,349 @@
+//! Finding the contours of the shapes on the base image, to outline them with tesserae.
+
+use crate::{
+    generator,
+    sampling::{self, PixelArea, SamplingMode},
+};
+
+/// How sensitive the edge detection is.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct EdgeDetectionSettings {
+    /// The image is looked at in cells of this size, in mm, so bigger cells ignore smaller details.
+    pub cell_size_mm: f32,
+    /// Edges stronger than this fraction of the strongest one start a contour.
+    pub high_threshold: f32,
+    /// Edges weaker than this fraction of the strongest one are never part of a contour.
+    pub low_threshold: f32,
+    /// Shorter contours, in cells, are left out.
+    pub min_cells_in_contour: usize,
+    /// Blur the image a little first, so noise does not make edges.
+    pub blur: bool,
+}
+
+impl Default for EdgeDetectionSettings {
+    fn default() -> Self {
+        Self {
+            cell_size_mm: 5.0,
+            high_threshold: 0.3,
+            low_threshold: 0.15,
+            min_cells_in_contour: 4,
+            blur: true,
+        }
+    }
+}
+
+/// A line along an edge of the image, [horizontal, vertical] in mm on the mosaic.
+pub type Contour = Vec<[f32; 2]>;
+
+/// Canny edge detection over the lightness of the image, stretched over the mosaic:
+/// a light blur (if asked for), Sobel gradients, thinning to the strongest cells across each edge
+/// and hysteresis between both thresholds. Then the edge cells are chained into contours.
+pub fn find_contours(
+    image: &image::Rgba32FImage,
+    mosaic_dimensions_mm: [usize; 2],
+    settings: &EdgeDetectionSettings,
+) -> Vec<Contour> {
+    let cell_size = settings.cell_size_mm.max(1.0);
+    let lightness = get_lightness_by_cell(image, mosaic_dimensions_mm, cell_size);
+    let lightness = if settings.blur {
+        blurred(&lightness)
+    } else {
+        lightness
+    };
+    let rows = lightness.len();
+    let columns = lightness.first().map_or(0, |row| row.len());
+    if columns < 3 || rows < 3 {
+        return Vec::new();
+    }
+
+    let l = |r: usize, c: usize| lightness[r][c];
+    let gradients: Vec<Vec<[f32; 2]>> = (0..rows)
+        .map(|row| {
+            (0..columns)
+                .map(|column| {
+                    if row == 0 || column == 0 || row == rows - 1 || column == columns - 1 {
+                        return [0.0, 0.0];
+                    }
+                    [
+                        (l(row - 1, column + 1)
+                            + 2.0 * l(row, column + 1)
+                            + l(row + 1, column + 1))
+                            - (l(row - 1, column - 1)
+                                + 2.0 * l(row, column - 1)
+                                + l(row + 1, column - 1)),
+                        (l(row + 1, column - 1)
+                            + 2.0 * l(row + 1, column)
+                            + l(row + 1, column + 1))
+                            - (l(row - 1, column - 1)
+                                + 2.0 * l(row - 1, column)
+                                + l(row - 1, column + 1)),
+                    ]
+                })
+                .collect()
+        })
+        .collect();
+    let magnitude = |g: [f32; 2]| (g[0] * g[0] + g[1] * g[1]).sqrt();
+    let strongest = gradients
+        .iter()
+        .flatten()
+        .map(|g| magnitude(*g))
+        .fold(0.0, f32::max);
+    if strongest < f32::EPSILON {
+        return Vec::new();
+    }
+
+    // Only the cells stronger than their neighbours across the edge, to get thin lines.
+    let mut strength = vec![vec![0.0; columns]; rows];
+    for row in 1..rows - 1 {
+        for column in 1..columns - 1 {
+            let gradient = gradients[row][column];
+            let here = magnitude(gradient);
+            if here < settings.low_threshold * strongest {
+                continue;
+            }
+            let across = [
+                (gradient[0] / here).round() as isize,
+                (gradient[1] / here).round() as isize,
+            ];
+            let neighbour = |sign: isize| {
+                let r = (row as isize + sign * across[1]) as usize;
+                let c = (column as isize + sign * across[0]) as usize;
+                magnitude(gradients[r][c])
+            };
+            if here >= neighbour(1) && here > neighbour(-1) {
+                strength[row][column] = here / strongest;
+            }
+        }
+    }
+
+    // Hysteresis: weak cells are kept only when they are connected to a strong one.
+    let mut is_edge = vec![vec![false; columns]; rows];
+    for row in 0..rows {
+        for column in 0..columns {
+            if strength[row][column] >= settings.high_threshold && !is_edge[row][column] {
+                is_edge[row][column] = true;
+                let mut pending = vec![[row, column]];
+                while let Some(cell) = pending.pop() {
+                    for [r, c] in get_neighbours(cell, [rows, columns]) {
+                        if strength[r][c] > 0.0 && !is_edge[r][c] {
+                            is_edge[r][c] = true;
+                            pending.push([r, c]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Edge cells are chained into contours, following neighbours in both directions.
+    let mut contours = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            if !is_edge[row][column] {
+                continue;
+            }
+            is_edge[row][column] = false;
+            let mut forward = follow_edge(&mut is_edge, [row, column]);
+            let backward = follow_edge(&mut is_edge, [row, column]);
+            if forward.len() + backward.len() + 1 < settings.min_cells_in_contour {
+                continue;
+            }
+            let mut cells: Vec<[usize; 2]> = backward.into_iter().rev().collect();
+            cells.push([row, column]);
+            cells.append(&mut forward);
+            let points: Vec<[f32; 2]> = cells
+                .iter()
+                .map(|[r, c]| [(*c as f32 + 0.5) * cell_size, (*r as f32 + 0.5) * cell_size])
+                .collect();
+            contours.push(smooth(&points));
+        }
+    }
+    contours
+}
+
+// The average lightness of the image under every cell of the mosaic.
+fn get_lightness_by_cell(
+    image: &image::Rgba32FImage,
+    mosaic_dimensions_mm: [usize; 2],
+    cell_size: f32,
+) -> Vec<Vec<f32>> {
+    let columns = (mosaic_dimensions_mm[0] as f32 / cell_size).ceil() as usize;
+    let rows = (mosaic_dimensions_mm[1] as f32 / cell_size).ceil() as usize;
+    let image_dimensions = [image.width() as usize, image.height() as usize];
+    let pixel_at = |column: usize, row: usize| {
+        generator::get_pixel_position_on_image(
+            [
+                (column as f32 * cell_size) as usize,
+                (row as f32 * cell_size) as usize,
+            ],
+            mosaic_dimensions_mm,
+            image_dimensions,
+        )
+    };
+    (0..rows)
+        .map(|row| {
+            (0..columns)
+                .map(|column| {
+                    let top_left = pixel_at(column, row);
+                    let bottom_right = pixel_at(column + 1, row + 1);
+                    let area = PixelArea {
+                        left: top_left[0] as u32,
+                        top: top_left[1] as u32,
+                        right: bottom_right[0] as u32,
+                        bottom: bottom_right[1] as u32,
+                    };
+                    sampling::sample_color(image, area, SamplingMode::Average)
+                        .map(|color| color.l)
+                        .unwrap_or(0.0)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// 3x3 binomial blur, so noise does not make edges. The borders repeat the nearest cell.
+fn blurred(values: &[Vec<f32>]) -> Vec<Vec<f32>> {
+    let rows = values.len() as isize;
+    let at = |r: isize, c: isize| {
+        let row = &values[r.clamp(0, rows - 1) as usize];
+        row[c.clamp(0, row.len() as isize - 1) as usize]
+    };
+    (0..rows)
+        .map(|r| {
+            (0..values[r as usize].len() as isize)
+                .map(|c| {
+                    let mut sum = 0.0;
+                    for (dr, row_weight) in [(-1, 1.0), (0, 2.0), (1, 1.0)] {
+                        for (dc, column_weight) in [(-1, 1.0), (0, 2.0), (1, 1.0)] {
+                            sum += row_weight * column_weight * at(r + dr, c + dc);
+                        }
+                    }
+                    sum / 16.0
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Sides first, then corners, so contours go straight when they can.
+fn get_neighbours(cell: [usize; 2], dimensions: [usize; 2]) -> Vec<[usize; 2]> {
+    [
+        [0, 1],
+        [1, 0],
+        [0, -1],
+        [-1, 0],
+        [1, 1],
+        [1, -1],
+        [-1, 1],
+        [-1, -1],
+    ]
+    .iter()
+    .filter_map(|[dr, dc]| {
+        let r = cell[0] as isize + dr;
+        let c = cell[1] as isize + dc;
+        if r < 0 || c < 0 || r as usize >= dimensions[0] || c as usize >= dimensions[1] {
+            None
+        } else {
+            Some([r as usize, c as usize])
+        }
+    })
+    .collect()
+}
+
+// Takes the cells it goes through out of the edges.
+fn follow_edge(is_edge: &mut [Vec<bool>], start: [usize; 2]) -> Vec<[usize; 2]> {
+    let dimensions = [is_edge.len(), is_edge[0].len()];
+    let mut cells = Vec::new();
+    let mut current = start;
+    while let Some([r, c]) = get_neighbours(current, dimensions)
+        .into_iter()
+        .find(|[r, c]| is_edge[*r][*c])
+    {
+        is_edge[r][c] = false;
+        cells.push([r, c]);
+        current = [r, c];
+    }
+    cells
+}
+
+// Moving average of three points, the ends stay.
+fn smooth(points: &[[f32; 2]]) -> Vec<[f32; 2]> {
+    (0..points.len())
+        .map(|i| {
+            if i == 0 || i == points.len() - 1 {
+                points[i]
+            } else {
+                [
+                    (points[i - 1][0] + points[i][0] + points[i + 1][0]) / 3.0,
+                    (points[i - 1][1] + points[i][1] + points[i + 1][1]) / 3.0,
+                ]
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn settings_with_cells_of(cell_size_mm: f32) -> EdgeDetectionSettings {
+        EdgeDetectionSettings {
+            cell_size_mm,
+            ..EdgeDetectionSettings::default()
+        }
+    }
+
+    #[test]
+    fn a_plain_image_has_no_contours() {
+        let image = image::Rgba32FImage::from_pixel(50, 50, image::Rgba([0.3, 0.6, 0.9, 1.0]));
+        assert!(find_contours(&image, [100, 100], &settings_with_cells_of(5.0)).is_empty());
+    }
+
+    #[test]
+    fn the_outline_of_a_square_is_one_closed_contour() {
+        let image = image::Rgba32FImage::from_fn(100, 100, |x, y| {
+            if (30..70).contains(&x) && (30..70).contains(&y) {
+                image::Rgba([1.0, 1.0, 1.0, 1.0])
+            } else {
+                image::Rgba([0.0, 0.0, 0.0, 1.0])
+            }
+        });
+        let contours = find_contours(&image, [100, 100], &settings_with_cells_of(2.0));
+        assert_eq!(contours.len(), 1);
+        let contour = &contours[0];
+        assert!(contour.len() > 40);
+        // Every point is near the sides of the square.
+        assert!(contour.iter().all(|[x, y]| {
+            let near = |value: f32| (value - 30.0).abs() < 3.0 || (value - 70.0).abs() < 3.0;
+            (near(*x) && (26.0..74.0).contains(y)) || (near(*y) && (26.0..74.0).contains(x))
+        }));
+        let first = contour[0];
+        let last = contour[contour.len() - 1];
+        assert!((first[0] - last[0]).abs() <= 4.0 && (first[1] - last[1]).abs() <= 4.0);
+    }
+
+    #[test]
+    fn weak_edges_are_kept_only_when_connected_to_strong_ones() {
+        // A step across the middle, strong on the left and fading towards the right.
+        let image = image::Rgba32FImage::from_fn(100, 100, |x, y| {
+            let l = if y < 50 {
+                0.0
+            } else {
+                // Cubed, so the perceived lightness fades evenly.
+                (1.0 - x as f32 / 100.0).powi(3)
+            };
+            image::Rgba([l, l, l, 1.0])
+        });
+        let settings = settings_with_cells_of(2.0);
+        let contours = find_contours(&image, [100, 100], &settings);
+        assert_eq!(contours.len(), 1);
+        assert!(contours[0].iter().any(|[x, _y]| *x > 60.0));
+
+        let only_strong = EdgeDetectionSettings {
+            low_threshold: settings.high_threshold,
+            ..settings
+        };
+        let contours = find_contours(&image, [100, 100], &only_strong);
+        assert!(contours.iter().flatten().all(|[x, _y]| *x < 55.0));
+    }
+}
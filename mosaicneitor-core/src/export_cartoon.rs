@@ -0,0 +1,448 @@
+//! The cartoon is the full scale drawing of the mosaic, split across paper pages to print.
+//!
+//! For the indirect (reverse) method tesserae are glued face down on it,
+//! so it has to be printed mirrored.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    export_svg,
+    mosaic::{Mosaic, Tessera, TesseraPlacement},
+    tessera_shape::TesseraShape,
+    tile_palette::TilePalette,
+};
+
+/// Standard paper sizes.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum PaperSize {
+    /// 210x297 mm
+    A4,
+    /// 297x420 mm
+    A3,
+    /// 8.5x11 in
+    Letter,
+}
+
+impl PaperSize {
+    /// Every size, to list them on menus.
+    pub const ALL: [PaperSize; 3] = [PaperSize::A4, PaperSize::A3, PaperSize::Letter];
+
+    /// Portrait, in mm.
+    pub fn get_dimensions(&self) -> [f32; 2] {
+        match self {
+            PaperSize::A4 => [210.0, 297.0],
+            PaperSize::A3 => [297.0, 420.0],
+            PaperSize::Letter => [215.9, 279.4],
+        }
+    }
+
+    /// As printers call it.
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            PaperSize::A4 => "A4",
+            PaperSize::A3 => "A3",
+            PaperSize::Letter => "Letter",
+        }
+    }
+}
+
+/// How to split the cartoon into pages.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct CartoonSettings {
+    /// Of every page.
+    pub paper_size: PaperSize,
+    /// Otherwise portrait.
+    pub landscape: bool,
+    /// Blank border around each page, printers cannot print there.
+    pub margin_mm: f32,
+    /// The strip of mosaic repeated on two neighbour pages, to glue or tape them together.
+    pub overlap_mm: f32,
+    /// Flipped left to right, for the indirect method.
+    pub mirrored: bool,
+}
+
+impl Default for CartoonSettings {
+    fn default() -> Self {
+        Self {
+            paper_size: PaperSize::A4,
+            landscape: false,
+            margin_mm: 10.0,
+            overlap_mm: 10.0,
+            mirrored: false,
+        }
+    }
+}
+
+impl CartoonSettings {
+    /// In mm, with the orientation applied.
+    pub fn get_paper_dimensions(&self) -> [f32; 2] {
+        let [short_side, long_side] = self.paper_size.get_dimensions();
+        if self.landscape {
+            [long_side, short_side]
+        } else {
+            [short_side, long_side]
+        }
+    }
+
+    /// The part of the page where the mosaic is drawn, in mm.
+    pub fn get_printable_dimensions(&self) -> [f32; 2] {
+        self.get_paper_dimensions()
+            .map(|side| (side - 2.0 * self.margin_mm).max(1.0))
+    }
+
+    // How much the mosaic advances from one page to the next one.
+    fn get_step(&self) -> [f32; 2] {
+        self.get_printable_dimensions()
+            .map(|side| (side - self.overlap_mm.max(0.0)).max(1.0))
+    }
+}
+
+/// A page of the cartoon.
+pub struct CartoonPage {
+    /// Starting at 1, from the top.
+    pub row: usize,
+    /// Starting at 1, from the left.
+    pub column: usize,
+    /// The whole page, in mm.
+    pub svg: String,
+}
+
+const LABEL_FONT_SIZE_MM: f32 = 4.0;
+const REGISTRATION_MARK_SIZE_MM: f32 = 4.0;
+
+/// How many pages are needed to cover the mosaic, [columns, rows].
+pub fn get_number_of_pages(mosaic_size: [f32; 2], settings: &CartoonSettings) -> [usize; 2] {
+    let printable = settings.get_printable_dimensions();
+    let step = settings.get_step();
+    [0, 1].map(|axis| {
+        if mosaic_size[axis] <= printable[axis] {
+            1
+        } else {
+            ((mosaic_size[axis] - printable[axis]) / step[axis]).ceil() as usize + 1
+        }
+    })
+}
+
+/// Every page, row by row, with registration marks on the overlap strips and a label.
+pub fn generate_cartoon_pages(
+    mosaic: &Mosaic,
+    gap_between_tesserae: usize,
+    tile_palette: &TilePalette,
+    settings: &CartoonSettings,
+) -> Vec<CartoonPage> {
+    let overall_size = mosaic.get_overall_size_in_mm(gap_between_tesserae);
+    let mosaic_size = [overall_size.horizontal as f32, overall_size.vertical as f32];
+    let [columns, rows] = get_number_of_pages(mosaic_size, settings);
+    let paper = settings.get_paper_dimensions();
+    let printable = settings.get_printable_dimensions();
+    let step = settings.get_step();
+    let margin = settings.margin_mm;
+
+    let tesserae: Vec<_> = mosaic
+        .get_tesserae_with_placements(gap_between_tesserae)
+        .into_iter()
+        .filter(|(_position, tessera, _placement)| tessera.shape != TesseraShape::Empty)
+        .map(|(position, tessera, placement)| {
+            if settings.mirrored {
+                let placement = TesseraPlacement {
+                    origin: [
+                        overall_size.horizontal - placement.origin[0] - placement.size.horizontal,
+                        placement.origin[1],
+                    ],
+                    size: placement.size,
+                };
+                let tessera = Tessera {
+                    shape: tessera.shape.mirrored(&tessera.size),
+                    ..tessera.clone()
+                };
+                (position, tessera, placement)
+            } else {
+                (position, tessera.clone(), placement)
+            }
+        })
+        .collect();
+    let free_tesserae: Vec<_> = mosaic
+        .get_free_tesserae()
+        .iter()
+        .enumerate()
+        .map(|(index, free_tessera)| {
+            let mut polygon = free_tessera.get_polygon();
+            if settings.mirrored {
+                // Reversed too, to keep it clockwise.
+                polygon = polygon
+                    .iter()
+                    .rev()
+                    .map(|[x, y]| [overall_size.horizontal as f32 - x, *y])
+                    .collect();
+            }
+            (index, &free_tessera.tessera, polygon)
+        })
+        .collect();
+
+    let mut pages = Vec::new();
+    for row in 1..=rows {
+        for column in 1..=columns {
+            // The part of the mosaic shown in this page.
+            let left = (column - 1) as f32 * step[0];
+            let top = (row - 1) as f32 * step[1];
+            let right = left + printable[0];
+            let bottom = top + printable[1];
+
+            let mut svg = String::new();
+            svg.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+            svg.push_str(&format!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}mm\" height=\"{h}mm\" viewBox=\"0 0 {w} {h}\">\n",
+                w = paper[0],
+                h = paper[1]
+            ));
+            svg.push_str(&format!(
+                "<defs><clipPath id=\"printable\"><rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/></clipPath></defs>\n",
+                margin, margin, printable[0], printable[1]
+            ));
+            svg.push_str(&format!(
+                "<g clip-path=\"url(#printable)\"><g transform=\"translate({} {})\">\n",
+                margin - left,
+                margin - top
+            ));
+            for (position, tessera, placement) in &tesserae {
+                let tessera_left = placement.origin[0] as f32;
+                let tessera_top = placement.origin[1] as f32;
+                let tessera_right = tessera_left + placement.size.horizontal as f32;
+                let tessera_bottom = tessera_top + placement.size.vertical as f32;
+                if tessera_right > left
+                    && tessera_left < right
+                    && tessera_bottom > top
+                    && tessera_top < bottom
+                {
+                    svg.push_str(&export_svg::tessera_element(
+                        position,
+                        tessera,
+                        placement,
+                        tile_palette,
+                    ));
+                    svg.push('\n');
+                }
+            }
+            for (index, tessera, polygon) in &free_tesserae {
+                if polygon.iter().any(|[x, _y]| *x > left)
+                    && polygon.iter().any(|[x, _y]| *x < right)
+                    && polygon.iter().any(|[_x, y]| *y > top)
+                    && polygon.iter().any(|[_x, y]| *y < bottom)
+                {
+                    svg.push_str(&export_svg::free_tessera_element(
+                        *index,
+                        tessera,
+                        polygon,
+                        tile_palette,
+                    ));
+                    svg.push('\n');
+                }
+            }
+            svg.push_str("</g></g>\n");
+            svg.push_str(&format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"#000000\" stroke-width=\"0.2\"/>\n",
+                margin, margin, printable[0], printable[1]
+            ));
+
+            // Marks in the middle of the overlap strips. Neighbour pages have
+            // the same marks at the same point of the mosaic, put one on top of the other.
+            let overlap = settings.overlap_mm.max(0.0);
+            let mut vertical_strips = Vec::new();
+            if column > 1 {
+                vertical_strips.push(margin + overlap / 2.0);
+            }
+            if column < columns {
+                vertical_strips.push(margin + step[0] + overlap / 2.0);
+            }
+            let mut horizontal_strips = Vec::new();
+            if row > 1 {
+                horizontal_strips.push(margin + overlap / 2.0);
+            }
+            if row < rows {
+                horizontal_strips.push(margin + step[1] + overlap / 2.0);
+            }
+            let near_the_ends = |length: f32| {
+                [
+                    margin + REGISTRATION_MARK_SIZE_MM * 2.0,
+                    margin + length - REGISTRATION_MARK_SIZE_MM * 2.0,
+                ]
+            };
+            for x in &vertical_strips {
+                for y in near_the_ends(printable[1]) {
+                    svg.push_str(&registration_mark(*x, y));
+                }
+            }
+            for y in &horizontal_strips {
+                for x in near_the_ends(printable[0]) {
+                    svg.push_str(&registration_mark(x, *y));
+                }
+            }
+
+            svg.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"{}\">{}</text>\n",
+                margin,
+                margin - 2.0,
+                LABEL_FONT_SIZE_MM,
+                export_svg::escape_xml(&format!(
+                    "Row {row}/{rows} - Column {column}/{columns}{}",
+                    if settings.mirrored { " - MIRRORED" } else { "" }
+                ))
+            ));
+            svg.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"{}\" text-anchor=\"end\">R{row}C{column}</text>\n",
+                paper[0] - margin,
+                paper[1] - margin + LABEL_FONT_SIZE_MM + 1.0,
+                LABEL_FONT_SIZE_MM,
+            ));
+            svg.push_str("</svg>\n");
+            pages.push(CartoonPage { row, column, svg });
+        }
+    }
+    pages
+}
+
+fn registration_mark(x: f32, y: f32) -> String {
+    let size = REGISTRATION_MARK_SIZE_MM;
+    format!(
+        "<g class=\"registration-mark\" stroke=\"#000000\" stroke-width=\"0.2\" fill=\"none\"><circle cx=\"{x}\" cy=\"{y}\" r=\"{}\"/><line x1=\"{}\" y1=\"{y}\" x2=\"{}\" y2=\"{y}\"/><line x1=\"{x}\" y1=\"{}\" x2=\"{x}\" y2=\"{}\"/></g>\n",
+        size / 2.0,
+        x - size,
+        x + size,
+        y - size,
+        y + size
+    )
+}
+
+/// From "some/folder/cartoon.svg" to "some/folder/cartoon_r1_c2.svg"
+pub fn get_page_file_path(
+    base_path: &std::path::Path,
+    row: usize,
+    column: usize,
+) -> std::path::PathBuf {
+    let stem = match base_path.file_stem() {
+        Some(stem) => stem.to_string_lossy().to_string(),
+        None => String::from("cartoon"),
+    };
+    base_path.with_file_name(format!("{stem}_r{row}_c{column}.svg"))
+}
+
+/// Writes every page to its own file, see [`get_page_file_path`]. Yields the files written,
+/// row by row.
+pub fn export_cartoon(
+    mosaic: &Mosaic,
+    gap_between_tesserae: usize,
+    tile_palette: &TilePalette,
+    settings: &CartoonSettings,
+    base_path: &std::path::Path,
+) -> Result<Vec<std::path::PathBuf>, String> {
+    let pages = generate_cartoon_pages(mosaic, gap_between_tesserae, tile_palette, settings);
+    let mut written = Vec::new();
+    for page in &pages {
+        let path = get_page_file_path(base_path, page.row, page.column);
+        if let Err(e) = std::fs::write(&path, &page.svg) {
+            return Err(format!("Cannot write {}: {e}", path.display()));
+        }
+        written.push(path);
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{colors, mosaic::a_mosaic_of_colors};
+
+    // A 499x299 mm mosaic (with 1 mm gaps). The first column is red, the rest white.
+    fn a_half_meter_mosaic() -> Mosaic {
+        let mut row = vec![colors::oklch_from_srgb8([255, 255, 255]); 50];
+        row[0] = colors::oklch_from_srgb8([255, 0, 0]);
+        a_mosaic_of_colors([9, 9], vec![row; 30])
+    }
+
+    #[test]
+    fn the_mosaic_is_tiled_over_enough_pages() {
+        let settings = CartoonSettings::default();
+        // Printable 190x277, advancing 180x267 from page to page.
+        assert_eq!(get_number_of_pages([100.0, 100.0], &settings), [1, 1]);
+        assert_eq!(get_number_of_pages([190.0, 277.0], &settings), [1, 1]);
+        assert_eq!(get_number_of_pages([191.0, 278.0], &settings), [2, 2]);
+        assert_eq!(get_number_of_pages([499.0, 299.0], &settings), [3, 2]);
+
+        let landscape = CartoonSettings {
+            landscape: true,
+            ..CartoonSettings::default()
+        };
+        assert_eq!(get_number_of_pages([499.0, 299.0], &landscape), [2, 2]);
+    }
+
+    #[test]
+    fn every_page_is_labelled_and_has_the_paper_size() {
+        let pages = generate_cartoon_pages(
+            &a_half_meter_mosaic(),
+            1,
+            &TilePalette::default(),
+            &CartoonSettings::default(),
+        );
+        assert_eq!(pages.len(), 6);
+        let last = &pages[5];
+        assert_eq!((last.row, last.column), (2, 3));
+        assert!(last.svg.contains("width=\"210mm\" height=\"297mm\""));
+        assert!(last.svg.contains("Row 2/2 - Column 3/3"));
+    }
+
+    #[test]
+    fn registration_marks_are_only_on_the_sides_with_a_neighbour_page() {
+        let pages = generate_cartoon_pages(
+            &a_half_meter_mosaic(),
+            1,
+            &TilePalette::default(),
+            &CartoonSettings::default(),
+        );
+        let marks = |page: &CartoonPage| page.svg.matches("registration-mark").count();
+        // Top left page: neighbours at right and below.
+        assert_eq!(marks(&pages[0]), 4);
+        // Top middle page: neighbours at left, right and below.
+        assert_eq!(marks(&pages[1]), 6);
+    }
+
+    #[test]
+    fn pages_contain_only_the_tesserae_they_show() {
+        let pages = generate_cartoon_pages(
+            &a_half_meter_mosaic(),
+            1,
+            &TilePalette::default(),
+            &CartoonSettings::default(),
+        );
+        assert!(pages[0].svg.contains("data-row=\"1\" data-column=\"1\""));
+        assert!(!pages[2].svg.contains("data-column=\"1\""));
+        assert!(pages[2].svg.contains("data-column=\"50\""));
+    }
+
+    #[test]
+    fn mirrored_cartoons_put_the_first_column_on_the_right() {
+        let settings = CartoonSettings {
+            mirrored: true,
+            ..CartoonSettings::default()
+        };
+        let pages = generate_cartoon_pages(
+            &a_half_meter_mosaic(),
+            1,
+            &TilePalette::default(),
+            &settings,
+        );
+        assert!(!pages[0].svg.contains("#ff0000"));
+        assert!(pages[2].svg.contains("#ff0000"));
+        assert!(pages[2]
+            .svg
+            .contains("<rect x=\"490\" y=\"0\" width=\"9\" height=\"9\" fill=\"#ff0000\""));
+        assert!(pages[0].svg.contains("MIRRORED"));
+    }
+
+    #[test]
+    fn page_files_are_named_after_their_row_and_column() {
+        assert_eq!(
+            get_page_file_path(std::path::Path::new("some/folder/cartoon.svg"), 1, 2),
+            std::path::PathBuf::from("some/folder/cartoon_r1_c2.svg")
+        );
+    }
+}
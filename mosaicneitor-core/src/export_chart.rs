@@ -0,0 +1,426 @@
+//! A color-by-number chart: one cell per tessera, with the key of its color,
+//! so you can follow it at the workshop, row by row.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    colors, export_svg, materials,
+    mosaic::{Mosaic, Tessera},
+    tessera_shape::TesseraShape,
+    tile_palette::TilePalette,
+};
+
+/// What to print on every cell.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum ChartKeys {
+    /// The palette entry number.
+    Numbers,
+    /// A symbol per color, easier to tell apart at a glance.
+    Symbols,
+}
+
+/// How to draw the chart.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct ChartSettings {
+    /// Numbers or symbols.
+    pub keys: ChartKeys,
+    /// Paint the cells with the tessera color, or print them black on white.
+    pub colored_cells: bool,
+}
+
+impl Default for ChartSettings {
+    fn default() -> Self {
+        Self {
+            keys: ChartKeys::Numbers,
+            colored_cells: true,
+        }
+    }
+}
+
+const SYMBOLS: [char; 24] = [
+    '●', '■', '▲', '◆', '★', '✚', '✖', '♥', '♣', '♠', '○', '□', '△', '◇', '☆', '◐', '◑', '▼', '►',
+    '◄', '♦', '☀', '☂', '♪',
+];
+
+/// A color used in the chart.
+#[derive(Debug, PartialEq)]
+pub struct ChartLegendEntry {
+    /// The number or symbol printed on its cells.
+    pub key: String,
+    /// The color, [red, green, blue], 0 to 255.
+    pub srgb: [u8; 3],
+    /// None if the color is not in the palette.
+    pub palette_entry_name: Option<String>,
+    /// How many cells have it.
+    pub tesserae_count: usize,
+}
+
+/// The chart contents, before drawing it.
+#[derive(Debug, PartialEq)]
+pub struct Chart {
+    /// Sorted by key number.
+    pub legend: Vec<ChartLegendEntry>,
+    /// Index in the legend of every tessera, like [`Mosaic::get_contents`]. None for empty cells.
+    pub cells: Vec<Vec<Option<usize>>>,
+    /// Index in the legend of every free tessera, like [`Mosaic::get_free_tesserae`].
+    /// They are out of the grid, so they are drawn apart, below it.
+    pub free_cells: Vec<usize>,
+}
+
+impl Chart {
+    /// Colors in the palette get the number of their entry (starting at 1), so
+    /// charts made with the same palette share keys. The others are numbered after them.
+    pub fn new(mosaic: &Mosaic, tile_palette: &TilePalette, keys: ChartKeys) -> Self {
+        let mut numbered_colors: Vec<(usize, [u8; 3], Option<String>, usize)> = Vec::new();
+        let mut next_number_out_of_palette = tile_palette.entries.len() + 1;
+        let mut number_of = |tessera: &Tessera| {
+            let srgb = colors::srgb8_from_oklch(tessera.color);
+            match numbered_colors.iter_mut().find(|c| c.1 == srgb) {
+                Some(color) => {
+                    color.3 += 1;
+                    color.0
+                }
+                None => {
+                    let palette_entry = tile_palette
+                        .entries
+                        .iter()
+                        .enumerate()
+                        .find(|(_index, e)| e.srgb == srgb);
+                    let (number, name) = match palette_entry {
+                        Some((index, entry)) => (index + 1, Some(entry.name.clone())),
+                        None => {
+                            next_number_out_of_palette += 1;
+                            (next_number_out_of_palette - 1, None)
+                        }
+                    };
+                    numbered_colors.push((number, srgb, name, 1));
+                    number
+                }
+            }
+        };
+        let cells_by_number: Vec<Vec<Option<usize>>> = mosaic
+            .get_contents()
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|tessera| {
+                        (tessera.shape != TesseraShape::Empty).then(|| number_of(tessera))
+                    })
+                    .collect()
+            })
+            .collect();
+        let free_cells_by_number: Vec<usize> = mosaic
+            .get_free_tesserae()
+            .iter()
+            .map(|free_tessera| number_of(&free_tessera.tessera))
+            .collect();
+        numbered_colors.sort_by_key(|c| c.0);
+
+        let index_of = |number: usize| {
+            numbered_colors
+                .iter()
+                .position(|c| c.0 == number)
+                .unwrap_or_default()
+        };
+        let cells = cells_by_number
+            .into_iter()
+            .map(|row| row.into_iter().map(|number| number.map(index_of)).collect())
+            .collect();
+        let free_cells = free_cells_by_number.into_iter().map(index_of).collect();
+        let legend = numbered_colors
+            .into_iter()
+            .map(
+                |(number, srgb, palette_entry_name, tesserae_count)| ChartLegendEntry {
+                    key: get_key(number, keys),
+                    srgb,
+                    palette_entry_name,
+                    tesserae_count,
+                },
+            )
+            .collect();
+        Self {
+            legend,
+            cells,
+            free_cells,
+        }
+    }
+
+    /// The grid of cells with rulers numbered like [`crate::mosaic::PositionOnGrid`], and the legend below.
+    pub fn to_svg(&self, colored_cells: bool) -> String {
+        let rows = self.cells.len();
+        let columns = self.cells.iter().map(|row| row.len()).max().unwrap_or(0);
+        let grid_left = RULER_SIZE;
+        let grid_top = RULER_SIZE;
+        let grid_width = columns as f32 * CELL_SIZE;
+        let grid_height = rows as f32 * CELL_SIZE;
+        // Free tesserae go in lines as wide as the grid, below it.
+        let free_columns = columns.max(FREE_CELLS_MIN_COLUMNS);
+        let free_top = grid_top + grid_height + CELL_SIZE;
+        let free_height = self.free_cells.len().div_ceil(free_columns) as f32 * CELL_SIZE;
+        let legend_top = if self.free_cells.is_empty() {
+            free_top
+        } else {
+            free_top + free_height + CELL_SIZE
+        };
+        let width = (grid_left + grid_width.max(free_columns as f32 * CELL_SIZE) + RULER_SIZE)
+            .max(LEGEND_MIN_WIDTH);
+        let height = legend_top + self.legend.len() as f32 * LEGEND_LINE_HEIGHT + CELL_SIZE;
+
+        let cell = |legend_index: usize, x: f32, y: f32| {
+            let entry = &self.legend[legend_index];
+            let (fill, text_color) = if colored_cells {
+                (
+                    materials::hex_color(entry.srgb),
+                    text_color_over(entry.srgb),
+                )
+            } else {
+                (String::from("#ffffff"), "#000000")
+            };
+            format!(
+                "<rect x=\"{x}\" y=\"{y}\" width=\"{CELL_SIZE}\" height=\"{CELL_SIZE}\" fill=\"{fill}\" stroke=\"#808080\" stroke-width=\"0.2\"/><text x=\"{}\" y=\"{}\" fill=\"{text_color}\">{}</text>\n",
+                x + CELL_SIZE / 2.0,
+                y + CELL_SIZE / 2.0 + 1.8,
+                export_svg::escape_xml(&entry.key)
+            )
+        };
+
+        let mut svg = String::new();
+        svg.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        svg.push_str(&format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\" font-family=\"sans-serif\" text-anchor=\"middle\">\n"
+        ));
+        svg.push_str(&format!(
+            "<rect x=\"0\" y=\"0\" width=\"{width}\" height=\"{height}\" fill=\"#ffffff\"/>\n"
+        ));
+
+        // Rulers, numbered like PositionOnGrid.
+        svg.push_str("<g id=\"rulers\" font-size=\"4\">\n");
+        for column in 1..=columns {
+            let x = grid_left + (column as f32 - 0.5) * CELL_SIZE;
+            svg.push_str(&format!(
+                "<text x=\"{x}\" y=\"{}\">{column}</text>\n<text x=\"{x}\" y=\"{}\">{column}</text>\n",
+                grid_top - 2.0,
+                grid_top + grid_height + 5.0
+            ));
+        }
+        for row in 1..=rows {
+            let y = grid_top + (row as f32 - 0.5) * CELL_SIZE + 1.5;
+            svg.push_str(&format!(
+                "<text x=\"{}\" y=\"{y}\">{row}</text>\n<text x=\"{}\" y=\"{y}\">{row}</text>\n",
+                grid_left / 2.0,
+                grid_left + grid_width + RULER_SIZE / 2.0
+            ));
+        }
+        svg.push_str("</g>\n");
+
+        svg.push_str("<g id=\"cells\" font-size=\"5\">\n");
+        for (row_index, row) in self.cells.iter().enumerate() {
+            // Empty cells are left blank, as the grout.
+            for (column_index, legend_index) in row.iter().enumerate() {
+                if let Some(legend_index) = legend_index {
+                    svg.push_str(&cell(
+                        *legend_index,
+                        grid_left + column_index as f32 * CELL_SIZE,
+                        grid_top + row_index as f32 * CELL_SIZE,
+                    ));
+                }
+            }
+        }
+        svg.push_str("</g>\n");
+
+        if !self.free_cells.is_empty() {
+            svg.push_str("<g id=\"free\" font-size=\"5\">\n");
+            for (index, legend_index) in self.free_cells.iter().enumerate() {
+                svg.push_str(&cell(
+                    *legend_index,
+                    grid_left + (index % free_columns) as f32 * CELL_SIZE,
+                    free_top + (index / free_columns) as f32 * CELL_SIZE,
+                ));
+            }
+            svg.push_str("</g>\n");
+        }
+
+        // A thicker line every 10 cells, to help counting.
+        svg.push_str("<g id=\"guides\" stroke=\"#000000\" stroke-width=\"0.6\">\n");
+        for column in (0..=columns).step_by(10) {
+            let x = grid_left + column as f32 * CELL_SIZE;
+            svg.push_str(&format!(
+                "<line x1=\"{x}\" y1=\"{grid_top}\" x2=\"{x}\" y2=\"{}\"/>\n",
+                grid_top + grid_height
+            ));
+        }
+        for row in (0..=rows).step_by(10) {
+            let y = grid_top + row as f32 * CELL_SIZE;
+            svg.push_str(&format!(
+                "<line x1=\"{grid_left}\" y1=\"{y}\" x2=\"{}\" y2=\"{y}\"/>\n",
+                grid_left + grid_width
+            ));
+        }
+        svg.push_str("</g>\n");
+
+        svg.push_str("<g id=\"legend\" font-size=\"5\" text-anchor=\"start\">\n");
+        for (index, entry) in self.legend.iter().enumerate() {
+            let y = legend_top + index as f32 * LEGEND_LINE_HEIGHT;
+            let name = match &entry.palette_entry_name {
+                Some(name) => format!("{name} ({})", materials::hex_color(entry.srgb)),
+                None => materials::hex_color(entry.srgb),
+            };
+            svg.push_str(&format!(
+                "<rect x=\"{RULER_SIZE}\" y=\"{y}\" width=\"{CELL_SIZE}\" height=\"{CELL_SIZE}\" fill=\"{}\" stroke=\"#808080\" stroke-width=\"0.2\"/><text x=\"{}\" y=\"{}\">{}    {}    x {}</text>\n",
+                materials::hex_color(entry.srgb),
+                RULER_SIZE + CELL_SIZE + 3.0,
+                y + CELL_SIZE / 2.0 + 1.8,
+                export_svg::escape_xml(&entry.key),
+                export_svg::escape_xml(&name),
+                entry.tesserae_count
+            ));
+        }
+        svg.push_str("</g>\n</svg>\n");
+        svg
+    }
+}
+
+const CELL_SIZE: f32 = 8.0;
+const RULER_SIZE: f32 = 10.0;
+const LEGEND_LINE_HEIGHT: f32 = 10.0;
+const LEGEND_MIN_WIDTH: f32 = 150.0;
+const FREE_CELLS_MIN_COLUMNS: usize = 10;
+
+fn get_key(number: usize, keys: ChartKeys) -> String {
+    match keys {
+        // When there are more colors than symbols, the rest keep their number.
+        ChartKeys::Symbols if number <= SYMBOLS.len() => SYMBOLS[number - 1].to_string(),
+        _ => number.to_string(),
+    }
+}
+
+fn text_color_over(srgb: [u8; 3]) -> &'static str {
+    if colors::oklch_from_srgb8(srgb).l > 0.6 {
+        "#000000"
+    } else {
+        "#ffffff"
+    }
+}
+
+/// Writes the chart to an SVG file.
+pub fn export_chart(
+    mosaic: &Mosaic,
+    tile_palette: &TilePalette,
+    settings: &ChartSettings,
+    path: &std::path::Path,
+) -> Result<(), String> {
+    let svg = Chart::new(mosaic, tile_palette, settings.keys).to_svg(settings.colored_cells);
+    match std::fs::write(path, svg) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Cannot write {}: {e}", path.display())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mosaic::{a_mosaic_of_colors, FreeTessera, RectangleInMm};
+
+    // Two rows: red, white, blue / blue, blue, red
+    fn a_2x3_mosaic() -> Mosaic {
+        a_mosaic_of_colors(
+            [10, 10],
+            [
+                [[255, 0, 0], [255, 255, 255], [0, 0, 255]],
+                [[0, 0, 255], [0, 0, 255], [255, 0, 0]],
+            ]
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|srgb| colors::oklch_from_srgb8(*srgb))
+                    .collect()
+            })
+            .collect(),
+        )
+    }
+
+    #[test]
+    fn palette_colors_keep_their_palette_number_and_the_rest_go_after() {
+        let mut tile_palette = TilePalette::default();
+        tile_palette.add_entry([0, 0, 0]);
+        tile_palette.add_entry([0, 0, 255]);
+        let chart = Chart::new(&a_2x3_mosaic(), &tile_palette, ChartKeys::Numbers);
+        let keys: Vec<&str> = chart.legend.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, ["2", "3", "4"]);
+        assert_eq!(chart.legend[0].srgb, [0, 0, 255]);
+        assert_eq!(chart.legend[0].tesserae_count, 3);
+        assert_eq!(chart.legend[1].srgb, [255, 0, 0]);
+        assert_eq!(chart.legend[1].tesserae_count, 2);
+        assert_eq!(chart.legend[2].srgb, [255, 255, 255]);
+        assert_eq!(chart.legend[2].tesserae_count, 1);
+        assert_eq!(
+            chart.cells,
+            vec![
+                vec![Some(1), Some(2), Some(0)],
+                vec![Some(0), Some(0), Some(1)]
+            ]
+        );
+        assert!(chart.free_cells.is_empty());
+    }
+
+    #[test]
+    fn free_tesserae_are_counted_and_drawn_below_the_grid() {
+        let mut mosaic = a_2x3_mosaic();
+        for srgb in [[0, 255, 0], [255, 0, 0]] {
+            mosaic.add_free_tessera(FreeTessera {
+                tessera: Tessera {
+                    color: colors::oklch_from_srgb8(srgb),
+                    size: RectangleInMm {
+                        horizontal: 10,
+                        vertical: 10,
+                    },
+                    shape: TesseraShape::Whole,
+                },
+                center_mm: [5.0, 5.0],
+                rotation_degrees: 30.0,
+            });
+        }
+        let chart = Chart::new(&mosaic, &TilePalette::default(), ChartKeys::Numbers);
+        assert_eq!(chart.legend.len(), 4);
+        assert_eq!(chart.legend[0].srgb, [255, 0, 0]);
+        assert_eq!(chart.legend[0].tesserae_count, 3);
+        assert_eq!(chart.legend[3].srgb, [0, 255, 0]);
+        assert_eq!(chart.legend[3].tesserae_count, 1);
+        assert_eq!(chart.free_cells, vec![3, 0]);
+        let total: usize = chart.legend.iter().map(|e| e.tesserae_count).sum();
+        assert_eq!(total, mosaic.get_all_tesserae().count());
+
+        let svg = chart.to_svg(true);
+        let free = svg
+            .split("<g id=\"free\"")
+            .nth(1)
+            .unwrap()
+            .split("</g>")
+            .next()
+            .unwrap();
+        assert_eq!(free.matches("<rect").count(), 2);
+    }
+
+    #[test]
+    fn symbols_are_used_while_there_are_enough() {
+        assert_eq!(get_key(1, ChartKeys::Symbols), "●");
+        assert_eq!(get_key(1, ChartKeys::Numbers), "1");
+        assert_eq!(get_key(SYMBOLS.len() + 1, ChartKeys::Symbols), "25");
+    }
+
+    #[test]
+    fn the_svg_has_a_cell_per_tessera_rulers_and_legend() {
+        let chart = Chart::new(&a_2x3_mosaic(), &TilePalette::default(), ChartKeys::Numbers);
+        let svg = chart.to_svg(false);
+        let cells = svg
+            .split("<g id=\"cells\"")
+            .nth(1)
+            .unwrap()
+            .split("</g>")
+            .next()
+            .unwrap();
+        assert_eq!(cells.matches("<rect").count(), 6);
+        assert!(svg.contains("<g id=\"rulers\""));
+        assert!(svg.contains("#0000ff    x 3</text>"));
+    }
+}
@@ -0,0 +1,276 @@
+//! Pictures of the mosaic, as PNG files.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    colors,
+    mosaic::Mosaic,
+    tessera_shape::{self, TesseraShape},
+};
+
+/// How to render the mosaic to a picture.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct RenderSettings {
+    /// Resolution of the picture.
+    pub pixels_per_mm: f32,
+    /// Color of the gaps between tesserae, [red, green, blue], 0 to 255.
+    pub grout_srgb: [u8; 3],
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            pixels_per_mm: 4.0,
+            grout_srgb: [200, 200, 195],
+        }
+    }
+}
+
+// The biggest side a PNG file can have.
+const MAX_PNG_SIDE_PIXELS: f64 = i32::MAX as f64;
+// About 750 MB in memory, far more than any print needs.
+const MAX_RENDER_PIXELS: f64 = 250_000_000.0;
+
+/// Software rendering, it does not need any GPU.
+/// Yields an error, before taking any memory, when the picture would be too big.
+pub fn render_mosaic(
+    mosaic: &Mosaic,
+    gap_between_tesserae: usize,
+    settings: &RenderSettings,
+) -> Result<image::RgbImage, String> {
+    let overall_size = mosaic.get_overall_size_in_mm(gap_between_tesserae);
+    if !settings.pixels_per_mm.is_finite() || settings.pixels_per_mm <= 0.0 {
+        return Err(format!(
+            "Wrong resolution: {} pixels per mm.",
+            settings.pixels_per_mm
+        ));
+    }
+    let [width, height] = [overall_size.horizontal, overall_size.vertical]
+        .map(|mm| (mm as f64 * settings.pixels_per_mm as f64).round().max(1.0));
+    if width > MAX_PNG_SIDE_PIXELS
+        || height > MAX_PNG_SIDE_PIXELS
+        || width * height > MAX_RENDER_PIXELS
+    {
+        return Err(format!(
+            "The picture would be too big, {width}x{height} pixels. Lower the pixels per mm."
+        ));
+    }
+    let to_pixels = |mm: usize| (mm as f32 * settings.pixels_per_mm).round() as u32;
+    let mut image =
+        image::RgbImage::from_pixel(width as u32, height as u32, image::Rgb(settings.grout_srgb));
+    for (_position, tessera, placement) in mosaic.get_tesserae_with_placements(gap_between_tesserae)
+    {
+        if tessera.shape == TesseraShape::Empty {
+            continue;
+        }
+        let color = image::Rgb(colors::srgb8_from_oklch(tessera.color));
+        let left = to_pixels(placement.origin[0]);
+        let top = to_pixels(placement.origin[1]);
+        let right = to_pixels(placement.origin[0] + placement.size.horizontal).min(image.width());
+        let bottom = to_pixels(placement.origin[1] + placement.size.vertical).min(image.height());
+        let is_cut = tessera.shape != TesseraShape::Whole;
+        for y in top..bottom {
+            for x in left..right {
+                // Cut tesserae are only painted where the middle of the pixel is inside them.
+                if is_cut
+                    && !tessera.shape.contains(
+                        &placement.size,
+                        [
+                            (x as f32 + 0.5) / settings.pixels_per_mm - placement.origin[0] as f32,
+                            (y as f32 + 0.5) / settings.pixels_per_mm - placement.origin[1] as f32,
+                        ],
+                    )
+                {
+                    continue;
+                }
+                image.put_pixel(x, y, color);
+            }
+        }
+    }
+    for free_tessera in mosaic.get_free_tesserae() {
+        let color = image::Rgb(colors::srgb8_from_oklch(free_tessera.tessera.color));
+        let polygon = free_tessera.get_polygon();
+        let bound = |axis: usize, pick: fn(f32, f32) -> f32, start: f32| {
+            polygon.iter().map(|vertex| vertex[axis]).fold(start, pick) * settings.pixels_per_mm
+        };
+        let left = bound(0, f32::min, f32::MAX).floor().max(0.0) as u32;
+        let top = bound(1, f32::min, f32::MAX).floor().max(0.0) as u32;
+        let right = (bound(0, f32::max, f32::MIN).ceil().max(0.0) as u32).min(image.width());
+        let bottom = (bound(1, f32::max, f32::MIN).ceil().max(0.0) as u32).min(image.height());
+        for y in top..bottom {
+            for x in left..right {
+                let point = [
+                    (x as f32 + 0.5) / settings.pixels_per_mm,
+                    (y as f32 + 0.5) / settings.pixels_per_mm,
+                ];
+                if tessera_shape::is_inside_convex_polygon(&polygon, point) {
+                    image.put_pixel(x, y, color);
+                }
+            }
+        }
+    }
+    Ok(image)
+}
+
+/// Renders the mosaic and writes it to a PNG file.
+pub fn export_mosaic_to_png(
+    mosaic: &Mosaic,
+    gap_between_tesserae: usize,
+    settings: &RenderSettings,
+    path: &std::path::Path,
+) -> Result<(), String> {
+    match render_mosaic(mosaic, gap_between_tesserae, settings)?
+        .save_with_format(path, image::ImageFormat::Png)
+    {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Cannot write {}: {e}", path.display())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        mosaic::{a_mosaic_of_colors, FreeTessera, PositionOnGrid, RectangleInMm, Tessera},
+        tessera_shape::CutPreset,
+    };
+
+    // 2 rows of 3 tesserae of 10x5 mm, red on the first row and blue on the second.
+    fn a_red_and_blue_mosaic() -> Mosaic {
+        a_mosaic_of_colors(
+            [10, 5],
+            [[255, 0, 0], [0, 0, 255]]
+                .iter()
+                .map(|srgb| vec![colors::oklch_from_srgb8(*srgb); 3])
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn the_image_has_the_mosaic_size_at_the_chosen_resolution() {
+        let settings = RenderSettings {
+            pixels_per_mm: 2.0,
+            grout_srgb: [0, 0, 0],
+        };
+        let image = render_mosaic(&a_red_and_blue_mosaic(), 1, &settings).unwrap();
+        assert_eq!(image.width(), (3 * 10 + 2) * 2);
+        assert_eq!(image.height(), (2 * 5 + 1) * 2);
+    }
+
+    #[test]
+    fn tesserae_are_painted_with_their_color_and_gaps_with_the_grout_color() {
+        let settings = RenderSettings {
+            pixels_per_mm: 2.0,
+            grout_srgb: [10, 20, 30],
+        };
+        let image = render_mosaic(&a_red_and_blue_mosaic(), 1, &settings).unwrap();
+        assert_eq!(image.get_pixel(0, 0).0, [255, 0, 0]);
+        assert_eq!(image.get_pixel(19, 9).0, [255, 0, 0]);
+        // The vertical gap between the first and second column.
+        assert_eq!(image.get_pixel(20, 0).0, [10, 20, 30]);
+        assert_eq!(image.get_pixel(21, 0).0, [10, 20, 30]);
+        assert_eq!(image.get_pixel(22, 0).0, [255, 0, 0]);
+        // The horizontal gap between rows.
+        assert_eq!(image.get_pixel(5, 10).0, [10, 20, 30]);
+        assert_eq!(image.get_pixel(5, 12).0, [0, 0, 255]);
+    }
+
+    #[test]
+    fn cut_tesserae_leave_the_rest_of_their_cell_with_the_grout_color() {
+        let mut mosaic = a_red_and_blue_mosaic();
+        let position = PositionOnGrid { row: 1, column: 1 };
+        let mut tessera = mosaic.get_tessera(&position).unwrap().clone();
+        tessera.shape = TesseraShape::Preset(CutPreset::HalfLeft);
+        mosaic.change_tessera(&position, tessera).unwrap();
+        let settings = RenderSettings {
+            pixels_per_mm: 2.0,
+            grout_srgb: [10, 20, 30],
+        };
+        let image = render_mosaic(&mosaic, 1, &settings).unwrap();
+        assert_eq!(image.get_pixel(9, 5).0, [255, 0, 0]);
+        assert_eq!(image.get_pixel(10, 5).0, [10, 20, 30]);
+    }
+
+    #[test]
+    fn free_tesserae_are_painted_rotated() {
+        let mut mosaic = Mosaic::new(
+            None,
+            RectangleInMm {
+                horizontal: 10,
+                vertical: 10,
+            },
+        );
+        mosaic.add_free_tessera(FreeTessera {
+            tessera: Tessera {
+                color: colors::oklch_from_srgb8([255, 0, 0]),
+                size: *mosaic.get_general_tessera_size(),
+                shape: TesseraShape::Whole,
+            },
+            center_mm: [10.0, 10.0],
+            rotation_degrees: 45.0,
+        });
+        let settings = RenderSettings {
+            pixels_per_mm: 1.0,
+            grout_srgb: [10, 20, 30],
+        };
+        let image = render_mosaic(&mosaic, 1, &settings).unwrap();
+        assert_eq!(image.width(), 18);
+        assert_eq!(image.get_pixel(10, 10).0, [255, 0, 0]);
+        assert_eq!(image.get_pixel(10, 4).0, [255, 0, 0]);
+        assert_eq!(image.get_pixel(5, 5).0, [10, 20, 30]);
+    }
+
+    #[test]
+    fn too_big_pictures_are_an_error() {
+        // A 2 m mural at 100 pixels per mm.
+        let mut mosaic = Mosaic::new(
+            None,
+            RectangleInMm {
+                horizontal: 2000,
+                vertical: 2000,
+            },
+        );
+        mosaic.add_a_row_of_tesserae(vec![Tessera {
+            color: colors::oklch_from_srgb8([255, 0, 0]),
+            size: *mosaic.get_general_tessera_size(),
+            shape: TesseraShape::Whole,
+        }]);
+        let settings = RenderSettings {
+            pixels_per_mm: 100.0,
+            grout_srgb: [0, 0, 0],
+        };
+        assert!(render_mosaic(&mosaic, 1, &settings).is_err());
+        let path = std::env::temp_dir().join("mosaicneitor-never-written.png");
+        assert!(export_mosaic_to_png(&mosaic, 1, &settings, &path).is_err());
+        assert!(!path.exists());
+
+        let no_resolution = RenderSettings {
+            pixels_per_mm: 0.0,
+            ..settings
+        };
+        assert!(render_mosaic(&mosaic, 1, &no_resolution).is_err());
+        let fine = RenderSettings {
+            pixels_per_mm: 1.0,
+            ..settings
+        };
+        assert!(render_mosaic(&mosaic, 1, &fine).is_ok());
+    }
+
+    #[test]
+    fn the_png_file_is_written() {
+        let path = std::env::temp_dir().join(format!(
+            "mosaicneitor-test-{}-render.png",
+            std::process::id()
+        ));
+        export_mosaic_to_png(
+            &a_red_and_blue_mosaic(),
+            1,
+            &RenderSettings::default(),
+            &path,
+        )
+        .unwrap();
+        let reloaded = image::open(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(reloaded.width(), 128);
+    }
+}
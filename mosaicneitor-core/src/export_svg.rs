@@ -0,0 +1,247 @@
+//! Drawings of the mosaic, as SVG files in millimetres.
+
+use crate::{
+    colors, materials,
+    mosaic::{Mosaic, PositionOnGrid, Tessera, TesseraPlacement},
+    tessera_shape::TesseraShape,
+    tile_palette::TilePalette,
+};
+
+/// One user unit is one millimetre, so the file prints at true scale.
+pub fn mosaic_to_svg(
+    mosaic: &Mosaic,
+    gap_between_tesserae: usize,
+    tile_palette: &TilePalette,
+    grout_srgb: [u8; 3],
+) -> String {
+    let overall_size = mosaic.get_overall_size_in_mm(gap_between_tesserae);
+    let mut svg = String::new();
+    svg.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    svg.push_str(&format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}mm\" height=\"{h}mm\" viewBox=\"0 0 {w} {h}\">\n",
+        w = overall_size.horizontal,
+        h = overall_size.vertical
+    ));
+    svg.push_str(&format!(
+        "<rect x=\"0\" y=\"0\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+        overall_size.horizontal,
+        overall_size.vertical,
+        materials::hex_color(grout_srgb)
+    ));
+    svg.push_str("<g id=\"tesserae\">\n");
+    for (position, tessera, placement) in mosaic.get_tesserae_with_placements(gap_between_tesserae)
+    {
+        if tessera.shape == TesseraShape::Empty {
+            continue;
+        }
+        svg.push_str(&tessera_element(
+            &position,
+            tessera,
+            &placement,
+            tile_palette,
+        ));
+        svg.push('\n');
+    }
+    for (index, free_tessera) in mosaic.get_free_tesserae().iter().enumerate() {
+        svg.push_str(&free_tessera_element(
+            index,
+            &free_tessera.tessera,
+            &free_tessera.get_polygon(),
+            tile_palette,
+        ));
+        svg.push('\n');
+    }
+    svg.push_str("</g>\n</svg>\n");
+    svg
+}
+
+/// A rect for the tessera (a polygon if it is cut), with its row and column,
+/// and its palette entry when the color is in the palette.
+pub fn tessera_element(
+    position: &PositionOnGrid,
+    tessera: &Tessera,
+    placement: &TesseraPlacement,
+    tile_palette: &TilePalette,
+) -> String {
+    let geometry = match tessera.shape {
+        TesseraShape::Whole => format!(
+            "rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"",
+            placement.origin[0],
+            placement.origin[1],
+            placement.size.horizontal,
+            placement.size.vertical,
+        ),
+        _ => polygon_geometry(
+            &tessera
+                .shape
+                .get_polygon(&placement.size)
+                .iter()
+                .map(|[x, y]| {
+                    [
+                        placement.origin[0] as f32 + x,
+                        placement.origin[1] as f32 + y,
+                    ]
+                })
+                .collect::<Vec<[f32; 2]>>(),
+        ),
+    };
+    format!(
+        "<{} fill=\"{}\" data-row=\"{}\" data-column=\"{}\"{}/>",
+        geometry,
+        materials::hex_color(colors::srgb8_from_oklch(tessera.color)),
+        position.row,
+        position.column,
+        palette_attributes(tessera, tile_palette)
+    )
+}
+
+/// A polygon for a tessera out of the grid, with its index among the free tesserae.
+/// Its outline is given apart, so it can be moved or mirrored.
+pub fn free_tessera_element(
+    index: usize,
+    tessera: &Tessera,
+    polygon: &[[f32; 2]],
+    tile_palette: &TilePalette,
+) -> String {
+    format!(
+        "<{} fill=\"{}\" data-free=\"{}\"{}/>",
+        polygon_geometry(polygon),
+        materials::hex_color(colors::srgb8_from_oklch(tessera.color)),
+        index,
+        palette_attributes(tessera, tile_palette)
+    )
+}
+
+fn polygon_geometry(polygon: &[[f32; 2]]) -> String {
+    format!(
+        "polygon points=\"{}\"",
+        polygon
+            .iter()
+            .map(|[x, y]| format!("{},{}", round_to_hundredths(*x), round_to_hundredths(*y)))
+            .collect::<Vec<String>>()
+            .join(" ")
+    )
+}
+
+// Rotated tesserae have vertices like 12.999999, a hundredth of mm is enough.
+// Adding zero turns -0 into 0.
+fn round_to_hundredths(value: f32) -> f32 {
+    (value * 100.0).round() / 100.0 + 0.0
+}
+
+fn palette_attributes(tessera: &Tessera, tile_palette: &TilePalette) -> String {
+    let srgb = colors::srgb8_from_oklch(tessera.color);
+    match tile_palette.entries.iter().find(|e| e.srgb == srgb) {
+        Some(entry) => format!(
+            " data-palette-code=\"{}\" data-palette-name=\"{}\"",
+            escape_xml(&entry.supplier_code),
+            escape_xml(&entry.name)
+        ),
+        None => String::new(),
+    }
+}
+
+/// For text inside elements and attributes.
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Writes [`mosaic_to_svg`] to the file.
+pub fn export_mosaic_to_svg(
+    mosaic: &Mosaic,
+    gap_between_tesserae: usize,
+    tile_palette: &TilePalette,
+    grout_srgb: [u8; 3],
+    path: &std::path::Path,
+) -> Result<(), String> {
+    let svg = mosaic_to_svg(mosaic, gap_between_tesserae, tile_palette, grout_srgb);
+    match std::fs::write(path, svg) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Cannot write {}: {e}", path.display())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        mosaic::{a_mosaic_of_colors, FreeTessera, RectangleInMm},
+        tessera_shape::CutPreset,
+    };
+
+    fn a_2x2_mosaic() -> Mosaic {
+        a_mosaic_of_colors(
+            [10, 20],
+            [[255, 0, 0], [0, 0, 255]]
+                .iter()
+                .map(|srgb| vec![colors::oklch_from_srgb8(*srgb); 2])
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn the_svg_measures_in_millimetres() {
+        let svg = mosaic_to_svg(&a_2x2_mosaic(), 1, &TilePalette::default(), [0, 0, 0]);
+        assert!(svg.contains("width=\"21mm\" height=\"41mm\" viewBox=\"0 0 21 41\""));
+    }
+
+    #[test]
+    fn there_is_a_rect_per_tessera_at_its_position_and_with_its_color() {
+        let svg = mosaic_to_svg(&a_2x2_mosaic(), 1, &TilePalette::default(), [0, 0, 0]);
+        assert_eq!(svg.matches("data-row=").count(), 4);
+        assert!(svg.contains(
+            "<rect x=\"11\" y=\"21\" width=\"10\" height=\"20\" fill=\"#0000ff\" data-row=\"2\" data-column=\"2\"/>"
+        ));
+    }
+
+    #[test]
+    fn cut_tesserae_are_polygons() {
+        let mut mosaic = a_2x2_mosaic();
+        let position = PositionOnGrid { row: 2, column: 2 };
+        let mut tessera = mosaic.get_tessera(&position).unwrap().clone();
+        tessera.shape = TesseraShape::Preset(CutPreset::TriangleTopLeft);
+        mosaic.change_tessera(&position, tessera).unwrap();
+        let svg = mosaic_to_svg(&mosaic, 1, &TilePalette::default(), [0, 0, 0]);
+        assert_eq!(svg.matches("<rect").count(), 4);
+        assert!(svg.contains(
+            "<polygon points=\"11,21 21,21 11,41\" fill=\"#0000ff\" data-row=\"2\" data-column=\"2\"/>"
+        ));
+    }
+
+    #[test]
+    fn free_tesserae_are_rotated_polygons() {
+        let mut mosaic = Mosaic::new(
+            None,
+            RectangleInMm {
+                horizontal: 10,
+                vertical: 20,
+            },
+        );
+        mosaic.add_free_tessera(FreeTessera {
+            tessera: Tessera {
+                color: colors::oklch_from_srgb8([255, 0, 0]),
+                size: *mosaic.get_general_tessera_size(),
+                shape: TesseraShape::Whole,
+            },
+            center_mm: [15.0, 15.0],
+            rotation_degrees: 90.0,
+        });
+        let svg = mosaic_to_svg(&mosaic, 1, &TilePalette::default(), [0, 0, 0]);
+        assert!(svg.contains("viewBox=\"0 0 25 20\""));
+        assert!(svg.contains(
+            "<polygon points=\"25,10 25,20 5,20 5,10\" fill=\"#ff0000\" data-free=\"0\"/>"
+        ));
+    }
+
+    #[test]
+    fn palette_colors_carry_their_supplier_code() {
+        let mut tile_palette = TilePalette::default();
+        tile_palette.add_entry([255, 0, 0]);
+        tile_palette.entries[0].supplier_code = String::from("R&D-1");
+        let svg = mosaic_to_svg(&a_2x2_mosaic(), 1, &tile_palette, [0, 0, 0]);
+        assert_eq!(svg.matches("data-palette-code=\"R&amp;D-1\"").count(), 2);
+    }
+}
@@ -0,0 +1,598 @@
+//! Laying the tesserae over the base image and picking their colors from it.
+
+use crate::{
+    andamento, colors,
+    dithering::{self, DitheringMethod, DitheringSettings},
+    edges::EdgeDetectionSettings,
+    mosaic::{BaseImage, Mosaic, RectangleInMm, Tessera},
+    sampling::{self, PixelArea, SamplingMode},
+    tessera_shape::TesseraShape,
+    tile_palette::TilePalette,
+};
+
+/// Everything needed to lay the tesserae over the base image, both from the GUI and the command line.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct GenerationSettings {
+    /// The base image is stretched to cover it.
+    pub mosaic_dimensions_mm: [usize; 2],
+    /// Of every tessera.
+    pub tessera_size: RectangleInMm,
+    /// In mm.
+    pub gap_between_tesserae: usize,
+    /// How to pick the color of every tessera.
+    pub sampling_mode: SamplingMode,
+    /// Cut the last tessera of every row, and the last row, so the mosaic measures
+    /// exactly its dimensions. Otherwise they are whole, and may go past the edges.
+    pub fit_to_edges: bool,
+    /// How the tesserae are arranged.
+    pub pattern: LayoutPattern,
+    /// How to keep the gradients when the colors are restricted to a palette.
+    pub dithering: DitheringSettings,
+}
+
+/// The classical ways of laying the tesserae.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LayoutPattern {
+    /// Opus regulatum: a straight grid, with the joints aligned both ways.
+    Regulatum,
+    /// Running bond: every other row starts with half a tessera, like the bricks of a wall.
+    Brick,
+    /// Opus tessellatum: tesserae of slightly different widths, so the joints do not line up.
+    Tessellatum,
+    /// Opus vermiculatum: a row of free tesserae along the contours found on the image,
+    /// with the rows of opus regulatum around them. Without image it is just opus regulatum.
+    Vermiculatum,
+}
+
+impl GenerationSettings {
+    /// Tesserae of no width or height cannot be laid.
+    pub fn check(&self) -> Result<(), String> {
+        if self.tessera_size.horizontal == 0 || self.tessera_size.vertical == 0 {
+            return Err(String::from(
+                "The tesserae must be at least 1 mm wide and 1 mm high",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl LayoutPattern {
+    /// Every pattern, to list them on menus.
+    pub const ALL: [LayoutPattern; 4] = [
+        LayoutPattern::Regulatum,
+        LayoutPattern::Brick,
+        LayoutPattern::Tessellatum,
+        LayoutPattern::Vermiculatum,
+    ];
+}
+
+// Used where the base image cannot be sampled.
+const FALLBACK_SRGB: [u8; 3] = [255, 255, 0];
+// Opus tessellatum widths change up to this fraction of the tessera width, either way.
+const TESSELLATUM_JITTER: f32 = 0.2;
+
+/// Any PNG or JPEG image.
+pub fn load_base_image(path: &std::path::Path) -> Result<image::Rgba32FImage, String> {
+    match image::ImageReader::open(path) {
+        Err(e) => Err(format!("Cannot read {}: {e}", path.display())),
+        Ok(reader) => match reader.decode() {
+            Err(e) => Err(format!("Cannot decode {}: {e}", path.display())),
+            Ok(img) => Ok(img.to_rgba32f()),
+        },
+    }
+}
+
+/// If a palette is given, every color is snapped to the nearest tile color in it, or dithered.
+/// Tesserae without image under them get a bright yellow, to be noticed.
+pub fn generate_mosaic_from_image(
+    base_image: Option<BaseImage>,
+    settings: &GenerationSettings,
+    tile_palette: Option<&TilePalette>,
+) -> Mosaic {
+    generate_mosaic_and_colors_without_dithering(base_image, settings, tile_palette).0
+}
+
+/// The same as [`generate_mosaic_from_image`]. When it dithers, it also yields the nearest
+/// tile color of every tessera, in the order of [`Mosaic::get_all_tesserae`], to compare.
+pub fn generate_mosaic_and_colors_without_dithering(
+    base_image: Option<BaseImage>,
+    settings: &GenerationSettings,
+    tile_palette: Option<&TilePalette>,
+) -> (Mosaic, Option<Vec<palette::Oklch>>) {
+    let palette_to_dither_to =
+        tile_palette.filter(|_| settings.dithering.method != DitheringMethod::None);
+    if let Some(tile_palette) = palette_to_dither_to {
+        let mut mosaic = lay_tesserae_over_image(base_image, settings, None);
+        let without_dithering =
+            dithering::dither_to_palette(&mosaic, tile_palette, &DitheringSettings::default());
+        let colors = dithering::dither_to_palette(&mosaic, tile_palette, &settings.dithering);
+        for (tessera, color) in mosaic.get_all_tesserae_mut().zip(colors) {
+            tessera.color = color;
+        }
+        return (mosaic, Some(without_dithering));
+    }
+    (
+        lay_tesserae_over_image(base_image, settings, tile_palette),
+        None,
+    )
+}
+
+fn lay_tesserae_over_image(
+    base_image: Option<BaseImage>,
+    settings: &GenerationSettings,
+    tile_palette: Option<&TilePalette>,
+) -> Mosaic {
+    if settings.pattern == LayoutPattern::Vermiculatum && base_image.is_some() {
+        let size = settings.tessera_size;
+        let edge_detection = EdgeDetectionSettings {
+            cell_size_mm: size.horizontal.min(size.vertical) as f32 / 2.0,
+            ..EdgeDetectionSettings::default()
+        };
+        return andamento::generate_outlined_mosaic(
+            base_image,
+            settings,
+            &edge_detection,
+            tile_palette,
+        );
+    }
+    let mut mosaic = Mosaic::new(base_image, settings.tessera_size);
+    let mut rows: Vec<Vec<Tessera>> = Vec::new();
+    for row in get_layout(settings) {
+        rows.push(
+            row.into_iter()
+                .map(|(origin, size)| {
+                    let color = get_color_for_area(
+                        mosaic.get_base_image(),
+                        origin,
+                        size,
+                        settings,
+                        tile_palette,
+                    );
+                    Tessera {
+                        color,
+                        size,
+                        shape: TesseraShape::Whole,
+                    }
+                })
+                .collect(),
+        );
+    }
+    for row in rows {
+        mosaic.add_a_row_of_tesserae(row);
+    }
+    mosaic
+}
+
+/// The same layout as [`generate_mosaic_from_image`], with every tessera of the given color.
+pub fn generate_blank_mosaic(color: palette::Oklch, settings: &GenerationSettings) -> Mosaic {
+    let mut mosaic = Mosaic::new(None, settings.tessera_size);
+    for row in get_layout(settings) {
+        mosaic.add_a_row_of_tesserae(
+            row.into_iter()
+                .map(|(_origin, size)| Tessera {
+                    color,
+                    size,
+                    shape: TesseraShape::Whole,
+                })
+                .collect(),
+        );
+    }
+    mosaic
+}
+
+/// Origin (in mm) and size of every tessera on the grid, row by row, following the pattern.
+/// Opus vermiculatum gives the grid of opus regulatum, as its contours depend on the image.
+/// Settings that do not pass [`GenerationSettings::check`] are laid as tesserae of 1 mm.
+pub fn get_layout(settings: &GenerationSettings) -> Vec<Vec<([usize; 2], RectangleInMm)>> {
+    let row_stride = settings.tessera_size.vertical.max(1) + settings.gap_between_tesserae;
+    let fitted = |origin: usize, size: usize, dimension: usize| {
+        if settings.fit_to_edges {
+            size.min(dimension - origin)
+        } else {
+            size
+        }
+    };
+    let mut layout = Vec::new();
+    for (row_index, top) in (0..settings.mosaic_dimensions_mm[1].saturating_sub(1))
+        .step_by(row_stride)
+        .enumerate()
+    {
+        let mut row = Vec::new();
+        let mut left = 0;
+        while left < settings.mosaic_dimensions_mm[0].saturating_sub(1) {
+            let width = get_tessera_width(settings, row_index, row.len());
+            row.push((
+                [left, top],
+                RectangleInMm {
+                    horizontal: fitted(left, width, settings.mosaic_dimensions_mm[0]),
+                    vertical: fitted(
+                        top,
+                        settings.tessera_size.vertical.max(1),
+                        settings.mosaic_dimensions_mm[1],
+                    ),
+                },
+            ));
+            left += width + settings.gap_between_tesserae;
+        }
+        layout.push(row);
+    }
+    layout
+}
+
+fn get_tessera_width(settings: &GenerationSettings, row: usize, column: usize) -> usize {
+    let width = settings.tessera_size.horizontal.max(1);
+    match settings.pattern {
+        LayoutPattern::Brick if row % 2 == 1 && column == 0 => (width / 2).max(1),
+        LayoutPattern::Tessellatum => {
+            let jitter = (width as f32 * TESSELLATUM_JITTER).round().max(1.0) as usize;
+            let change = pseudo_random(row, column) % (2 * jitter + 1);
+            (width + change).saturating_sub(jitter).max(1)
+        }
+        _ => width,
+    }
+}
+
+// Always the same for the same position, so generating again lays the same tesserae.
+fn pseudo_random(row: usize, column: usize) -> usize {
+    let mut x = (row as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ (column as u64);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (x ^ (x >> 31)) as usize
+}
+
+/// Changes the vertical dimension to follow the reference aspect ratio, then rounds both up to tens.
+pub fn round_preserving_aspect_ratio(
+    dimensions_to_be_adjusted: [usize; 2],
+    reference_dimensions: [usize; 2],
+) -> [usize; 2] {
+    let adjust_amount: isize = (dimensions_to_be_adjusted[0] * reference_dimensions[1]
+        / reference_dimensions[0]) as isize
+        - dimensions_to_be_adjusted[1] as isize;
+    let adjusted_dimensions = [
+        dimensions_to_be_adjusted[0],
+        (dimensions_to_be_adjusted[1] as isize + adjust_amount) as usize,
+    ];
+    adjusted_dimensions.map(|x| ((x as f32 / 10.0).ceil() * 10.0) as usize)
+}
+
+/// The pixel under a point of the mosaic, given in mm, when the image is stretched to cover it.
+pub fn get_pixel_position_on_image(
+    point_position_on_mosaic: [usize; 2],
+    mosaic_dimensions: [usize; 2],
+    image_dimensions: [usize; 2],
+) -> [usize; 2] {
+    [
+        image_dimensions[0] * point_position_on_mosaic[0] / mosaic_dimensions[0].max(1),
+        image_dimensions[1] * point_position_on_mosaic[1] / mosaic_dimensions[1].max(1),
+    ]
+}
+
+// With the fallback color when the image cannot be sampled, and snapped to the palette if given.
+pub(crate) fn get_color_for_area(
+    base_image: Option<&image::Rgba32FImage>,
+    origin: [usize; 2],
+    size: RectangleInMm,
+    settings: &GenerationSettings,
+    tile_palette: Option<&TilePalette>,
+) -> palette::Oklch {
+    match get_tessera_color(base_image, origin, size, settings) {
+        Ok(color) => match tile_palette {
+            Some(tile_palette) => tile_palette.snap_to_nearest_color(color),
+            None => color,
+        },
+        Err(_) => colors::oklch_from_srgb8(FALLBACK_SRGB),
+    }
+}
+
+fn get_tessera_color(
+    base_image: Option<&image::Rgba32FImage>,
+    tessera_position_on_mosaic: [usize; 2],
+    tessera_size: RectangleInMm,
+    settings: &GenerationSettings,
+) -> Result<palette::Oklch, String> {
+    match base_image {
+        Some(img) => {
+            let image_dimensions = [img.width() as usize, img.height() as usize];
+            let top_left = get_pixel_position_on_image(
+                tessera_position_on_mosaic,
+                settings.mosaic_dimensions_mm,
+                image_dimensions,
+            );
+            let bottom_right = get_pixel_position_on_image(
+                [
+                    tessera_position_on_mosaic[0] + tessera_size.horizontal,
+                    tessera_position_on_mosaic[1] + tessera_size.vertical,
+                ],
+                settings.mosaic_dimensions_mm,
+                image_dimensions,
+            );
+            sampling::sample_color(
+                img,
+                PixelArea {
+                    left: top_left[0] as u32,
+                    top: top_left[1] as u32,
+                    right: bottom_right[0] as u32,
+                    bottom: bottom_right[1] as u32,
+                },
+                settings.sampling_mode,
+            )
+        }
+        None => Err(String::from("no image")),
+    }
+}
+
+// 10 mm tesserae with a gap of 1 mm, for the tests here and in other modules.
+#[cfg(test)]
+pub(crate) fn settings_for(mosaic_dimensions_mm: [usize; 2]) -> GenerationSettings {
+    GenerationSettings {
+        mosaic_dimensions_mm,
+        tessera_size: RectangleInMm {
+            horizontal: 10,
+            vertical: 10,
+        },
+        gap_between_tesserae: 1,
+        sampling_mode: SamplingMode::Average,
+        fit_to_edges: false,
+        pattern: LayoutPattern::Regulatum,
+        dithering: DitheringSettings::default(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tesserae_of_no_size_are_rejected_and_still_laid_as_1_mm() {
+        let mut settings = settings_for([5, 5]);
+        assert!(settings.check().is_ok());
+        settings.tessera_size.horizontal = 0;
+        settings.gap_between_tesserae = 0;
+        assert!(settings.check().is_err());
+        assert_eq!(get_layout(&settings)[0].len(), 4);
+        settings.tessera_size = RectangleInMm {
+            horizontal: 2,
+            vertical: 0,
+        };
+        assert!(settings.check().is_err());
+        assert_eq!(get_layout(&settings).len(), 4);
+    }
+
+    #[test]
+    fn rounder_works_for_a_realistic_image() {
+        let reference_for_proportionality = [739, 381];
+
+        let initial_dimensions = [200, 100];
+        let adjusted_dimensions =
+            round_preserving_aspect_ratio(initial_dimensions, reference_for_proportionality);
+        assert_eq!(adjusted_dimensions, [200, 110]);
+
+        let initial_dimensions = [100, 200];
+        let adjusted_dimensions =
+            round_preserving_aspect_ratio(initial_dimensions, reference_for_proportionality);
+        assert_eq!(adjusted_dimensions, [100, 60]);
+
+        let initial_dimensions = [401, 400];
+        let adjusted_dimensions =
+            round_preserving_aspect_ratio(initial_dimensions, reference_for_proportionality);
+        assert_eq!(adjusted_dimensions, [410, 210]);
+    }
+
+    #[test]
+    fn rounder_rounds_to_the_nearest_ten() {
+        let reference_for_proportionality = [1, 1];
+
+        let initial_dimensions = [75, 50];
+        let adjusted_dimensions =
+            round_preserving_aspect_ratio(initial_dimensions, reference_for_proportionality);
+        assert_eq!(adjusted_dimensions, [80, 80]);
+
+        let initial_dimensions = [345, 234];
+        let adjusted_dimensions =
+            round_preserving_aspect_ratio(initial_dimensions, reference_for_proportionality);
+        assert_eq!(adjusted_dimensions, [350, 350]);
+
+        let initial_dimensions = [34567, 23456];
+        let adjusted_dimensions =
+            round_preserving_aspect_ratio(initial_dimensions, reference_for_proportionality);
+        assert_eq!(adjusted_dimensions, [34570, 34570]);
+    }
+
+    #[test]
+    fn rounder_enforces_aspect_ratio() {
+        let reference_for_proportionality = [16, 9];
+
+        let initial_dimensions = [75, 50];
+        let adjusted_dimensions =
+            round_preserving_aspect_ratio(initial_dimensions, reference_for_proportionality);
+        assert_eq!(adjusted_dimensions, [80, 50]);
+
+        let initial_dimensions = [345, 234];
+        let adjusted_dimensions =
+            round_preserving_aspect_ratio(initial_dimensions, reference_for_proportionality);
+        assert_eq!(adjusted_dimensions, [350, 200]);
+
+        let initial_dimensions = [34567, 23456];
+        let adjusted_dimensions =
+            round_preserving_aspect_ratio(initial_dimensions, reference_for_proportionality);
+        assert_eq!(adjusted_dimensions, [34570, 19450]);
+    }
+
+    #[test]
+    fn get_tessera_color_yields_error_if_image_is_none() {
+        assert_eq!(
+            get_tessera_color(
+                None,
+                [234, 567],
+                settings_for([500, 300]).tessera_size,
+                &settings_for([500, 300])
+            ),
+            Err(String::from("no image"))
+        );
+    }
+
+    #[test]
+    fn get_tessera_color_yields_error_if_you_ask_for_pixels_outside_image_boundaries() {
+        let image = image::Rgba32FImage::new(64, 48);
+        let settings = settings_for([64, 48]);
+        assert_eq!(
+            get_tessera_color(
+                Some(&image),
+                [64 + 5, 48 + 5],
+                settings.tessera_size,
+                &settings
+            ),
+            Err(String::from("no pixel"))
+        );
+    }
+
+    #[test]
+    fn tesserae_not_covered_by_the_image_get_the_fallback_color() {
+        let mosaic = generate_mosaic_from_image(None, &settings_for([30, 30]), None);
+        assert_eq!(mosaic.get_number_of_rows(), 3);
+        assert_eq!(
+            colors::srgb8_from_oklch(mosaic.get_contents()[0][0].color),
+            FALLBACK_SRGB
+        );
+    }
+
+    #[test]
+    fn dithering_mixes_the_palette_colors_on_a_plain_grey_image() {
+        let grey = image::Rgba32FImage::from_pixel(100, 100, image::Rgba([0.2, 0.2, 0.2, 1.0]));
+        let mut tile_palette = TilePalette::default();
+        tile_palette.add_entry([0, 0, 0]);
+        tile_palette.add_entry([255, 255, 255]);
+        let distinct_colors = |mosaic: &Mosaic| {
+            let mut srgbs: Vec<[u8; 3]> = mosaic
+                .get_all_tesserae()
+                .map(|tessera| colors::srgb8_from_oklch(tessera.color))
+                .collect();
+            srgbs.sort();
+            srgbs.dedup();
+            srgbs
+        };
+
+        let snapped = generate_mosaic_from_image(
+            Some(BaseImage::new(grey.clone())),
+            &settings_for([100, 100]),
+            Some(&tile_palette),
+        );
+        assert_eq!(distinct_colors(&snapped).len(), 1);
+
+        let settings = GenerationSettings {
+            dithering: DitheringSettings {
+                method: DitheringMethod::FloydSteinberg,
+                strength: 1.0,
+            },
+            ..settings_for([100, 100])
+        };
+        let (dithered, without_dithering) = generate_mosaic_and_colors_without_dithering(
+            Some(BaseImage::new(grey)),
+            &settings,
+            Some(&tile_palette),
+        );
+        assert_eq!(distinct_colors(&dithered), vec![[0, 0, 0], [255, 255, 255]]);
+        let without_dithering = without_dithering.unwrap();
+        assert_eq!(without_dithering.len(), dithered.get_all_tesserae().count());
+        assert!(without_dithering
+            .iter()
+            .all(|color| *color == snapped.get_contents()[0][0].color));
+    }
+
+    #[test]
+    fn brick_rows_start_with_half_a_tessera_every_other_row() {
+        let settings = GenerationSettings {
+            pattern: LayoutPattern::Brick,
+            ..settings_for([55, 33])
+        };
+        let layout = get_layout(&settings);
+        assert_eq!(layout[0][0].1.horizontal, 10);
+        assert_eq!(layout[1][0].1.horizontal, 5);
+        assert_eq!(layout[1][1].0, [6, 11]);
+        assert_eq!(layout[2][1].0, [11, 22]);
+    }
+
+    #[test]
+    fn tessellatum_joints_do_not_line_up_but_the_rows_are_full() {
+        let settings = GenerationSettings {
+            pattern: LayoutPattern::Tessellatum,
+            fit_to_edges: true,
+            ..settings_for([200, 100])
+        };
+        let layout = get_layout(&settings);
+        assert!(layout
+            .iter()
+            .flat_map(|row| &row[..row.len() - 1])
+            .all(|(_origin, size)| (8..=12).contains(&size.horizontal)));
+        let joints = |row: &Vec<([usize; 2], RectangleInMm)>| -> Vec<usize> {
+            row.iter().map(|(origin, _size)| origin[0]).collect()
+        };
+        assert_ne!(joints(&layout[0]), joints(&layout[1]));
+        for row in &layout {
+            let (origin, size) = row.last().unwrap();
+            assert!((198..=200).contains(&(origin[0] + size.horizontal)));
+        }
+        assert_eq!(layout, get_layout(&settings));
+    }
+
+    #[test]
+    fn vermiculatum_outlines_the_contours_of_the_image_with_free_tesserae_in_the_grid() {
+        let settings = GenerationSettings {
+            pattern: LayoutPattern::Vermiculatum,
+            ..settings_for([100, 100])
+        };
+        let blank = generate_mosaic_from_image(None, &settings, None);
+        assert_eq!(blank.get_number_of_rows(), 9);
+
+        let half_black_half_white = image::Rgba32FImage::from_fn(100, 100, |x, _y| {
+            let l = if x < 50 { 0.0 } else { 1.0 };
+            image::Rgba([l, l, l, 1.0])
+        });
+        let mosaic = generate_mosaic_from_image(
+            Some(BaseImage::new(half_black_half_white)),
+            &settings,
+            None,
+        );
+        assert_eq!(mosaic.get_number_of_rows(), 9);
+        assert!(mosaic
+            .get_free_tesserae()
+            .iter()
+            .any(|free_tessera| (free_tessera.rotation_degrees.abs() - 90.0).abs() < 1.0));
+    }
+
+    #[test]
+    fn fitting_to_the_edges_cuts_the_last_column_and_row() {
+        let settings = GenerationSettings {
+            fit_to_edges: true,
+            ..settings_for([105, 36])
+        };
+        let mosaic = generate_mosaic_from_image(None, &settings, None);
+        assert_eq!(mosaic.get_number_of_rows(), 4);
+        assert_eq!(mosaic.get_number_of_tesserae_in_row(0), 10);
+        assert_eq!(mosaic.get_contents()[0][8].size.horizontal, 10);
+        assert_eq!(mosaic.get_contents()[0][9].size.horizontal, 6);
+        assert_eq!(mosaic.get_contents()[3][0].size.vertical, 3);
+        assert_eq!(
+            mosaic.get_overall_size_in_mm(1),
+            RectangleInMm {
+                horizontal: 105,
+                vertical: 36
+            }
+        );
+
+        let whole = generate_mosaic_from_image(None, &settings_for([105, 36]), None);
+        assert_eq!(whole.get_overall_size_in_mm(1).horizontal, 109);
+    }
+
+    #[test]
+    fn blank_mosaics_have_the_same_layout_as_generated_ones() {
+        let settings = settings_for([500, 300]);
+        let blank = generate_blank_mosaic(colors::oklch_from_srgb8([255, 255, 255]), &settings);
+        let generated = generate_mosaic_from_image(None, &settings, None);
+        assert_eq!(blank.get_number_of_rows(), generated.get_number_of_rows());
+        assert_eq!(
+            blank.get_number_of_tesserae_in_row(1),
+            generated.get_number_of_tesserae_in_row(1)
+        );
+    }
+}
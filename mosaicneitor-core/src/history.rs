@@ -0,0 +1,309 @@
+//! Undo and redo of the changes to the mosaic.
+
+use crate::mosaic::{Mosaic, PositionOnGrid, Tessera};
+
+/// Every change to the mosaic goes through here, to be able to undo and redo it.
+///
+/// An edit holds the new state of what it changes. Applying it swaps that state
+/// with the current one in the mosaic. So, after applying, the edit holds the old state
+/// and it is ready to be applied again to revert the change.
+pub enum MosaicEdit {
+    /// Some tesserae get new ones, all at once.
+    ChangeTesserae(Vec<TesseraChange>),
+    /// A new color for every tessera, in the order of [`Mosaic::get_all_tesserae`].
+    ChangeColors(Vec<palette::Oklch>),
+    /// The whole mosaic, for instance when it is generated again.
+    ReplaceMosaic(Box<Mosaic>),
+}
+
+/// A tessera to put at a position.
+#[derive(Debug, Clone)]
+pub struct TesseraChange {
+    /// Where.
+    pub position: PositionOnGrid,
+    /// What.
+    pub tessera: Tessera,
+}
+
+impl MosaicEdit {
+    // If any position is outside the mosaic, nothing is changed.
+    fn swap_with(&mut self, mosaic: &mut Mosaic) -> Result<(), String> {
+        match self {
+            MosaicEdit::ChangeTesserae(changes) => {
+                if let Some(change) = changes
+                    .iter()
+                    .find(|change| mosaic.get_tessera(&change.position).is_none())
+                {
+                    return Err(format!(
+                        "Out of bounds! There is no tessera at row {}, column {}.",
+                        change.position.row, change.position.column
+                    ));
+                }
+                for change in changes.iter_mut() {
+                    let previous =
+                        mosaic.change_tessera(&change.position, change.tessera.clone())?;
+                    change.tessera = previous;
+                }
+                // If a position is repeated, the earlier change must be the last one reverted.
+                changes.reverse();
+                Ok(())
+            }
+            MosaicEdit::ChangeColors(colors) => {
+                let number_of_tesserae = mosaic.get_all_tesserae().count();
+                if colors.len() != number_of_tesserae {
+                    return Err(format!(
+                        "There are {} colors for {number_of_tesserae} tesserae.",
+                        colors.len()
+                    ));
+                }
+                for (tessera, color) in mosaic.get_all_tesserae_mut().zip(colors.iter_mut()) {
+                    std::mem::swap(&mut tessera.color, color);
+                }
+                Ok(())
+            }
+            MosaicEdit::ReplaceMosaic(other_mosaic) => {
+                std::mem::swap(mosaic, other_mosaic.as_mut());
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The stacks of edits that can be undone and redone.
+pub struct EditHistory {
+    undo_stack: Vec<MosaicEdit>,
+    redo_stack: Vec<MosaicEdit>,
+    // None means unbounded.
+    max_depth: Option<usize>,
+}
+
+impl EditHistory {
+    /// Keeps at most max_depth edits to undo. None means unbounded.
+    pub fn new(max_depth: Option<usize>) -> Self {
+        Self {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            max_depth,
+        }
+    }
+
+    /// Forgets the oldest edits if there are more than the new maximum.
+    pub fn set_max_depth(&mut self, max_depth: Option<usize>) {
+        self.max_depth = max_depth;
+        self.trim();
+    }
+
+    /// Applies the edit to the mosaic and keeps it to be undone. Clears the edits to redo.
+    pub fn apply(&mut self, mosaic: &mut Mosaic, mut edit: MosaicEdit) -> Result<(), String> {
+        edit.swap_with(mosaic)?;
+        self.undo_stack.push(edit);
+        self.redo_stack.clear();
+        self.trim();
+        Ok(())
+    }
+
+    /// Yields false if there was nothing to undo.
+    pub fn undo(&mut self, mosaic: &mut Mosaic) -> bool {
+        match self.undo_stack.pop() {
+            Some(mut edit) => {
+                // It was applied before, so it fits in the mosaic.
+                let _ = edit.swap_with(mosaic);
+                self.redo_stack.push(edit);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Yields false if there was nothing to redo.
+    pub fn redo(&mut self, mosaic: &mut Mosaic) -> bool {
+        match self.redo_stack.pop() {
+            Some(mut edit) => {
+                let _ = edit.swap_with(mosaic);
+                self.undo_stack.push(edit);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether there are edits to undo.
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    /// Whether there are edits to redo.
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Forgets every edit, for instance when another project is opened.
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+
+    fn trim(&mut self) {
+        if let Some(max_depth) = self.max_depth {
+            if self.undo_stack.len() > max_depth {
+                let excess = self.undo_stack.len() - max_depth;
+                self.undo_stack.drain(0..excess);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        mosaic::{a_mosaic_of_colors, RectangleInMm},
+        tessera_shape::TesseraShape,
+    };
+
+    fn a_mosaic_of_color(l: f32) -> Mosaic {
+        a_mosaic_of_colors([10, 10], vec![vec![palette::Oklch::new(l, 0.0, 0.0); 3]; 3])
+    }
+
+    fn change_to(row: usize, column: usize, l: f32) -> TesseraChange {
+        TesseraChange {
+            position: PositionOnGrid { row, column },
+            tessera: Tessera {
+                color: palette::Oklch::new(l, 0.0, 0.0),
+                size: RectangleInMm {
+                    horizontal: 10,
+                    vertical: 10,
+                },
+                shape: TesseraShape::Whole,
+            },
+        }
+    }
+
+    fn lightness_at(mosaic: &Mosaic, row: usize, column: usize) -> f32 {
+        mosaic
+            .get_tessera(&PositionOnGrid { row, column })
+            .unwrap()
+            .color
+            .l
+    }
+
+    #[test]
+    fn tessera_changes_can_be_undone_and_redone() {
+        let mut mosaic = a_mosaic_of_color(0.0);
+        let mut history = EditHistory::new(None);
+        history
+            .apply(
+                &mut mosaic,
+                MosaicEdit::ChangeTesserae(vec![change_to(1, 1, 0.5), change_to(2, 3, 0.7)]),
+            )
+            .unwrap();
+        assert_eq!(lightness_at(&mosaic, 1, 1), 0.5);
+        assert_eq!(lightness_at(&mosaic, 2, 3), 0.7);
+
+        assert!(history.undo(&mut mosaic));
+        assert_eq!(lightness_at(&mosaic, 1, 1), 0.0);
+        assert_eq!(lightness_at(&mosaic, 2, 3), 0.0);
+        assert!(!history.undo(&mut mosaic));
+
+        assert!(history.redo(&mut mosaic));
+        assert_eq!(lightness_at(&mosaic, 1, 1), 0.5);
+        assert_eq!(lightness_at(&mosaic, 2, 3), 0.7);
+        assert!(!history.redo(&mut mosaic));
+    }
+
+    #[test]
+    fn repeated_positions_in_one_edit_are_undone_to_the_original_state() {
+        let mut mosaic = a_mosaic_of_color(0.0);
+        let mut history = EditHistory::new(None);
+        history
+            .apply(
+                &mut mosaic,
+                MosaicEdit::ChangeTesserae(vec![change_to(1, 1, 0.5), change_to(1, 1, 0.9)]),
+            )
+            .unwrap();
+        assert_eq!(lightness_at(&mosaic, 1, 1), 0.9);
+        history.undo(&mut mosaic);
+        assert_eq!(lightness_at(&mosaic, 1, 1), 0.0);
+        history.redo(&mut mosaic);
+        assert_eq!(lightness_at(&mosaic, 1, 1), 0.9);
+    }
+
+    #[test]
+    fn replacing_the_whole_mosaic_is_undone_in_one_step() {
+        let mut mosaic = a_mosaic_of_color(0.0);
+        let mut history = EditHistory::new(None);
+        history
+            .apply(
+                &mut mosaic,
+                MosaicEdit::ReplaceMosaic(Box::new(a_mosaic_of_color(1.0))),
+            )
+            .unwrap();
+        assert_eq!(lightness_at(&mosaic, 3, 3), 1.0);
+        history.undo(&mut mosaic);
+        assert_eq!(lightness_at(&mosaic, 3, 3), 0.0);
+        history.redo(&mut mosaic);
+        assert_eq!(lightness_at(&mosaic, 3, 3), 1.0);
+    }
+
+    #[test]
+    fn changing_every_color_at_once_is_undone_in_one_step() {
+        let mut mosaic = a_mosaic_of_color(0.0);
+        let mut history = EditHistory::new(None);
+        let colors = (0..9)
+            .map(|i| palette::Oklch::new(i as f32 / 10.0, 0.0, 0.0))
+            .collect();
+        history
+            .apply(&mut mosaic, MosaicEdit::ChangeColors(colors))
+            .unwrap();
+        assert_eq!(lightness_at(&mosaic, 3, 2), 0.7);
+        history.undo(&mut mosaic);
+        assert_eq!(lightness_at(&mosaic, 3, 2), 0.0);
+        assert!(history
+            .apply(&mut mosaic, MosaicEdit::ChangeColors(Vec::new()))
+            .is_err());
+    }
+
+    #[test]
+    fn edits_outside_the_mosaic_change_nothing_and_are_not_recorded() {
+        let mut mosaic = a_mosaic_of_color(0.0);
+        let mut history = EditHistory::new(None);
+        assert!(history
+            .apply(
+                &mut mosaic,
+                MosaicEdit::ChangeTesserae(vec![change_to(1, 1, 0.5), change_to(9, 9, 0.5)]),
+            )
+            .is_err());
+        assert_eq!(lightness_at(&mosaic, 1, 1), 0.0);
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn a_new_edit_discards_the_redo_steps() {
+        let mut mosaic = a_mosaic_of_color(0.0);
+        let mut history = EditHistory::new(None);
+        let edit = || MosaicEdit::ChangeTesserae(vec![change_to(1, 1, 0.5)]);
+        history.apply(&mut mosaic, edit()).unwrap();
+        history.undo(&mut mosaic);
+        assert!(history.can_redo());
+        history.apply(&mut mosaic, edit()).unwrap();
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn only_max_depth_steps_are_kept() {
+        let mut mosaic = a_mosaic_of_color(0.0);
+        let mut history = EditHistory::new(Some(2));
+        for l in [0.1, 0.2, 0.3] {
+            history
+                .apply(
+                    &mut mosaic,
+                    MosaicEdit::ChangeTesserae(vec![change_to(1, 1, l)]),
+                )
+                .unwrap();
+        }
+        assert!(history.undo(&mut mosaic));
+        assert!(history.undo(&mut mosaic));
+        assert!(!history.undo(&mut mosaic));
+        assert_eq!(lightness_at(&mosaic, 1, 1), 0.1);
+    }
+}
@@ -0,0 +1,45 @@
+//! The mosaic planning engine behind Mosaicneitor, without any GUI dependency.
+//!
+//! Starting with an image, it lays a grid of tesserae over it, picks their colors
+//! (optionally restricted to the tile colors you can actually buy), keeps the edits
+//! undoable, and exports the result: project files, pictures, drawings in millimetres,
+//! cartoons to print, color-by-number charts and materials reports.
+//!
+//! ```
+//! use mosaicneitor_core::{colors, generator, mosaic::RectangleInMm, sampling::SamplingMode};
+//!
+//! let settings = generator::GenerationSettings {
+//!     mosaic_dimensions_mm: [100, 50],
+//!     tessera_size: RectangleInMm { horizontal: 10, vertical: 10 },
+//!     gap_between_tesserae: 1,
+//!     sampling_mode: SamplingMode::Average,
+//!     fit_to_edges: false,
+//!     pattern: generator::LayoutPattern::Regulatum,
+//!     dithering: Default::default(),
+//! };
+//! let white = colors::oklch_from_srgb8([255, 255, 255]);
+//! let mosaic = generator::generate_blank_mosaic(white, &settings);
+//! assert_eq!(mosaic.get_number_of_rows(), 5);
+//! ```
+#![warn(missing_docs)]
+
+pub mod andamento;
+pub mod colors;
+pub mod dithering;
+pub mod edges;
+pub mod export_cartoon;
+pub mod export_chart;
+pub mod export_png;
+pub mod export_svg;
+pub mod generator;
+pub mod history;
+pub mod materials;
+pub mod mosaic;
+pub mod preprocessing;
+pub mod project_file;
+pub mod quantization;
+pub mod region;
+pub mod sampling;
+pub mod selection;
+pub mod tessera_shape;
+pub mod tile_palette;
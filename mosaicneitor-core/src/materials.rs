@@ -0,0 +1,289 @@
+//! How many tesserae of each color are needed, and their weight and cost.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{colors, mosaic::Mosaic, tessera_shape::TesseraShape, tile_palette::TilePalette};
+
+/// Used for the colors that are not in the palette,
+/// and for the palette entries without their own data.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct MaterialsSettings {
+    /// Extra tesserae to buy, for breakages and cuts.
+    pub waste_percentage: f32,
+    /// Weight of a square meter of tesserae.
+    pub default_kg_per_square_meter: f32,
+    /// Price of a square meter of tesserae.
+    pub default_price_per_square_meter: f32,
+}
+
+impl Default for MaterialsSettings {
+    fn default() -> Self {
+        Self {
+            waste_percentage: 10.0,
+            default_kg_per_square_meter: 10.0,
+            default_price_per_square_meter: 0.0,
+        }
+    }
+}
+
+/// The needs for one color.
+#[derive(Debug, PartialEq)]
+pub struct MaterialsReportLine {
+    /// The color, [red, green, blue], 0 to 255.
+    pub srgb: [u8; 3],
+    /// None if the color is not in the palette.
+    pub palette_entry_name: Option<String>,
+    /// None if the color is not in the palette.
+    pub supplier_code: Option<String>,
+    /// Tesserae of this color in the mosaic.
+    pub tesserae_count: usize,
+    /// Tesserae to buy.
+    pub tesserae_count_with_waste: usize,
+    /// Cuts with the nippers to shape the tesserae of this color, a workload estimate.
+    pub cuts: usize,
+    /// Of the tesserae to buy.
+    pub area_square_meters: f32,
+    /// Of the tesserae to buy.
+    pub weight_kg: f32,
+    /// Of the tesserae to buy.
+    pub cost: f32,
+}
+
+/// The materials needed for a mosaic, grouped by color.
+#[derive(Debug, PartialEq)]
+pub struct MaterialsReport {
+    /// Most used color first.
+    pub lines: Vec<MaterialsReportLine>,
+}
+
+impl MaterialsReport {
+    /// Weights and prices come from the palette entries, or from the settings when unknown.
+    pub fn new(mosaic: &Mosaic, tile_palette: &TilePalette, settings: &MaterialsSettings) -> Self {
+        let waste_factor = 1.0 + settings.waste_percentage.max(0.0) / 100.0;
+
+        // Color, count, area in square millimetres and cuts. Tesserae can have different sizes,
+        // and a cut tessera still takes a whole tile. Empty cells take none.
+        let mut totals: std::collections::HashMap<[u8; 3], (usize, usize, usize)> =
+            std::collections::HashMap::new();
+        for tessera in mosaic
+            .get_all_tesserae()
+            .filter(|tessera| tessera.shape != TesseraShape::Empty)
+        {
+            let srgb = colors::srgb8_from_oklch(tessera.color);
+            let (count, total_area, total_cuts) = totals.entry(srgb).or_default();
+            *count += 1;
+            *total_area += tessera.size.horizontal * tessera.size.vertical;
+            *total_cuts += tessera.shape.get_number_of_cuts(&tessera.size);
+        }
+        let mut counts: Vec<([u8; 3], usize, usize, usize)> = totals
+            .into_iter()
+            .map(|(srgb, (count, area, cuts))| (srgb, count, area, cuts))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        let lines = counts
+            .into_iter()
+            .map(|(srgb, tesserae_count, area_square_millimetres, cuts)| {
+                let palette_entry = tile_palette.entries.iter().find(|e| e.srgb == srgb);
+                let kg_per_square_meter = match palette_entry {
+                    Some(entry) if entry.kg_per_square_meter > 0.0 => entry.kg_per_square_meter,
+                    _ => settings.default_kg_per_square_meter,
+                };
+                let price_per_square_meter = match palette_entry {
+                    Some(entry) if entry.price_per_square_meter > 0.0 => {
+                        entry.price_per_square_meter
+                    }
+                    _ => settings.default_price_per_square_meter,
+                };
+                let tesserae_count_with_waste =
+                    (tesserae_count as f32 * waste_factor).ceil() as usize;
+                let area_square_meters =
+                    area_square_millimetres as f32 * waste_factor / 1_000_000.0;
+                MaterialsReportLine {
+                    srgb,
+                    palette_entry_name: palette_entry.map(|e| e.name.clone()),
+                    supplier_code: palette_entry.map(|e| e.supplier_code.clone()),
+                    tesserae_count,
+                    tesserae_count_with_waste,
+                    cuts,
+                    area_square_meters,
+                    weight_kg: area_square_meters * kg_per_square_meter,
+                    cost: area_square_meters * price_per_square_meter,
+                }
+            })
+            .collect();
+        Self { lines }
+    }
+
+    /// Tesserae in the mosaic.
+    pub fn get_total_tesserae(&self) -> usize {
+        self.lines.iter().map(|line| line.tesserae_count).sum()
+    }
+
+    /// Tesserae to buy.
+    pub fn get_total_tesserae_with_waste(&self) -> usize {
+        self.lines
+            .iter()
+            .map(|line| line.tesserae_count_with_waste)
+            .sum()
+    }
+
+    /// Cuts with the nippers, for the whole mosaic.
+    pub fn get_total_cuts(&self) -> usize {
+        self.lines.iter().map(|line| line.cuts).sum()
+    }
+
+    /// Weight of the tesserae to buy.
+    pub fn get_total_weight_kg(&self) -> f32 {
+        self.lines.iter().map(|line| line.weight_kg).sum()
+    }
+
+    /// Cost of the tesserae to buy.
+    pub fn get_total_cost(&self) -> f32 {
+        self.lines.iter().map(|line| line.cost).sum()
+    }
+
+    /// A header and a line per color.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "color,name,supplier_code,tesserae,tesserae_with_waste,area_m2,weight_kg,cost,cuts\n",
+        );
+        for line in &self.lines {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{:.4},{:.3},{:.2},{}\n",
+                hex_color(line.srgb),
+                csv_field(line.palette_entry_name.as_deref().unwrap_or("")),
+                csv_field(line.supplier_code.as_deref().unwrap_or("")),
+                line.tesserae_count,
+                line.tesserae_count_with_waste,
+                line.area_square_meters,
+                line.weight_kg,
+                line.cost,
+                line.cuts
+            ));
+        }
+        csv
+    }
+
+    /// Writes [`MaterialsReport::to_csv`] to the file.
+    pub fn save_csv_to_file(&self, path: &std::path::Path) -> Result<(), String> {
+        match std::fs::write(path, self.to_csv()) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Cannot write {}: {e}", path.display())),
+        }
+    }
+}
+
+/// As in HTML and SVG: "#rrggbb".
+pub fn hex_color(srgb: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", srgb[0], srgb[1], srgb[2])
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        String::from(text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        mosaic::{a_mosaic_of_colors, PositionOnGrid},
+        tessera_shape::CutPreset,
+    };
+
+    // 3 rows of 10 tesserae of 10x10 mm: 20 red and 10 white.
+    fn a_red_and_white_mosaic() -> Mosaic {
+        let red = vec![colors::oklch_from_srgb8([255, 0, 0]); 10];
+        let white = vec![colors::oklch_from_srgb8([255, 255, 255]); 10];
+        a_mosaic_of_colors([10, 10], vec![red.clone(), red, white])
+    }
+
+    #[test]
+    fn tesserae_are_counted_by_color_most_used_first() {
+        let report = MaterialsReport::new(
+            &a_red_and_white_mosaic(),
+            &TilePalette::default(),
+            &MaterialsSettings::default(),
+        );
+        assert_eq!(report.lines.len(), 2);
+        assert_eq!(report.lines[0].srgb, [255, 0, 0]);
+        assert_eq!(report.lines[0].tesserae_count, 20);
+        assert_eq!(report.lines[1].srgb, [255, 255, 255]);
+        assert_eq!(report.lines[1].tesserae_count, 10);
+        assert_eq!(report.get_total_tesserae(), 30);
+        assert_eq!(report.get_total_cuts(), 0);
+    }
+
+    #[test]
+    fn cuts_are_counted_by_color() {
+        let mut mosaic = a_red_and_white_mosaic();
+        for (column, shape) in [
+            (1, TesseraShape::Preset(CutPreset::QuarterTopLeft)),
+            (2, TesseraShape::Preset(CutPreset::HalfTop)),
+        ] {
+            let position = PositionOnGrid { row: 3, column };
+            let mut tessera = mosaic.get_tessera(&position).unwrap().clone();
+            tessera.shape = shape;
+            mosaic.change_tessera(&position, tessera).unwrap();
+        }
+        let report = MaterialsReport::new(
+            &mosaic,
+            &TilePalette::default(),
+            &MaterialsSettings::default(),
+        );
+        assert_eq!(report.lines[0].cuts, 0);
+        assert_eq!(report.lines[1].cuts, 3);
+        assert_eq!(report.get_total_cuts(), 3);
+        assert!(report.to_csv().lines().nth(2).unwrap().ends_with(",3"));
+    }
+
+    #[test]
+    fn waste_weight_and_cost_are_estimated_from_palette_data_or_defaults() {
+        let mut tile_palette = TilePalette::default();
+        tile_palette.add_entry([255, 0, 0]);
+        tile_palette.entries[0].name = String::from("Red, glass");
+        tile_palette.entries[0].kg_per_square_meter = 20.0;
+        tile_palette.entries[0].price_per_square_meter = 100.0;
+        let settings = MaterialsSettings {
+            waste_percentage: 50.0,
+            default_kg_per_square_meter: 10.0,
+            default_price_per_square_meter: 1.0,
+        };
+        let report = MaterialsReport::new(&a_red_and_white_mosaic(), &tile_palette, &settings);
+
+        let red = &report.lines[0];
+        assert_eq!(red.palette_entry_name, Some(String::from("Red, glass")));
+        assert_eq!(red.tesserae_count_with_waste, 30);
+        assert!((red.area_square_meters - 0.003).abs() < 0.00001);
+        assert!((red.weight_kg - 0.06).abs() < 0.00001);
+        assert!((red.cost - 0.3).abs() < 0.00001);
+
+        let white = &report.lines[1];
+        assert_eq!(white.palette_entry_name, None);
+        assert_eq!(white.tesserae_count_with_waste, 15);
+        assert!((white.weight_kg - 0.015).abs() < 0.00001);
+        assert!((white.cost - 0.0015).abs() < 0.00001);
+    }
+
+    #[test]
+    fn csv_has_a_header_and_a_line_per_color_with_quoted_names() {
+        let mut tile_palette = TilePalette::default();
+        tile_palette.add_entry([255, 0, 0]);
+        tile_palette.entries[0].name = String::from("Red, glass");
+        let report = MaterialsReport::new(
+            &a_red_and_white_mosaic(),
+            &tile_palette,
+            &MaterialsSettings::default(),
+        );
+        let csv = report.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("color,name,"));
+        assert!(lines[1].starts_with("#ff0000,\"Red, glass\",,20,22,"));
+        assert!(lines[2].starts_with("#ffffff,,,10,11,"));
+    }
+}
@@ -0,0 +1,570 @@
+//! The mosaic itself: rows of tesserae laid over a base image, and their geometry in millimetres.
+
+use crate::{colors, tessera_shape::TesseraShape};
+
+/// Rows of tesserae, top to bottom, each one left to right.
+/// Every tessera has its own size. The height of a row is the height of its tallest tessera,
+/// the others are aligned to the top of the row. Tesserae are separated by a gap
+/// that is given when asking for their placement.
+///
+/// Besides the grid, there can be free tesserae, each one with its own position and rotation,
+/// as laid along the flow lines of the andamento.
+pub struct Mosaic {
+    base_image: Option<BaseImage>,
+    general_tessera_size: RectangleInMm,
+    contents: Vec<Vec<Tessera>>,
+    free_tesserae: Vec<FreeTessera>,
+}
+
+/// The image under a mosaic. It is shared, so copies of the mosaic, as the ones kept to undo
+/// a regeneration, do not copy it.
+pub type BaseImage = std::sync::Arc<image::Rgba32FImage>;
+
+/// A single piece of the mosaic.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tessera {
+    /// Kept in Oklch, a perceptual color space, so color distances and adjustments look right.
+    pub color: palette::Oklch,
+    /// Usually the general tessera size of the mosaic, but it can be smaller (half tiles at borders)
+    /// or bigger (backgrounds).
+    pub size: RectangleInMm,
+    /// Cut tesserae only fill part of their size.
+    pub shape: TesseraShape,
+}
+
+/// A tessera out of the grid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FreeTessera {
+    /// Color, size and shape.
+    pub tessera: Tessera,
+    /// Where the middle of its cell is, [horizontal, vertical] in mm from the top left corner of the mosaic.
+    pub center_mm: [f32; 2],
+    /// Clockwise, as seen on screen. Zero leaves its width horizontal.
+    pub rotation_degrees: f32,
+}
+
+impl FreeTessera {
+    /// Vertices of its outline, in mm from the top left corner of the mosaic. Clockwise.
+    pub fn get_polygon(&self) -> Vec<[f32; 2]> {
+        let (sin, cos) = self.rotation_degrees.to_radians().sin_cos();
+        let half_size = [
+            self.tessera.size.horizontal as f32 / 2.0,
+            self.tessera.size.vertical as f32 / 2.0,
+        ];
+        self.tessera
+            .shape
+            .get_polygon(&self.tessera.size)
+            .iter()
+            .map(|[x, y]| {
+                let [x, y] = [x - half_size[0], y - half_size[1]];
+                [
+                    self.center_mm[0] + x * cos - y * sin,
+                    self.center_mm[1] + x * sin + y * cos,
+                ]
+            })
+            .collect()
+    }
+}
+
+/// A size, in whole millimetres.
+#[derive(Clone, PartialEq, Debug)]
+pub struct RectangleInMm {
+    /// Width.
+    pub horizontal: usize,
+    /// Height.
+    pub vertical: usize,
+}
+impl Copy for RectangleInMm {}
+
+/// Where a tessera lays on the mosaic, measuring from the top left corner.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct TesseraPlacement {
+    /// Top left corner of the tessera, [horizontal, vertical] in mm.
+    pub origin: [usize; 2],
+    /// Size of the tessera.
+    pub size: RectangleInMm,
+}
+
+/// Row and column of a tessera. Both are numbered starting at 1.
+/// They are sorted row by row.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+pub struct PositionOnGrid {
+    /// Starting at 1, from the top.
+    pub row: usize,
+    /// Starting at 1, from the left.
+    pub column: usize,
+}
+
+impl Mosaic {
+    /// An empty mosaic, add its rows with [`Mosaic::add_a_row_of_tesserae`].
+    pub fn new(base_image: Option<BaseImage>, general_tessera_base_size: RectangleInMm) -> Self {
+        Self {
+            base_image,
+            general_tessera_size: general_tessera_base_size,
+            contents: Vec::new(),
+            free_tesserae: Vec::new(),
+        }
+    }
+
+    /// The image the mosaic was generated from, if any.
+    pub fn get_base_image(&self) -> Option<&image::Rgba32FImage> {
+        self.base_image.as_deref()
+    }
+
+    /// The usual size of the tesserae, the one used when generating the mosaic.
+    pub fn get_general_tessera_size(&self) -> &RectangleInMm {
+        &self.general_tessera_size
+    }
+
+    /// The rows of tesserae, top to bottom.
+    pub fn get_contents(&self) -> &Vec<Vec<Tessera>> {
+        &self.contents
+    }
+
+    /// Adds a row at the bottom.
+    pub fn add_a_row_of_tesserae(&mut self, row: Vec<Tessera>) {
+        self.contents.push(row);
+    }
+
+    /// The tesserae out of the grid.
+    pub fn get_free_tesserae(&self) -> &Vec<FreeTessera> {
+        &self.free_tesserae
+    }
+
+    /// Every tessera, first the grid row by row, then the free ones.
+    pub fn get_all_tesserae(&self) -> impl Iterator<Item = &Tessera> {
+        self.contents.iter().flatten().chain(
+            self.free_tesserae
+                .iter()
+                .map(|free_tessera| &free_tessera.tessera),
+        )
+    }
+
+    /// The same as [`Mosaic::get_all_tesserae`], to change them in place.
+    pub fn get_all_tesserae_mut(&mut self) -> impl Iterator<Item = &mut Tessera> {
+        self.contents.iter_mut().flatten().chain(
+            self.free_tesserae
+                .iter_mut()
+                .map(|free_tessera| &mut free_tessera.tessera),
+        )
+    }
+
+    /// Adds a tessera out of the grid.
+    pub fn add_free_tessera(&mut self, free_tessera: FreeTessera) {
+        self.free_tesserae.push(free_tessera);
+    }
+
+    /// Rows and columns are numbered starting at 1. None outside the mosaic.
+    pub fn get_tessera(&self, position: &PositionOnGrid) -> Option<&Tessera> {
+        if position.row == 0 || position.column == 0 {
+            return None;
+        }
+        self.contents
+            .get(position.row - 1)
+            .and_then(|row| row.get(position.column - 1))
+    }
+
+    /// Yields the tessera that was there before, or an error for positions outside the mosaic.
+    pub fn change_tessera(
+        &mut self,
+        position: &PositionOnGrid,
+        new_tessera: Tessera,
+    ) -> Result<Tessera, String> {
+        if position.row == 0 || position.row > self.contents.len() {
+            return Err(format!(
+                "Out of bounds! The mosaic has only {} rows. And you want to change the {}nt row.",
+                self.contents.len(),
+                position.row
+            ));
+        }
+        if position.column == 0 || position.column > self.contents[position.row - 1].len() {
+            return Err(format!("Out of bounds! The {}nt row in the mosaic has only {} columns. And you want to change the {}nt column.", position.row, self.contents[position.row - 1].len(), position.column));
+        }
+        Ok(std::mem::replace(
+            &mut self.contents[position.row - 1][position.column - 1],
+            new_tessera,
+        ))
+    }
+
+    /// Where the tessera lays, with the given gap (in mm) between tesserae. None outside the mosaic.
+    pub fn get_tessera_placement(
+        &self,
+        position: &PositionOnGrid,
+        gap_between_tesserae: usize,
+    ) -> Option<TesseraPlacement> {
+        let tessera = self.get_tessera(position)?;
+        let top: usize = self.contents[..position.row - 1]
+            .iter()
+            .map(|row| get_row_height(row) + gap_between_tesserae)
+            .sum();
+        let left: usize = self.contents[position.row - 1][..position.column - 1]
+            .iter()
+            .map(|tessera| tessera.size.horizontal + gap_between_tesserae)
+            .sum();
+        Some(TesseraPlacement {
+            origin: [left, top],
+            size: tessera.size,
+        })
+    }
+
+    /// Every tessera, row by row, with its position on the grid and its placement.
+    pub fn get_tesserae_with_placements(
+        &self,
+        gap_between_tesserae: usize,
+    ) -> Vec<(PositionOnGrid, &Tessera, TesseraPlacement)> {
+        let mut tesserae = Vec::new();
+        let mut top = 0;
+        for (row_index, row) in self.contents.iter().enumerate() {
+            let mut left = 0;
+            for (column_index, tessera) in row.iter().enumerate() {
+                let position = PositionOnGrid {
+                    row: row_index + 1,
+                    column: column_index + 1,
+                };
+                let placement = TesseraPlacement {
+                    origin: [left, top],
+                    size: tessera.size,
+                };
+                tesserae.push((position, tessera, placement));
+                left += tessera.size.horizontal + gap_between_tesserae;
+            }
+            top += get_row_height(row) + gap_between_tesserae;
+        }
+        tesserae
+    }
+
+    /// The tessera at a point of the mosaic, given in mm from the top left corner.
+    /// The gap at the right and below a tessera counts as part of it. None outside the mosaic.
+    pub fn get_position_at_point(
+        &self,
+        point_mm: [f32; 2],
+        gap_between_tesserae: usize,
+    ) -> Option<PositionOnGrid> {
+        if point_mm[0] < 0.0 || point_mm[1] < 0.0 {
+            return None;
+        }
+        let mut top = 0.0;
+        for (row_index, row) in self.contents.iter().enumerate() {
+            let bottom = top + (get_row_height(row) + gap_between_tesserae) as f32;
+            if point_mm[1] < bottom {
+                let mut left = 0.0;
+                for (column_index, tessera) in row.iter().enumerate() {
+                    left += (tessera.size.horizontal + gap_between_tesserae) as f32;
+                    if point_mm[0] < left {
+                        return Some(PositionOnGrid {
+                            row: row_index + 1,
+                            column: column_index + 1,
+                        });
+                    }
+                }
+                return None;
+            }
+            top = bottom;
+        }
+        None
+    }
+
+    /// From the top left corner of the first tessera to the bottom right corner of the last one,
+    /// or to the furthest free tessera.
+    pub fn get_overall_size_in_mm(&self, gap_between_tesserae: usize) -> RectangleInMm {
+        let span = |sizes: Vec<usize>| match sizes.len() {
+            0 => 0,
+            count => sizes.iter().sum::<usize>() + (count - 1) * gap_between_tesserae,
+        };
+        let free_tesserae_extent = |axis: usize| {
+            self.free_tesserae
+                .iter()
+                .flat_map(|free_tessera| free_tessera.get_polygon())
+                .map(|vertex| vertex[axis].ceil().max(0.0) as usize)
+                .max()
+                .unwrap_or(0)
+        };
+        RectangleInMm {
+            horizontal: self
+                .contents
+                .iter()
+                .map(|row| span(row.iter().map(|t| t.size.horizontal).collect()))
+                .max()
+                .unwrap_or(0)
+                .max(free_tesserae_extent(0)),
+            vertical: span(
+                self.contents
+                    .iter()
+                    .map(|row| get_row_height(row))
+                    .collect(),
+            )
+            .max(free_tesserae_extent(1)),
+        }
+    }
+
+    /// How many rows of tesserae there are.
+    pub fn get_number_of_rows(&self) -> usize {
+        self.contents.len()
+    }
+
+    /// How many tesserae there are in the row with the given index (starting at 0, unlike [`PositionOnGrid`]).
+    pub fn get_number_of_tesserae_in_row(&self, row_index: usize) -> usize {
+        match self.contents.get(row_index) {
+            Some(row) => row.len(),
+            None => 0,
+        }
+    }
+
+    /// The indices, in the order of [`Mosaic::get_all_tesserae`], of the tesserae with a color
+    /// within the tolerance of the given one. The tolerance is the Oklab distance, 0 is the same color.
+    pub fn get_tesserae_of_similar_color(
+        &self,
+        color: palette::Oklch,
+        tolerance: f32,
+    ) -> Vec<usize> {
+        self.get_all_tesserae()
+            .enumerate()
+            .filter(|(_index, tessera)| colors::oklab_distance(tessera.color, color) <= tolerance)
+            .map(|(index, _tessera)| index)
+            .collect()
+    }
+
+    /// A color for every tessera, with the target color for those within the tolerance of the source
+    /// color, and how many of them get it. Ready to be applied as
+    /// [`crate::history::MosaicEdit::ChangeColors`], so the replacement can be undone.
+    pub fn get_colors_with_replacement(
+        &self,
+        source: palette::Oklch,
+        target: palette::Oklch,
+        tolerance: f32,
+    ) -> (Vec<palette::Oklch>, usize) {
+        let mut colors: Vec<palette::Oklch> = self
+            .get_all_tesserae()
+            .map(|tessera| tessera.color)
+            .collect();
+        let to_replace = self.get_tesserae_of_similar_color(source, tolerance);
+        for index in &to_replace {
+            colors[*index] = target;
+        }
+        (colors, to_replace.len())
+    }
+}
+
+// The tallest tessera sets the height of the row.
+fn get_row_height(row: &[Tessera]) -> usize {
+    row.iter()
+        .map(|tessera| tessera.size.vertical)
+        .max()
+        .unwrap_or(0)
+}
+
+// Whole tesserae of the same size, a row for every row of colors,
+// for the tests here and in other modules.
+#[cfg(test)]
+pub(crate) fn a_mosaic_of_colors(
+    tessera_size: [usize; 2],
+    rows: Vec<Vec<palette::Oklch>>,
+) -> Mosaic {
+    let size = RectangleInMm {
+        horizontal: tessera_size[0],
+        vertical: tessera_size[1],
+    };
+    let mut mosaic = Mosaic::new(None, size);
+    for row in rows {
+        mosaic.add_a_row_of_tesserae(
+            row.into_iter()
+                .map(|color| Tessera {
+                    color,
+                    size,
+                    shape: TesseraShape::Whole,
+                })
+                .collect(),
+        );
+    }
+    mosaic
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TEN_BY_TEN: RectangleInMm = RectangleInMm {
+        horizontal: 10,
+        vertical: 10,
+    };
+
+    fn a_3x2_mosaic() -> Mosaic {
+        a_mosaic_of_colors(
+            [10, 10],
+            vec![vec![palette::Oklch::new(0.0, 0.0, 0.0); 3]; 2],
+        )
+    }
+
+    // A row of 10x10, 5x10 and 20x10 tesserae, then a row with a single 10x20 tessera.
+    fn a_mosaic_with_different_sizes() -> Mosaic {
+        let mut mosaic = Mosaic::new(None, TEN_BY_TEN);
+        let tessera = |horizontal, vertical| Tessera {
+            color: palette::Oklch::new(0.0, 0.0, 0.0),
+            size: RectangleInMm {
+                horizontal,
+                vertical,
+            },
+            shape: TesseraShape::Whole,
+        };
+        mosaic.add_a_row_of_tesserae(vec![tessera(10, 10), tessera(5, 10), tessera(20, 10)]);
+        mosaic.add_a_row_of_tesserae(vec![tessera(10, 20)]);
+        mosaic.add_a_row_of_tesserae(vec![tessera(10, 10)]);
+        mosaic
+    }
+
+    #[test]
+    fn colors_within_the_tolerance_are_replaced() {
+        let mut mosaic = a_3x2_mosaic();
+        let greenish_grey = colors::oklch_from_srgb8([120, 130, 120]);
+        let a_bit_greener = colors::oklch_from_srgb8([118, 134, 118]);
+        for (column, color) in [(1, greenish_grey), (3, a_bit_greener)] {
+            let position = PositionOnGrid { row: 2, column };
+            let mut tessera = mosaic.get_tessera(&position).unwrap().clone();
+            tessera.color = color;
+            mosaic.change_tessera(&position, tessera).unwrap();
+        }
+        assert_eq!(
+            mosaic.get_tesserae_of_similar_color(greenish_grey, 0.0),
+            vec![3]
+        );
+        assert_eq!(
+            mosaic.get_tesserae_of_similar_color(greenish_grey, 0.05),
+            vec![3, 5]
+        );
+
+        let green = colors::oklch_from_srgb8([0, 128, 0]);
+        let (colors, replaced) = mosaic.get_colors_with_replacement(greenish_grey, green, 0.05);
+        assert_eq!(replaced, 2);
+        assert_eq!(colors.len(), 6);
+        assert_eq!(colors[3], green);
+        assert_eq!(colors[5], green);
+        assert_eq!(colors[0], palette::Oklch::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn change_tessera_changes_the_tessera_at_the_given_position() {
+        let mut mosaic = a_3x2_mosaic();
+        let position = PositionOnGrid { row: 2, column: 3 };
+        let white = palette::Oklch::new(1.0, 0.0, 0.0);
+        assert!(mosaic
+            .change_tessera(
+                &position,
+                Tessera {
+                    color: white,
+                    size: TEN_BY_TEN,
+                    shape: TesseraShape::Whole,
+                }
+            )
+            .is_ok());
+        assert_eq!(mosaic.get_tessera(&position).unwrap().color, white);
+        assert_eq!(mosaic.get_contents()[1][2].color, white);
+    }
+
+    #[test]
+    fn placements_and_overall_size_account_for_the_gaps() {
+        let mosaic = a_3x2_mosaic();
+        assert_eq!(
+            mosaic.get_tessera_placement(&PositionOnGrid { row: 2, column: 3 }, 2),
+            Some(TesseraPlacement {
+                origin: [24, 12],
+                size: RectangleInMm {
+                    horizontal: 10,
+                    vertical: 10
+                }
+            })
+        );
+        assert_eq!(
+            mosaic.get_tessera_placement(&PositionOnGrid { row: 3, column: 1 }, 2),
+            None
+        );
+        assert_eq!(
+            mosaic.get_overall_size_in_mm(2),
+            RectangleInMm {
+                horizontal: 34,
+                vertical: 22
+            }
+        );
+    }
+
+    #[test]
+    fn placements_follow_the_actual_size_of_every_tessera_and_row() {
+        let mosaic = a_mosaic_with_different_sizes();
+        let placement_of = |row, column| {
+            mosaic
+                .get_tessera_placement(&PositionOnGrid { row, column }, 1)
+                .unwrap()
+        };
+        assert_eq!(placement_of(1, 2).origin, [11, 0]);
+        assert_eq!(placement_of(1, 3).origin, [17, 0]);
+        assert_eq!(placement_of(1, 3).size.horizontal, 20);
+        assert_eq!(placement_of(2, 1).origin, [0, 11]);
+        // The second row is as tall as its tallest tessera.
+        assert_eq!(placement_of(3, 1).origin, [0, 32]);
+        assert_eq!(
+            mosaic.get_overall_size_in_mm(1),
+            RectangleInMm {
+                horizontal: 37,
+                vertical: 42
+            }
+        );
+
+        let all_placements: Vec<TesseraPlacement> = mosaic
+            .get_tesserae_with_placements(1)
+            .into_iter()
+            .map(|(_position, _tessera, placement)| placement)
+            .collect();
+        assert_eq!(
+            all_placements,
+            [(1, 1), (1, 2), (1, 3), (2, 1), (3, 1)].map(|(r, c)| placement_of(r, c))
+        );
+    }
+
+    #[test]
+    fn the_position_at_a_point_is_found_with_the_actual_sizes() {
+        let mosaic = a_mosaic_with_different_sizes();
+        let position_at = |x, y| mosaic.get_position_at_point([x, y], 1);
+        assert_eq!(
+            position_at(0.0, 0.0),
+            Some(PositionOnGrid { row: 1, column: 1 })
+        );
+        assert_eq!(
+            position_at(12.0, 5.0),
+            Some(PositionOnGrid { row: 1, column: 2 })
+        );
+        assert_eq!(
+            position_at(36.5, 5.0),
+            Some(PositionOnGrid { row: 1, column: 3 })
+        );
+        assert_eq!(position_at(38.0, 5.0), None);
+        assert_eq!(
+            position_at(5.0, 30.0),
+            Some(PositionOnGrid { row: 2, column: 1 })
+        );
+        assert_eq!(position_at(15.0, 30.0), None);
+        assert_eq!(
+            position_at(5.0, 35.0),
+            Some(PositionOnGrid { row: 3, column: 1 })
+        );
+        assert_eq!(position_at(5.0, 60.0), None);
+        assert_eq!(position_at(-1.0, 5.0), None);
+    }
+
+    #[test]
+    fn change_tessera_yields_error_for_positions_outside_the_mosaic() {
+        let mut mosaic = a_3x2_mosaic();
+        for (row, column) in [(0, 1), (1, 0), (3, 1), (1, 4)] {
+            let position = PositionOnGrid { row, column };
+            assert!(mosaic
+                .change_tessera(
+                    &position,
+                    Tessera {
+                        color: palette::Oklch::new(1.0, 0.0, 0.0),
+                        size: TEN_BY_TEN,
+                        shape: TesseraShape::Whole,
+                    }
+                )
+                .is_err());
+            assert!(mosaic.get_tessera(&position).is_none());
+        }
+    }
+}
@@ -0,0 +1,333 @@
+//! Adjustments to the base image before sampling the tesserae. They are not destructive:
+//! the image file stays as it is and the operations are stored in the project, to apply them again.
+
+use serde::{Deserialize, Serialize};
+
+/// One adjustment. Colors are the sRGB values of the image, from 0 to 1.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ImageOperation {
+    /// Fractions of the width or height taken out from every side.
+    Crop {
+        /// From the left side.
+        left: f32,
+        /// From the top side.
+        top: f32,
+        /// From the right side.
+        right: f32,
+        /// From the bottom side.
+        bottom: f32,
+    },
+    /// Clockwise, in quarter turns.
+    Rotate {
+        /// 1, 2 or 3, the rest of dividing by 4 for other values.
+        quarter_turns: u8,
+    },
+    /// Left becomes right.
+    FlipHorizontal,
+    /// Top becomes bottom.
+    FlipVertical,
+    /// A small rotation, clockwise, then the image is cut to the largest rectangle
+    /// with the same aspect ratio that has no empty corners.
+    Straighten {
+        /// Usually a few degrees, positive or negative.
+        degrees: f32,
+    },
+    /// Added to every channel, from -1 to 1.
+    Brightness {
+        /// 0 leaves the image as it is.
+        amount: f32,
+    },
+    /// Distances to middle grey are multiplied by 1 plus the amount.
+    Contrast {
+        /// From -1 (flat grey) up, 0 leaves the image as it is.
+        amount: f32,
+    },
+    /// Distances to the grey of the same luminance are multiplied by 1 plus the amount.
+    Saturation {
+        /// From -1 (black and white) up, 0 leaves the image as it is.
+        amount: f32,
+    },
+    /// Every channel is rounded to a few evenly spaced values.
+    Posterize {
+        /// Values per channel, at least 2.
+        levels: u8,
+    },
+    /// Gaussian blur.
+    Blur {
+        /// Standard deviation, in pixels of the image.
+        sigma: f32,
+    },
+    /// Median of the 3x3 pixels around, channel by channel. Takes out isolated specks.
+    Denoise,
+    /// Every channel to the power of 1 / gamma, so more than 1 lightens the mid tones.
+    Gamma {
+        /// 1 leaves the image as it is.
+        gamma: f32,
+    },
+}
+
+impl ImageOperation {
+    /// Every operation, with its default settings, to add them from a menu.
+    pub const ALL: [ImageOperation; 12] = [
+        ImageOperation::Crop {
+            left: 0.0,
+            top: 0.0,
+            right: 0.0,
+            bottom: 0.0,
+        },
+        ImageOperation::Rotate { quarter_turns: 1 },
+        ImageOperation::FlipHorizontal,
+        ImageOperation::FlipVertical,
+        ImageOperation::Straighten { degrees: 0.0 },
+        ImageOperation::Brightness { amount: 0.0 },
+        ImageOperation::Contrast { amount: 0.0 },
+        ImageOperation::Saturation { amount: 0.0 },
+        ImageOperation::Posterize { levels: 8 },
+        ImageOperation::Blur { sigma: 1.0 },
+        ImageOperation::Denoise,
+        ImageOperation::Gamma { gamma: 1.0 },
+    ];
+
+    /// A new image, the alpha channel is kept.
+    pub fn apply(&self, image: &image::Rgba32FImage) -> image::Rgba32FImage {
+        use image::imageops;
+        match *self {
+            ImageOperation::Crop {
+                left,
+                top,
+                right,
+                bottom,
+            } => {
+                let (width, height) = image.dimensions();
+                let fraction_of =
+                    |value: f32, total: u32| (value.clamp(0.0, 1.0) * total as f32).round() as u32;
+                let x = fraction_of(left, width).min(width - 1);
+                let y = fraction_of(top, height).min(height - 1);
+                let new_width = width.saturating_sub(x + fraction_of(right, width)).max(1);
+                let new_height = height
+                    .saturating_sub(y + fraction_of(bottom, height))
+                    .max(1);
+                imageops::crop_imm(image, x, y, new_width, new_height).to_image()
+            }
+            ImageOperation::Rotate { quarter_turns } => match quarter_turns % 4 {
+                1 => imageops::rotate90(image),
+                2 => imageops::rotate180(image),
+                3 => imageops::rotate270(image),
+                _ => image.clone(),
+            },
+            ImageOperation::FlipHorizontal => imageops::flip_horizontal(image),
+            ImageOperation::FlipVertical => imageops::flip_vertical(image),
+            ImageOperation::Straighten { degrees } => straightened(image, degrees),
+            ImageOperation::Brightness { amount } => {
+                map_colors(image, |rgb| rgb.map(|c| c + amount))
+            }
+            ImageOperation::Contrast { amount } => map_colors(image, |rgb| {
+                rgb.map(|c| (c - 0.5) * (1.0 + amount).max(0.0) + 0.5)
+            }),
+            ImageOperation::Saturation { amount } => map_colors(image, |rgb| {
+                let grey = 0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2];
+                rgb.map(|c| grey + (c - grey) * (1.0 + amount).max(0.0))
+            }),
+            ImageOperation::Posterize { levels } => {
+                let steps = levels.max(2) as f32 - 1.0;
+                map_colors(image, |rgb| rgb.map(|c| (c * steps).round() / steps))
+            }
+            ImageOperation::Blur { sigma } if sigma > 0.0 => imageops::blur(image, sigma),
+            ImageOperation::Blur { .. } => image.clone(),
+            ImageOperation::Denoise => denoised(image),
+            ImageOperation::Gamma { gamma } if gamma > 0.0 => {
+                map_colors(image, |rgb| rgb.map(|c| c.max(0.0).powf(1.0 / gamma)))
+            }
+            ImageOperation::Gamma { .. } => image.clone(),
+        }
+    }
+}
+
+/// The operations one after the other, in order.
+pub fn apply_operations(
+    image: &image::Rgba32FImage,
+    operations: &[ImageOperation],
+) -> image::Rgba32FImage {
+    operations
+        .iter()
+        .fold(image.clone(), |processed, operation| {
+            operation.apply(&processed)
+        })
+}
+
+// The result is clamped between 0 and 1.
+fn map_colors(
+    image: &image::Rgba32FImage,
+    change: impl Fn([f32; 3]) -> [f32; 3],
+) -> image::Rgba32FImage {
+    let mut result = image.clone();
+    for pixel in result.pixels_mut() {
+        let [r, g, b, a] = pixel.0;
+        let [r, g, b] = change([r, g, b]).map(|c| c.clamp(0.0, 1.0));
+        pixel.0 = [r, g, b, a];
+    }
+    result
+}
+
+fn straightened(image: &image::Rgba32FImage, degrees: f32) -> image::Rgba32FImage {
+    if degrees.abs() < 0.01 {
+        return image.clone();
+    }
+    let (width, height) = (image.width() as f32, image.height() as f32);
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let scale = f32::min(
+        width / (width * cos.abs() + height * sin.abs()),
+        height / (width * sin.abs() + height * cos.abs()),
+    );
+    let new_width = ((width * scale).floor() as u32).max(1);
+    let new_height = ((height * scale).floor() as u32).max(1);
+    image::Rgba32FImage::from_fn(new_width, new_height, |x, y| {
+        // From the center of the new pixel back to the original image, turning the other way.
+        let dx = x as f32 + 0.5 - new_width as f32 / 2.0;
+        let dy = y as f32 + 0.5 - new_height as f32 / 2.0;
+        let source_x = dx * cos + dy * sin + width / 2.0 - 0.5;
+        let source_y = -dx * sin + dy * cos + height / 2.0 - 0.5;
+        bilinear(image, source_x, source_y)
+    })
+}
+
+fn bilinear(image: &image::Rgba32FImage, x: f32, y: f32) -> image::Rgba<f32> {
+    let max_x = image.width() as f32 - 1.0;
+    let max_y = image.height() as f32 - 1.0;
+    let (x, y) = (x.clamp(0.0, max_x), y.clamp(0.0, max_y));
+    let (left, top) = (x.floor(), y.floor());
+    let (right, bottom) = ((left + 1.0).min(max_x), (top + 1.0).min(max_y));
+    let (fx, fy) = (x - left, y - top);
+    let at = |px: f32, py: f32| image.get_pixel(px as u32, py as u32).0;
+    let [top_left, top_right, bottom_left, bottom_right] = [
+        at(left, top),
+        at(right, top),
+        at(left, bottom),
+        at(right, bottom),
+    ];
+    image::Rgba(std::array::from_fn(|channel| {
+        let upper = top_left[channel] * (1.0 - fx) + top_right[channel] * fx;
+        let lower = bottom_left[channel] * (1.0 - fx) + bottom_right[channel] * fx;
+        upper * (1.0 - fy) + lower * fy
+    }))
+}
+
+fn denoised(image: &image::Rgba32FImage) -> image::Rgba32FImage {
+    let (width, height) = image.dimensions();
+    image::Rgba32FImage::from_fn(width, height, |x, y| {
+        let mut neighbourhood = Vec::with_capacity(9);
+        for ny in y.saturating_sub(1)..=(y + 1).min(height - 1) {
+            for nx in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+                neighbourhood.push(image.get_pixel(nx, ny).0);
+            }
+        }
+        let mut pixel = image.get_pixel(x, y).0;
+        for (channel, value) in pixel.iter_mut().enumerate().take(3) {
+            let mut values: Vec<f32> = neighbourhood.iter().map(|p| p[channel]).collect();
+            values.sort_by(f32::total_cmp);
+            *value = values[values.len() / 2];
+        }
+        image::Rgba(pixel)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Red on the left half, blue on the right one.
+    fn red_and_blue(width: u32, height: u32) -> image::Rgba32FImage {
+        image::Rgba32FImage::from_fn(width, height, |x, _y| {
+            if x < width / 2 {
+                image::Rgba([1.0, 0.0, 0.0, 1.0])
+            } else {
+                image::Rgba([0.0, 0.0, 1.0, 1.0])
+            }
+        })
+    }
+
+    #[test]
+    fn geometric_operations_change_the_dimensions_and_move_the_pixels() {
+        let image = red_and_blue(40, 20);
+        let cropped = ImageOperation::Crop {
+            left: 0.5,
+            top: 0.0,
+            right: 0.25,
+            bottom: 0.5,
+        }
+        .apply(&image);
+        assert_eq!(cropped.dimensions(), (10, 10));
+        assert_eq!(cropped.get_pixel(0, 0).0, [0.0, 0.0, 1.0, 1.0]);
+
+        let rotated = ImageOperation::Rotate { quarter_turns: 1 }.apply(&image);
+        assert_eq!(rotated.dimensions(), (20, 40));
+        // The left half goes to the top.
+        assert_eq!(rotated.get_pixel(0, 0).0, [1.0, 0.0, 0.0, 1.0]);
+
+        let flipped = ImageOperation::FlipHorizontal.apply(&image);
+        assert_eq!(flipped.get_pixel(0, 0).0, [0.0, 0.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn straightening_keeps_the_aspect_ratio_without_empty_corners() {
+        let image = red_and_blue(200, 100);
+        assert_eq!(
+            ImageOperation::Straighten { degrees: 0.0 }.apply(&image),
+            image
+        );
+        let straightened = ImageOperation::Straighten { degrees: 5.0 }.apply(&image);
+        let (width, height) = straightened.dimensions();
+        assert!(width < 200 && height < 100);
+        assert!((width as f32 / height as f32 - 2.0).abs() < 0.05);
+        assert!(straightened.pixels().all(|pixel| pixel.0[3] == 1.0));
+        assert_eq!(
+            straightened.get_pixel(0, height / 2).0,
+            [1.0, 0.0, 0.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn color_operations_stay_between_0_and_1() {
+        let image = image::Rgba32FImage::from_pixel(4, 4, image::Rgba([0.2, 0.5, 0.9, 1.0]));
+        let brighter = ImageOperation::Brightness { amount: 0.3 }.apply(&image);
+        assert_eq!(brighter.get_pixel(0, 0).0, [0.5, 0.8, 1.0, 1.0]);
+        let grey = ImageOperation::Saturation { amount: -1.0 }.apply(&image);
+        let [r, g, b, _a] = grey.get_pixel(0, 0).0;
+        assert!((r - g).abs() < 1e-6 && (g - b).abs() < 1e-6);
+        let flat = ImageOperation::Contrast { amount: -1.0 }.apply(&image);
+        assert_eq!(flat.get_pixel(0, 0).0, [0.5, 0.5, 0.5, 1.0]);
+        let posterized = ImageOperation::Posterize { levels: 2 }.apply(&image);
+        assert_eq!(posterized.get_pixel(0, 0).0, [0.0, 1.0, 1.0, 1.0]);
+        let lighter = ImageOperation::Gamma { gamma: 2.0 }.apply(&image);
+        assert!((lighter.get_pixel(0, 0).0[1] - 0.5f32.sqrt()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn denoising_takes_out_a_single_speck() {
+        let mut image = image::Rgba32FImage::from_pixel(5, 5, image::Rgba([0.0, 0.0, 0.0, 1.0]));
+        image.put_pixel(2, 2, image::Rgba([1.0, 1.0, 1.0, 1.0]));
+        let denoised = ImageOperation::Denoise.apply(&image);
+        assert!(denoised
+            .pixels()
+            .all(|pixel| pixel.0 == [0.0, 0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn operations_are_applied_in_order() {
+        let image = red_and_blue(40, 20);
+        let operations = [
+            ImageOperation::Rotate { quarter_turns: 1 },
+            ImageOperation::Crop {
+                left: 0.0,
+                top: 0.5,
+                right: 0.0,
+                bottom: 0.0,
+            },
+        ];
+        let processed = apply_operations(&image, &operations);
+        assert_eq!(processed.dimensions(), (20, 20));
+        assert!(processed
+            .pixels()
+            .all(|pixel| pixel.0 == [0.0, 0.0, 1.0, 1.0]));
+    }
+}
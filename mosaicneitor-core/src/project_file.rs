@@ -0,0 +1,436 @@
+//! Saving a mosaic to a JSON file, and opening it again.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    andamento::GuideCurve,
+    mosaic::{BaseImage, FreeTessera, Mosaic, RectangleInMm, Tessera},
+    preprocessing::ImageOperation,
+    tessera_shape::TesseraShape,
+};
+
+/// Increment it every time the file structure changes.
+/// Fields added after version 1 must have a #[serde(default)],
+/// so older files keep opening.
+///
+/// Version 2 added the size of every tessera.
+/// Version 3 added the shape of every tessera.
+/// Version 4 added the free tesserae and the guide curves of the andamento.
+/// Version 5 added the operations on the base image.
+/// Version 6 made the zoom fractional.
+pub const CURRENT_SCHEMA_VERSION: u32 = 6;
+
+/// Project files end with it.
+pub const PROJECT_FILE_EXTENSION: &str = ".mosaic.json";
+
+/// What is stored in a project file.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ProjectFile {
+    /// The file structure version it was written with.
+    pub schema_version: u32,
+    /// A reference to the base image, the image itself is not stored.
+    pub base_image_path: Option<std::path::PathBuf>,
+    /// To notice if the base image has changed since. As in the file, before the image operations.
+    pub base_image_size_px: Option<[u32; 2]>,
+    /// Applied to the base image, in order, before sampling the tesserae.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub image_operations: Vec<ImageOperation>,
+    /// As asked for when generating the mosaic.
+    pub mosaic_dimensions_mm: [usize; 2],
+    /// [horizontal, vertical]
+    pub general_tessera_size_mm: [usize; 2],
+    /// Row by row, like [`Mosaic::get_contents`].
+    pub tesserae: Vec<Vec<TesseraInFile>>,
+    /// Out of the grid, like [`Mosaic::get_free_tesserae`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub free_tesserae: Vec<FreeTesseraInFile>,
+    /// The flow lines the free tesserae were laid along.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub guide_curves: Vec<GuideCurve>,
+    /// How the mosaic was being displayed.
+    pub view: ViewSettings,
+}
+
+/// A tessera: its color, in Oklch, and its size.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct TesseraInFile {
+    /// Lightness.
+    pub l: f32,
+    /// Chroma.
+    pub chroma: f32,
+    /// Hue, in degrees.
+    pub hue: f32,
+    /// [horizontal, vertical] in mm. Only stored when it is not the general tessera size.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_mm: Option<[usize; 2]>,
+    /// Only stored for cut tesserae.
+    #[serde(default, skip_serializing_if = "is_whole")]
+    pub shape: TesseraShape,
+}
+
+fn is_whole(shape: &TesseraShape) -> bool {
+    *shape == TesseraShape::Whole
+}
+
+/// A tessera out of the grid, with its position and rotation.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct FreeTesseraInFile {
+    /// Color, size and shape.
+    #[serde(flatten)]
+    pub tessera: TesseraInFile,
+    /// [horizontal, vertical] in mm from the top left corner of the mosaic.
+    pub center_mm: [f32; 2],
+    /// Clockwise.
+    pub rotation_degrees: f32,
+}
+
+/// Display options saved with the project.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ViewSettings {
+    /// Show the base image.
+    pub show_image: bool,
+    /// Show the outline of the tesserae.
+    pub show_tesserae_grid: bool,
+    /// Show the tesserae filled with their colors.
+    pub show_actual_tesserae: bool,
+    /// Display zoom, in screen points per mm.
+    pub zoom_factor: f32,
+}
+
+impl ProjectFile {
+    /// Takes everything to store from the mosaic, except the base image, that is referenced by path.
+    /// The guide curves and the image operations are not part of the mosaic, set them apart.
+    pub fn new(
+        mosaic: &Mosaic,
+        base_image_path: Option<std::path::PathBuf>,
+        mosaic_dimensions_mm: [usize; 2],
+        view: ViewSettings,
+    ) -> Self {
+        Self {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            base_image_path,
+            base_image_size_px: mosaic
+                .get_base_image()
+                .map(|img| [img.width(), img.height()]),
+            image_operations: Vec::new(),
+            mosaic_dimensions_mm,
+            general_tessera_size_mm: [
+                mosaic.get_general_tessera_size().horizontal,
+                mosaic.get_general_tessera_size().vertical,
+            ],
+            tesserae: mosaic
+                .get_contents()
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|tessera| {
+                            TesseraInFile::new(tessera, mosaic.get_general_tessera_size())
+                        })
+                        .collect()
+                })
+                .collect(),
+            free_tesserae: mosaic
+                .get_free_tesserae()
+                .iter()
+                .map(|free_tessera| FreeTesseraInFile {
+                    tessera: TesseraInFile::new(
+                        &free_tessera.tessera,
+                        mosaic.get_general_tessera_size(),
+                    ),
+                    center_mm: free_tessera.center_mm,
+                    rotation_degrees: free_tessera.rotation_degrees,
+                })
+                .collect(),
+            guide_curves: Vec::new(),
+            view,
+        }
+    }
+
+    /// The base image is not in the file, load it apart and pass it here.
+    pub fn to_mosaic(&self, base_image: Option<BaseImage>) -> Mosaic {
+        let general_tessera_size = RectangleInMm {
+            horizontal: self.general_tessera_size_mm[0],
+            vertical: self.general_tessera_size_mm[1],
+        };
+        let mut mosaic = Mosaic::new(base_image, general_tessera_size);
+        for row in &self.tesserae {
+            mosaic.add_a_row_of_tesserae(
+                row.iter()
+                    .map(|tessera| tessera.to_tessera(general_tessera_size))
+                    .collect(),
+            );
+        }
+        for free_tessera in &self.free_tesserae {
+            mosaic.add_free_tessera(FreeTessera {
+                tessera: free_tessera.tessera.to_tessera(general_tessera_size),
+                center_mm: free_tessera.center_mm,
+                rotation_degrees: free_tessera.rotation_degrees,
+            });
+        }
+        mosaic
+    }
+
+    /// Writes it as pretty printed JSON.
+    pub fn save_to_file(&self, path: &std::path::Path) -> Result<(), String> {
+        let text = match serde_json::to_string_pretty(self) {
+            Ok(text) => text,
+            Err(e) => return Err(format!("Cannot convert the project to JSON: {e}")),
+        };
+        match std::fs::write(path, text) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Cannot write {}: {e}", path.display())),
+        }
+    }
+
+    /// Reads and checks a project file, see [`ProjectFile::from_json`].
+    pub fn load_from_file(path: &std::path::Path) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::from_json(&text),
+            Err(e) => Err(format!("Cannot read {}: {e}", path.display())),
+        }
+    }
+
+    /// Fails on files without a schema version or written by a newer version.
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let value: serde_json::Value = match serde_json::from_str(text) {
+            Ok(value) => value,
+            Err(e) => return Err(format!("The project file is not valid JSON: {e}")),
+        };
+        let schema_version = match value.get("schema_version").and_then(|v| v.as_u64()) {
+            Some(version) => version as u32,
+            None => return Err(String::from("The project file has no schema_version.")),
+        };
+        if schema_version > CURRENT_SCHEMA_VERSION {
+            return Err(format!(
+                "The project file has schema version {}, but this program only knows up to version {}. Please, update the program.",
+                schema_version, CURRENT_SCHEMA_VERSION
+            ));
+        }
+        match serde_json::from_value::<ProjectFile>(value) {
+            Ok(mut project) => {
+                project.schema_version = CURRENT_SCHEMA_VERSION;
+                Ok(project)
+            }
+            Err(e) => Err(format!("The project file is damaged: {e}")),
+        }
+    }
+}
+
+impl TesseraInFile {
+    fn new(tessera: &Tessera, general_tessera_size: &RectangleInMm) -> Self {
+        Self {
+            l: tessera.color.l,
+            chroma: tessera.color.chroma,
+            hue: tessera.color.hue.into_positive_degrees(),
+            size_mm: (tessera.size != *general_tessera_size)
+                .then_some([tessera.size.horizontal, tessera.size.vertical]),
+            shape: tessera.shape.clone(),
+        }
+    }
+
+    // Files from version 1 have no sizes, all their tesserae have the general size.
+    // Files before version 3 have no shapes, all their tesserae are whole.
+    fn to_tessera(&self, general_tessera_size: RectangleInMm) -> Tessera {
+        Tessera {
+            color: palette::Oklch::new(self.l, self.chroma, self.hue),
+            size: match self.size_mm {
+                Some([horizontal, vertical]) => RectangleInMm {
+                    horizontal,
+                    vertical,
+                },
+                None => general_tessera_size,
+            },
+            shape: self.shape.clone(),
+        }
+    }
+}
+
+/// Appends [`PROJECT_FILE_EXTENSION`] if the path does not end with it.
+pub fn add_extension_if_missing(path: std::path::PathBuf) -> std::path::PathBuf {
+    if path.to_string_lossy().ends_with(PROJECT_FILE_EXTENSION) {
+        path
+    } else {
+        let mut with_extension = path.into_os_string();
+        with_extension.push(PROJECT_FILE_EXTENSION);
+        std::path::PathBuf::from(with_extension)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        mosaic::{a_mosaic_of_colors, PositionOnGrid},
+        tessera_shape::CutPreset,
+    };
+
+    fn a_small_mosaic() -> Mosaic {
+        a_mosaic_of_colors(
+            [10, 20],
+            (0..3)
+                .map(|row| {
+                    (0..4)
+                        .map(|column| {
+                            palette::Oklch::new(0.1 * row as f32, 0.01 * column as f32, 90.0)
+                        })
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+
+    fn some_view_settings() -> ViewSettings {
+        ViewSettings {
+            show_image: false,
+            show_tesserae_grid: true,
+            show_actual_tesserae: true,
+            zoom_factor: 2.5,
+        }
+    }
+
+    #[test]
+    fn a_project_survives_a_round_trip_through_json() {
+        let project = ProjectFile::new(
+            &a_small_mosaic(),
+            Some(std::path::PathBuf::from("some/image.jpg")),
+            [500, 300],
+            some_view_settings(),
+        );
+        let text = serde_json::to_string(&project).unwrap();
+        let reloaded = ProjectFile::from_json(&text).unwrap();
+        assert_eq!(reloaded, project);
+
+        let mosaic = reloaded.to_mosaic(None);
+        assert_eq!(mosaic.get_number_of_rows(), 3);
+        assert_eq!(mosaic.get_number_of_tesserae_in_row(2), 4);
+        assert_eq!(mosaic.get_general_tessera_size().vertical, 20);
+        assert_eq!(mosaic.get_contents()[2][3].color.l, 0.2);
+    }
+
+    #[test]
+    fn only_the_sizes_different_from_the_general_one_and_cut_shapes_are_stored() {
+        let mut mosaic = a_small_mosaic();
+        let half_tile = Tessera {
+            color: palette::Oklch::new(0.5, 0.0, 0.0),
+            size: RectangleInMm {
+                horizontal: 5,
+                vertical: 20,
+            },
+            shape: TesseraShape::Preset(CutPreset::WedgeUp),
+        };
+        mosaic
+            .change_tessera(&PositionOnGrid { row: 1, column: 4 }, half_tile.clone())
+            .unwrap();
+        let project = ProjectFile::new(&mosaic, None, [500, 300], some_view_settings());
+        assert_eq!(project.tesserae[0][3].size_mm, Some([5, 20]));
+        assert_eq!(project.tesserae[0][2].size_mm, None);
+        assert!(!serde_json::to_string(&project.tesserae[0][2])
+            .unwrap()
+            .contains("size_mm"));
+        assert_eq!(
+            project.tesserae[0][3].shape,
+            TesseraShape::Preset(CutPreset::WedgeUp)
+        );
+        assert!(!serde_json::to_string(&project.tesserae[0][2])
+            .unwrap()
+            .contains("shape"));
+
+        let reloaded = ProjectFile::from_json(&serde_json::to_string(&project).unwrap()).unwrap();
+        assert_eq!(reloaded.to_mosaic(None).get_contents()[0][3], half_tile);
+    }
+
+    #[test]
+    fn free_tesserae_and_guide_curves_survive_a_round_trip() {
+        let mut mosaic = a_small_mosaic();
+        let free_tessera = FreeTessera {
+            tessera: Tessera {
+                color: palette::Oklch::new(0.5, 0.1, 30.0),
+                size: RectangleInMm {
+                    horizontal: 10,
+                    vertical: 5,
+                },
+                shape: TesseraShape::Whole,
+            },
+            center_mm: [40.5, 12.25],
+            rotation_degrees: 30.0,
+        };
+        mosaic.add_free_tessera(free_tessera.clone());
+        let mut project = ProjectFile::new(&mosaic, None, [500, 300], some_view_settings());
+        project.guide_curves = vec![GuideCurve {
+            points: vec![[0.0, 1.0], [2.0, 3.0]],
+        }];
+        let text = serde_json::to_string(&project).unwrap();
+        assert!(text.contains("\"size_mm\":[10,5],\"center_mm\""));
+        let reloaded = ProjectFile::from_json(&text).unwrap();
+        assert_eq!(reloaded, project);
+        assert_eq!(
+            reloaded.to_mosaic(None).get_free_tesserae(),
+            &vec![free_tessera]
+        );
+    }
+
+    #[test]
+    fn image_operations_survive_a_round_trip() {
+        let mut project =
+            ProjectFile::new(&a_small_mosaic(), None, [500, 300], some_view_settings());
+        assert!(!serde_json::to_string(&project)
+            .unwrap()
+            .contains("image_operations"));
+        project.image_operations = vec![
+            ImageOperation::Crop {
+                left: 0.1,
+                top: 0.0,
+                right: 0.2,
+                bottom: 0.05,
+            },
+            ImageOperation::FlipVertical,
+            ImageOperation::Gamma { gamma: 1.4 },
+        ];
+        let text = serde_json::to_string(&project).unwrap();
+        assert_eq!(ProjectFile::from_json(&text).unwrap(), project);
+    }
+
+    #[test]
+    fn files_from_version_1_open_with_the_general_tessera_size() {
+        let text = r#"{"schema_version": 1, "base_image_path": null, "base_image_size_px": null,
+            "mosaic_dimensions_mm": [20, 10], "general_tessera_size_mm": [8, 9],
+            "tesserae": [[{"l": 0.5, "chroma": 0.1, "hue": 30.0}]],
+            "view": {"show_image": false, "show_tesserae_grid": true, "show_actual_tesserae": true, "zoom_factor": 1}}"#;
+        let project = ProjectFile::from_json(text).unwrap();
+        // Integer zooms from older files are read as fractional ones.
+        assert_eq!(project.view.zoom_factor, 1.0);
+        let mosaic = project.to_mosaic(None);
+        assert_eq!(
+            mosaic.get_contents()[0][0].size,
+            RectangleInMm {
+                horizontal: 8,
+                vertical: 9
+            }
+        );
+    }
+
+    #[test]
+    fn files_from_a_newer_schema_version_are_rejected() {
+        let project = ProjectFile::new(&a_small_mosaic(), None, [500, 300], some_view_settings());
+        let mut value = serde_json::to_value(&project).unwrap();
+        value["schema_version"] = serde_json::json!(CURRENT_SCHEMA_VERSION + 1);
+        assert!(ProjectFile::from_json(&value.to_string()).is_err());
+    }
+
+    #[test]
+    fn files_without_schema_version_are_rejected() {
+        assert!(ProjectFile::from_json("{\"tesserae\": []}").is_err());
+    }
+
+    #[test]
+    fn the_extension_is_added_only_when_missing() {
+        assert_eq!(
+            add_extension_if_missing(std::path::PathBuf::from("a/b")),
+            std::path::PathBuf::from("a/b.mosaic.json")
+        );
+        assert_eq!(
+            add_extension_if_missing(std::path::PathBuf::from("a/b.mosaic.json")),
+            std::path::PathBuf::from("a/b.mosaic.json")
+        );
+    }
+}
@@ -0,0 +1,313 @@
+//! Proposing a palette when there is none yet: the tessera colors reduced to a few clusters.
+
+use crate::{
+    colors, materials, mosaic::Mosaic, tessera_shape::TesseraShape, tile_palette::TilePalette,
+};
+
+/// A group of similar tessera colors, a candidate tile color.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ColorCluster {
+    /// Named after its color at first, it can be renamed.
+    pub name: String,
+    /// The center of the cluster, the color its tesserae get.
+    pub color: palette::Oklch,
+    /// Tesserae in the cluster.
+    pub count: usize,
+    /// Kept, with its color and name, when the clustering runs again.
+    pub locked: bool,
+}
+
+/// The clusters found on the colors of the mosaic, the most used first.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ProposedPalette {
+    /// One per proposed color.
+    pub clusters: Vec<ColorCluster>,
+}
+
+// k-means usually settles much earlier.
+const MAX_ITERATIONS: usize = 30;
+
+impl ProposedPalette {
+    /// k-means in Oklab over the colors of every tessera of the mosaic. Locked clusters keep
+    /// their color and the rest are proposed again, up to number_of_colors in total.
+    /// Seeds are the most common color, then, one at a time, the color farthest from the chosen ones.
+    pub fn run(&mut self, mosaic: &Mosaic, number_of_colors: usize) {
+        let samples = get_distinct_colors(mosaic);
+        let mut clusters: Vec<ColorCluster> = self
+            .clusters
+            .iter()
+            .filter(|cluster| cluster.locked)
+            .cloned()
+            .collect();
+        let mut centers: Vec<[f32; 3]> = clusters
+            .iter()
+            .map(|cluster| colors::oklab_from_oklch(cluster.color))
+            .collect();
+        if centers.is_empty() {
+            if let Some((most_common, _count)) = samples.iter().max_by_key(|(_color, count)| *count)
+            {
+                centers.push(*most_common);
+            }
+        }
+        while centers.len() < number_of_colors {
+            let farthest = samples
+                .iter()
+                .map(|(color, _count)| (*color, distance_to_nearest(&centers, *color).1))
+                .max_by(|a, b| a.1.total_cmp(&b.1));
+            match farthest {
+                Some((color, distance)) if distance > 0.0 => centers.push(color),
+                _ => break,
+            }
+        }
+
+        let number_of_locked = clusters.len();
+        let mut assignments = vec![usize::MAX; samples.len()];
+        for _iteration in 0..MAX_ITERATIONS {
+            let mut changed = false;
+            for (assignment, (color, _count)) in assignments.iter_mut().zip(&samples) {
+                let nearest = distance_to_nearest(&centers, *color).0;
+                changed |= *assignment != nearest;
+                *assignment = nearest;
+            }
+            if !changed {
+                break;
+            }
+            for (index, center) in centers.iter_mut().enumerate().skip(number_of_locked) {
+                let mut sum = [0.0; 3];
+                let mut weight = 0;
+                for ((color, count), _assignment) in samples
+                    .iter()
+                    .zip(&assignments)
+                    .filter(|(_sample, assignment)| **assignment == index)
+                {
+                    for axis in 0..3 {
+                        sum[axis] += color[axis] * *count as f32;
+                    }
+                    weight += count;
+                }
+                if weight > 0 {
+                    *center = sum.map(|value| value / weight as f32);
+                }
+            }
+        }
+
+        let mut counts = vec![0; centers.len()];
+        for (assignment, (_color, count)) in assignments.iter().zip(&samples) {
+            if let Some(total) = counts.get_mut(*assignment) {
+                *total += count;
+            }
+        }
+        for (cluster, count) in clusters.iter_mut().zip(&counts) {
+            cluster.count = *count;
+        }
+        for (center, count) in centers.iter().zip(&counts).skip(number_of_locked) {
+            if *count == 0 {
+                continue;
+            }
+            let color = colors::oklch_from_oklab(*center);
+            clusters.push(ColorCluster {
+                name: materials::hex_color(colors::srgb8_from_oklch(color)),
+                color,
+                count: *count,
+                locked: false,
+            });
+        }
+        clusters.sort_by_key(|cluster| std::cmp::Reverse(cluster.count));
+        self.clusters = clusters;
+    }
+
+    /// The second cluster joins the first one, that keeps its name. The color is the weighted mean,
+    /// unless one of them is locked, then it is the color of the locked one.
+    pub fn merge(&mut self, into: usize, other: usize) -> Result<(), String> {
+        if into == other || into >= self.clusters.len() || other >= self.clusters.len() {
+            return Err(format!("Cannot merge the clusters {into} and {other}."));
+        }
+        let removed = self.clusters[other].clone();
+        let kept = &mut self.clusters[into];
+        kept.color = match (kept.locked, removed.locked) {
+            (true, _) => kept.color,
+            (false, true) => removed.color,
+            (false, false) => {
+                let total = (kept.count + removed.count).max(1) as f32;
+                let a = colors::oklab_from_oklch(kept.color);
+                let b = colors::oklab_from_oklch(removed.color);
+                colors::oklch_from_oklab(std::array::from_fn(|axis| {
+                    (a[axis] * kept.count as f32 + b[axis] * removed.count as f32) / total
+                }))
+            }
+        };
+        kept.count += removed.count;
+        kept.locked |= removed.locked;
+        self.clusters.remove(other);
+        Ok(())
+    }
+
+    /// The nearest cluster color for every tessera, in the order of [`Mosaic::get_all_tesserae`].
+    /// The colors are kept if there are no clusters.
+    pub fn get_quantized_colors(&self, mosaic: &Mosaic) -> Vec<palette::Oklch> {
+        mosaic
+            .get_all_tesserae()
+            .map(|tessera| {
+                self.clusters
+                    .iter()
+                    .min_by(|a, b| {
+                        colors::oklab_distance(a.color, tessera.color)
+                            .total_cmp(&colors::oklab_distance(b.color, tessera.color))
+                    })
+                    .map_or(tessera.color, |cluster| cluster.color)
+            })
+            .collect()
+    }
+
+    /// Adds an entry for every cluster to the tile palette, to complete with the supplier data.
+    /// The entries already there are kept, and colors already in it are not added again.
+    /// Yields how many entries were added.
+    pub fn add_to_tile_palette(&self, tile_palette: &mut TilePalette) -> usize {
+        let mut added = 0;
+        for cluster in &self.clusters {
+            let srgb = colors::srgb8_from_oklch(cluster.color);
+            if tile_palette.entries.iter().any(|entry| entry.srgb == srgb) {
+                continue;
+            }
+            tile_palette.add_entry(srgb);
+            if let Some(entry) = tile_palette.entries.last_mut() {
+                entry.name = cluster.name.clone();
+            }
+            added += 1;
+        }
+        added
+    }
+}
+
+// Every color once, in Oklab, with how many tesserae have it. In order of appearance.
+// Empty cells have no color of their own.
+fn get_distinct_colors(mosaic: &Mosaic) -> Vec<([f32; 3], usize)> {
+    let mut index_of = std::collections::HashMap::new();
+    let mut distinct: Vec<([f32; 3], usize)> = Vec::new();
+    for tessera in mosaic
+        .get_all_tesserae()
+        .filter(|tessera| tessera.shape != TesseraShape::Empty)
+    {
+        let color = colors::oklab_from_oklch(tessera.color);
+        let index = *index_of.entry(color.map(f32::to_bits)).or_insert_with(|| {
+            distinct.push((color, 0));
+            distinct.len() - 1
+        });
+        distinct[index].1 += 1;
+    }
+    distinct
+}
+
+// Index of the nearest center and the distance to it.
+fn distance_to_nearest(centers: &[[f32; 3]], color: [f32; 3]) -> (usize, f32) {
+    centers
+        .iter()
+        .map(|center| colors::distance_in_oklab(*center, color))
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap_or((0, f32::MAX))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mosaic::a_mosaic_of_colors;
+
+    // A row of tesserae per color, as many as the count.
+    fn a_mosaic_with(colors_and_counts: &[([u8; 3], usize)]) -> Mosaic {
+        a_mosaic_of_colors(
+            [10, 10],
+            colors_and_counts
+                .iter()
+                .map(|(srgb, count)| vec![colors::oklch_from_srgb8(*srgb); *count])
+                .collect(),
+        )
+    }
+
+    fn reds_and_blues() -> Mosaic {
+        a_mosaic_with(&[
+            ([250, 0, 0], 5),
+            ([230, 20, 10], 3),
+            ([0, 0, 250], 2),
+            ([10, 20, 230], 1),
+        ])
+    }
+
+    #[test]
+    fn similar_colors_end_up_in_the_same_cluster() {
+        let mut proposed = ProposedPalette::default();
+        proposed.run(&reds_and_blues(), 2);
+        assert_eq!(proposed.clusters.len(), 2);
+        assert_eq!(proposed.clusters[0].count, 8);
+        assert_eq!(proposed.clusters[1].count, 3);
+        let [r, _g, b] = colors::srgb8_from_oklch(proposed.clusters[0].color);
+        assert!(r > 200 && b < 50);
+
+        let quantized = proposed.get_quantized_colors(&reds_and_blues());
+        assert_eq!(quantized.len(), 11);
+        assert_eq!(quantized[0], quantized[7]);
+        assert_eq!(quantized[8], quantized[10]);
+    }
+
+    #[test]
+    fn there_are_no_more_clusters_than_distinct_colors() {
+        let mut proposed = ProposedPalette::default();
+        proposed.run(&reds_and_blues(), 10);
+        assert_eq!(proposed.clusters.len(), 4);
+    }
+
+    #[test]
+    fn locked_clusters_keep_their_color_and_name_when_running_again() {
+        let mut proposed = ProposedPalette::default();
+        proposed.run(&reds_and_blues(), 2);
+        proposed.clusters[1].name = String::from("Cobalt");
+        proposed.clusters[1].locked = true;
+        let cobalt = proposed.clusters[1].color;
+        proposed.run(&reds_and_blues(), 3);
+        assert_eq!(proposed.clusters.len(), 3);
+        let locked: Vec<&ColorCluster> = proposed.clusters.iter().filter(|c| c.locked).collect();
+        assert_eq!(locked.len(), 1);
+        assert_eq!(locked[0].name, "Cobalt");
+        assert_eq!(locked[0].color, cobalt);
+        assert_eq!(locked[0].count, 3);
+    }
+
+    #[test]
+    fn merged_clusters_add_their_counts_and_keep_the_first_name() {
+        let mut proposed = ProposedPalette::default();
+        proposed.run(&reds_and_blues(), 4);
+        let first_name = proposed.clusters[0].name.clone();
+        proposed.merge(0, 3).unwrap();
+        assert_eq!(proposed.clusters.len(), 3);
+        assert_eq!(proposed.clusters[0].name, first_name);
+        assert_eq!(proposed.clusters[0].count, 6);
+        assert!(proposed.merge(1, 1).is_err());
+        assert!(proposed.merge(0, 7).is_err());
+    }
+
+    #[test]
+    fn the_clusters_are_added_to_the_tile_palette_keeping_its_entries() {
+        let mut proposed = ProposedPalette::default();
+        proposed.run(
+            &a_mosaic_with(&[([255, 255, 255], 2), ([0, 0, 0], 1), ([0, 0, 250], 1)]),
+            3,
+        );
+        proposed.clusters[0].name = String::from("White");
+        let mut tile_palette = TilePalette::default();
+        tile_palette.add_entry([0, 0, 0]);
+        tile_palette.entries[0].name = String::from("Nero");
+        tile_palette.entries[0].supplier_code = String::from("N-01");
+        tile_palette.entries[0].price_per_square_meter = 42.0;
+
+        assert_eq!(proposed.add_to_tile_palette(&mut tile_palette), 2);
+        assert_eq!(tile_palette.entries.len(), 3);
+        assert_eq!(tile_palette.entries[0].name, "Nero");
+        assert_eq!(tile_palette.entries[0].supplier_code, "N-01");
+        assert_eq!(tile_palette.entries[0].price_per_square_meter, 42.0);
+        assert_eq!(tile_palette.entries[1].name, "White");
+        assert_eq!(tile_palette.entries[1].srgb, [255, 255, 255]);
+        assert_eq!(tile_palette.entries[2].srgb, [0, 0, 250]);
+        // Nothing new the second time.
+        assert_eq!(proposed.add_to_tile_palette(&mut tile_palette), 0);
+    }
+}
@@ -0,0 +1,338 @@
+//! Rectangular blocks of rows and columns of the grid, to copy, turn and paste motifs.
+
+use crate::{
+    history::TesseraChange,
+    mosaic::{Mosaic, PositionOnGrid, Tessera},
+    tessera_shape::TesseraShape,
+};
+
+/// From the first to the last position, both included.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct GridRectangle {
+    /// Top left.
+    pub first: PositionOnGrid,
+    /// Bottom right.
+    pub last: PositionOnGrid,
+}
+
+impl GridRectangle {
+    /// The rectangle with both positions as corners, in any order.
+    pub fn new(corner: PositionOnGrid, opposite_corner: PositionOnGrid) -> Self {
+        Self {
+            first: PositionOnGrid {
+                row: corner.row.min(opposite_corner.row),
+                column: corner.column.min(opposite_corner.column),
+            },
+            last: PositionOnGrid {
+                row: corner.row.max(opposite_corner.row),
+                column: corner.column.max(opposite_corner.column),
+            },
+        }
+    }
+
+    /// The smallest rectangle with all the positions in it. None without positions.
+    pub fn around(positions: &[PositionOnGrid]) -> Option<Self> {
+        let first = positions.first()?;
+        Some(
+            positions
+                .iter()
+                .fold(Self::new(*first, *first), |rectangle, position| Self {
+                    first: Self::new(rectangle.first, *position).first,
+                    last: Self::new(rectangle.last, *position).last,
+                }),
+        )
+    }
+
+    /// [rows, columns].
+    pub fn get_size(&self) -> [usize; 2] {
+        [
+            self.last.row - self.first.row + 1,
+            self.last.column - self.first.column + 1,
+        ]
+    }
+
+    /// Whether the position is inside, the sides included.
+    pub fn contains(&self, position: &PositionOnGrid) -> bool {
+        (self.first.row..=self.last.row).contains(&position.row)
+            && (self.first.column..=self.last.column).contains(&position.column)
+    }
+
+    /// The same rectangle, some rows down and some columns right (or up and left if negative).
+    /// None if it would start before the first row or column.
+    pub fn moved(&self, rows: isize, columns: isize) -> Option<Self> {
+        let move_position = |position: PositionOnGrid| {
+            Some(PositionOnGrid {
+                row: position
+                    .row
+                    .checked_add_signed(rows)
+                    .filter(|row| *row > 0)?,
+                column: position
+                    .column
+                    .checked_add_signed(columns)
+                    .filter(|column| *column > 0)?,
+            })
+        };
+        Some(Self {
+            first: move_position(self.first)?,
+            last: move_position(self.last)?,
+        })
+    }
+
+    /// Row by row.
+    pub fn get_positions(&self) -> Vec<PositionOnGrid> {
+        (self.first.row..=self.last.row)
+            .flat_map(|row| {
+                (self.first.column..=self.last.column)
+                    .map(move |column| PositionOnGrid { row, column })
+            })
+            .collect()
+    }
+}
+
+/// How to change the content of a region.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RegionTransform {
+    /// Left to right.
+    FlipHorizontal,
+    /// Top to bottom.
+    FlipVertical,
+    /// A quarter turn clockwise. The rows become columns.
+    RotateClockwise,
+}
+
+/// The tesserae of a rectangle of the grid, copied to paste them somewhere else.
+/// Rows of the grid can be of different lengths, so some cells may be empty.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TesseraRegion {
+    cells: Vec<Vec<Option<Tessera>>>,
+}
+
+impl TesseraRegion {
+    /// The tesserae in the rectangle. Cells outside the mosaic are empty.
+    pub fn copy_from(mosaic: &Mosaic, rectangle: &GridRectangle) -> Self {
+        let cells = (rectangle.first.row..=rectangle.last.row)
+            .map(|row| {
+                (rectangle.first.column..=rectangle.last.column)
+                    .map(|column| mosaic.get_tessera(&PositionOnGrid { row, column }).cloned())
+                    .collect()
+            })
+            .collect();
+        Self { cells }
+    }
+
+    /// [rows, columns].
+    pub fn get_size(&self) -> [usize; 2] {
+        [
+            self.cells.len(),
+            self.cells.first().map_or(0, |row| row.len()),
+        ]
+    }
+
+    /// The content is changed in place, shapes included.
+    pub fn transform(&mut self, transform: RegionTransform) {
+        let change_tessera = |tessera: &mut Tessera| match transform {
+            RegionTransform::FlipHorizontal => {
+                tessera.shape = tessera.shape.mirrored(&tessera.size)
+            }
+            RegionTransform::FlipVertical => {
+                tessera.shape = tessera.shape.flipped_vertically(&tessera.size)
+            }
+            RegionTransform::RotateClockwise => {
+                tessera.shape = tessera.shape.rotated_clockwise(&tessera.size);
+                std::mem::swap(&mut tessera.size.horizontal, &mut tessera.size.vertical);
+            }
+        };
+        for tessera in self.cells.iter_mut().flatten().flatten() {
+            change_tessera(tessera);
+        }
+        match transform {
+            RegionTransform::FlipHorizontal => {
+                for row in &mut self.cells {
+                    row.reverse();
+                }
+            }
+            RegionTransform::FlipVertical => self.cells.reverse(),
+            RegionTransform::RotateClockwise => {
+                let [rows, columns] = self.get_size();
+                // The last row becomes the first column.
+                self.cells = (0..columns)
+                    .map(|column| {
+                        (0..rows)
+                            .rev()
+                            .map(|row| self.cells[row][column].clone())
+                            .collect()
+                    })
+                    .collect();
+            }
+        }
+    }
+
+    /// The changes to put the tesserae with their top left one at the given position.
+    /// Every tessera keeps the size of the one it replaces, so the rows are not broken,
+    /// and its shape is stretched to it. Empty cells and cells outside the mosaic are skipped.
+    pub fn get_changes_to_paste(&self, mosaic: &Mosaic, at: PositionOnGrid) -> Vec<TesseraChange> {
+        let mut changes = Vec::new();
+        for (row_offset, row) in self.cells.iter().enumerate() {
+            for (column_offset, cell) in row.iter().enumerate() {
+                let position = PositionOnGrid {
+                    row: at.row + row_offset,
+                    column: at.column + column_offset,
+                };
+                if let (Some(tessera), Some(replaced)) = (cell, mosaic.get_tessera(&position)) {
+                    changes.push(TesseraChange {
+                        position,
+                        tessera: Tessera {
+                            color: tessera.color,
+                            size: replaced.size,
+                            shape: tessera.shape.resized(&tessera.size, &replaced.size),
+                        },
+                    });
+                }
+            }
+        }
+        changes
+    }
+}
+
+/// The changes to leave the rectangle with empty cells, as after cutting it. They keep their size,
+/// so the rest of the grid stays in place, and get the given color, usually the grout's.
+pub fn get_changes_to_clear(
+    mosaic: &Mosaic,
+    rectangle: &GridRectangle,
+    color: palette::Oklch,
+) -> Vec<TesseraChange> {
+    rectangle
+        .get_positions()
+        .into_iter()
+        .filter_map(|position| {
+            mosaic.get_tessera(&position).map(|tessera| TesseraChange {
+                position,
+                tessera: Tessera {
+                    color,
+                    size: tessera.size,
+                    shape: TesseraShape::Empty,
+                },
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{colors, history::EditHistory, history::MosaicEdit, mosaic::a_mosaic_of_colors};
+
+    // 4x4 tesserae of 10 mm, each one with its own lightness, 1 for the first one.
+    fn a_mosaic_of_lightnesses() -> Mosaic {
+        a_mosaic_of_colors(
+            [10, 10],
+            (0..4)
+                .map(|row| {
+                    (0..4)
+                        .map(|column| {
+                            palette::Oklch::new((row * 4 + column + 1) as f32 / 100.0, 0.0, 0.0)
+                        })
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+
+    fn at(row: usize, column: usize) -> PositionOnGrid {
+        PositionOnGrid { row, column }
+    }
+
+    fn lightness_at(mosaic: &Mosaic, row: usize, column: usize) -> usize {
+        (mosaic.get_tessera(&at(row, column)).unwrap().color.l * 100.0).round() as usize
+    }
+
+    #[test]
+    fn rectangles_are_sorted_and_moved() {
+        let rectangle = GridRectangle::new(at(3, 1), at(2, 4));
+        assert_eq!(rectangle.first, at(2, 1));
+        assert_eq!(rectangle.last, at(3, 4));
+        assert_eq!(rectangle.get_size(), [2, 4]);
+        assert!(rectangle.contains(&at(3, 2)));
+        assert!(!rectangle.contains(&at(1, 2)));
+        assert_eq!(rectangle.get_positions().len(), 8);
+        assert_eq!(
+            GridRectangle::around(&[at(2, 3), at(4, 1), at(3, 2)]),
+            Some(GridRectangle::new(at(2, 1), at(4, 3)))
+        );
+        assert_eq!(
+            rectangle.moved(1, 2),
+            Some(GridRectangle::new(at(3, 3), at(4, 6)))
+        );
+        assert_eq!(rectangle.moved(0, -1), None);
+    }
+
+    #[test]
+    fn a_copied_region_is_pasted_elsewhere_and_can_be_undone() {
+        let mut mosaic = a_mosaic_of_lightnesses();
+        let region = TesseraRegion::copy_from(&mosaic, &GridRectangle::new(at(1, 1), at(2, 2)));
+        assert_eq!(region.get_size(), [2, 2]);
+        // Partly out of the mosaic.
+        let changes = region.get_changes_to_paste(&mosaic, at(3, 4));
+        assert_eq!(changes.len(), 2);
+        let mut history = EditHistory::new(None);
+        history
+            .apply(&mut mosaic, MosaicEdit::ChangeTesserae(changes))
+            .unwrap();
+        assert_eq!(lightness_at(&mosaic, 3, 4), 1);
+        assert_eq!(lightness_at(&mosaic, 4, 4), 5);
+        history.undo(&mut mosaic);
+        assert_eq!(lightness_at(&mosaic, 3, 4), 12);
+    }
+
+    #[test]
+    fn regions_are_flipped_and_rotated() {
+        let mosaic = a_mosaic_of_lightnesses();
+        // 1 2 3
+        // 5 6 7
+        let region = TesseraRegion::copy_from(&mosaic, &GridRectangle::new(at(1, 1), at(2, 3)));
+        let lightnesses = |region: &TesseraRegion| -> Vec<Vec<usize>> {
+            region
+                .cells
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|cell| (cell.as_ref().unwrap().color.l * 100.0).round() as usize)
+                        .collect()
+                })
+                .collect()
+        };
+        let mut flipped = region.clone();
+        flipped.transform(RegionTransform::FlipHorizontal);
+        assert_eq!(lightnesses(&flipped), vec![vec![3, 2, 1], vec![7, 6, 5]]);
+        flipped.transform(RegionTransform::FlipVertical);
+        assert_eq!(lightnesses(&flipped), vec![vec![7, 6, 5], vec![3, 2, 1]]);
+
+        let mut rotated = region.clone();
+        rotated.transform(RegionTransform::RotateClockwise);
+        assert_eq!(rotated.get_size(), [3, 2]);
+        assert_eq!(
+            lightnesses(&rotated),
+            vec![vec![5, 1], vec![6, 2], vec![7, 3]]
+        );
+        for _turn in 0..3 {
+            rotated.transform(RegionTransform::RotateClockwise);
+        }
+        assert_eq!(rotated, region);
+    }
+
+    #[test]
+    fn cleared_regions_get_empty_cells_of_the_color() {
+        let mut mosaic = a_mosaic_of_lightnesses();
+        let white = colors::oklch_from_srgb8([255, 255, 255]);
+        let changes = get_changes_to_clear(&mosaic, &GridRectangle::new(at(4, 3), at(5, 5)), white);
+        assert_eq!(changes.len(), 2);
+        EditHistory::new(None)
+            .apply(&mut mosaic, MosaicEdit::ChangeTesserae(changes))
+            .unwrap();
+        let cleared = mosaic.get_tessera(&at(4, 4)).unwrap();
+        assert_eq!(cleared.color, white);
+        assert_eq!(cleared.shape, TesseraShape::Empty);
+        assert_eq!(cleared.size.horizontal, 10);
+        assert_eq!(lightness_at(&mosaic, 4, 2), 14);
+    }
+}
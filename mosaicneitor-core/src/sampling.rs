@@ -0,0 +1,223 @@
+//! Picking the color of a tessera from the pixels of the base image under it.
+
+use palette::convert::FromColor;
+
+/// How to choose the color for a tessera, from all the image pixels under it.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SamplingMode {
+    /// The pixel in the middle of the area.
+    CenterPixel,
+    /// The mean of all the pixels, in Oklab.
+    Average,
+    /// The median of each Oklab channel, less sensitive to small details than the average.
+    Median,
+    /// The average of the most common group of similar pixels, keeps colors crisp at edges.
+    DominantColor,
+}
+
+impl SamplingMode {
+    /// Every mode, to list them on menus.
+    pub const ALL: [SamplingMode; 4] = [
+        SamplingMode::CenterPixel,
+        SamplingMode::Average,
+        SamplingMode::Median,
+        SamplingMode::DominantColor,
+    ];
+}
+
+/// A rectangle of pixels on the image. Left and top included, right and bottom excluded.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PixelArea {
+    /// First column.
+    pub left: u32,
+    /// First row.
+    pub top: u32,
+    /// Column after the last one.
+    pub right: u32,
+    /// Row after the last one.
+    pub bottom: u32,
+}
+
+// Side length of the Oklab cubes used to group similar pixels when looking for the dominant color.
+const DOMINANT_COLOR_BIN_SIZE: f32 = 0.05;
+
+/// The area is clipped to the image. Yields an error if no pixel is left.
+pub fn sample_color(
+    image: &image::Rgba32FImage,
+    area: PixelArea,
+    mode: SamplingMode,
+) -> Result<palette::Oklch, String> {
+    let area = PixelArea {
+        left: area.left,
+        top: area.top,
+        right: area.right.min(image.width()).max(area.left + 1),
+        bottom: area.bottom.min(image.height()).max(area.top + 1),
+    };
+    if area.left >= image.width() || area.top >= image.height() {
+        return Err(String::from("no pixel"));
+    }
+    let oklab_color = match mode {
+        SamplingMode::CenterPixel => get_oklab_at(
+            image,
+            area.left + (area.right - area.left - 1) / 2,
+            area.top + (area.bottom - area.top - 1) / 2,
+        ),
+        SamplingMode::Average => average(&get_oklab_pixels_in(image, area)),
+        SamplingMode::Median => median(get_oklab_pixels_in(image, area)),
+        SamplingMode::DominantColor => dominant(&get_oklab_pixels_in(image, area)),
+    };
+    Ok(palette::Oklch::from_color(oklab_color))
+}
+
+fn get_oklab_at(image: &image::Rgba32FImage, x: u32, y: u32) -> palette::Oklab {
+    let pixel = image.get_pixel(x, y);
+    palette::Oklab::from_color(palette::Srgb::new(pixel[0], pixel[1], pixel[2]))
+}
+
+fn get_oklab_pixels_in(image: &image::Rgba32FImage, area: PixelArea) -> Vec<palette::Oklab> {
+    let mut pixels = Vec::new();
+    for y in area.top..area.bottom {
+        for x in area.left..area.right {
+            pixels.push(get_oklab_at(image, x, y));
+        }
+    }
+    pixels
+}
+
+fn average(pixels: &[palette::Oklab]) -> palette::Oklab {
+    let count = pixels.len().max(1) as f32;
+    let sum = pixels.iter().fold([0.0, 0.0, 0.0], |sum, pixel| {
+        [sum[0] + pixel.l, sum[1] + pixel.a, sum[2] + pixel.b]
+    });
+    palette::Oklab::new(sum[0] / count, sum[1] / count, sum[2] / count)
+}
+
+fn median(pixels: Vec<palette::Oklab>) -> palette::Oklab {
+    let median_of = |mut values: Vec<f32>| {
+        values.sort_by(|a, b| a.total_cmp(b));
+        values.get(values.len() / 2).copied().unwrap_or(0.0)
+    };
+    palette::Oklab::new(
+        median_of(pixels.iter().map(|pixel| pixel.l).collect()),
+        median_of(pixels.iter().map(|pixel| pixel.a).collect()),
+        median_of(pixels.iter().map(|pixel| pixel.b).collect()),
+    )
+}
+
+fn dominant(pixels: &[palette::Oklab]) -> palette::Oklab {
+    let bin_of = |pixel: &palette::Oklab| {
+        [
+            (pixel.l / DOMINANT_COLOR_BIN_SIZE).floor() as i32,
+            (pixel.a / DOMINANT_COLOR_BIN_SIZE).floor() as i32,
+            (pixel.b / DOMINANT_COLOR_BIN_SIZE).floor() as i32,
+        ]
+    };
+    let mut bins: std::collections::HashMap<[i32; 3], Vec<palette::Oklab>> =
+        std::collections::HashMap::new();
+    for pixel in pixels {
+        bins.entry(bin_of(pixel)).or_default().push(*pixel);
+    }
+    // Ties are solved by the bin key, so the result does not depend on the HashMap order.
+    match bins
+        .iter()
+        .max_by(|a, b| a.1.len().cmp(&b.1.len()).then(b.0.cmp(a.0)))
+    {
+        Some((_bin, members)) => average(members),
+        None => average(pixels),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn an_image_with_left_half_black_and_right_half_white(
+        width: u32,
+        height: u32,
+    ) -> image::Rgba32FImage {
+        image::Rgba32FImage::from_fn(width, height, |x, _y| {
+            if x < width / 2 {
+                image::Rgba([0.0, 0.0, 0.0, 1.0])
+            } else {
+                image::Rgba([1.0, 1.0, 1.0, 1.0])
+            }
+        })
+    }
+
+    fn whole(image: &image::Rgba32FImage) -> PixelArea {
+        PixelArea {
+            left: 0,
+            top: 0,
+            right: image.width(),
+            bottom: image.height(),
+        }
+    }
+
+    #[test]
+    fn average_mixes_all_pixels_in_oklab() {
+        let image = an_image_with_left_half_black_and_right_half_white(10, 10);
+        let color = sample_color(&image, whole(&image), SamplingMode::Average).unwrap();
+        assert!((color.l - 0.5).abs() < 0.001);
+        assert!(color.chroma < 0.001);
+    }
+
+    #[test]
+    fn median_and_dominant_pick_the_majority_color() {
+        let image = image::Rgba32FImage::from_fn(10, 10, |x, _y| {
+            if x < 3 {
+                image::Rgba([0.0, 0.0, 0.0, 1.0])
+            } else {
+                image::Rgba([1.0, 0.0, 0.0, 1.0])
+            }
+        });
+        let red = palette::Oklch::from_color(palette::Srgb::new(1.0, 0.0, 0.0));
+        for mode in [SamplingMode::Median, SamplingMode::DominantColor] {
+            let color = sample_color(&image, whole(&image), mode).unwrap();
+            assert!((color.l - red.l).abs() < 0.001, "{:?}", mode);
+            assert!((color.chroma - red.chroma).abs() < 0.001, "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn center_pixel_takes_the_pixel_in_the_middle_of_the_area() {
+        let image = an_image_with_left_half_black_and_right_half_white(10, 10);
+        let area = PixelArea {
+            left: 4,
+            top: 0,
+            right: 9,
+            bottom: 10,
+        };
+        let color = sample_color(&image, area, SamplingMode::CenterPixel).unwrap();
+        assert!((color.l - 1.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn areas_outside_the_image_yield_an_error() {
+        let image = an_image_with_left_half_black_and_right_half_white(10, 10);
+        let area = PixelArea {
+            left: 15,
+            top: 15,
+            right: 20,
+            bottom: 20,
+        };
+        for mode in SamplingMode::ALL {
+            assert_eq!(
+                sample_color(&image, area, mode),
+                Err(String::from("no pixel"))
+            );
+        }
+    }
+
+    #[test]
+    fn areas_partially_outside_the_image_are_clipped() {
+        let image = an_image_with_left_half_black_and_right_half_white(10, 10);
+        let area = PixelArea {
+            left: 6,
+            top: 6,
+            right: 20,
+            bottom: 20,
+        };
+        let color = sample_color(&image, area, SamplingMode::Average).unwrap();
+        assert!((color.l - 1.0).abs() < 0.001);
+    }
+}
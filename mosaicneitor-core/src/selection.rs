@@ -0,0 +1,296 @@
+//! Finding the tesserae on the grid that an area, or a patch of similar color, covers.
+//! Points are in mm from the top left corner of the mosaic. Free tesserae are not included.
+
+use crate::{
+    colors,
+    mosaic::{Mosaic, PositionOnGrid, TesseraPlacement},
+};
+
+/// The tesserae with their center within the radius of the center of the tessera under the point.
+/// The radius counts tesserae of the general size, 0 is only the tessera under the point.
+pub fn get_positions_in_brush(
+    mosaic: &Mosaic,
+    point_mm: [f32; 2],
+    radius_in_tesserae: usize,
+    gap_between_tesserae: usize,
+) -> Vec<PositionOnGrid> {
+    let Some(position) = mosaic.get_position_at_point(point_mm, gap_between_tesserae) else {
+        return Vec::new();
+    };
+    if radius_in_tesserae == 0 {
+        return vec![position];
+    }
+    let Some(center) = mosaic
+        .get_tessera_placement(&position, gap_between_tesserae)
+        .map(|placement| get_center(&placement))
+    else {
+        return Vec::new();
+    };
+    let general_size = mosaic.get_general_tessera_size();
+    let step = (general_size.horizontal + general_size.vertical) as f32 / 2.0
+        + gap_between_tesserae as f32;
+    // A bit more, so tesserae of the general size right at the radius are in.
+    let radius = (radius_in_tesserae as f32 + 0.25) * step;
+    positions_where(mosaic, gap_between_tesserae, |tessera_center| {
+        (tessera_center[0] - center[0]).hypot(tessera_center[1] - center[1]) <= radius
+    })
+}
+
+/// The tesserae with their center inside the rectangle between both corners, in any order.
+pub fn get_positions_in_rectangle(
+    mosaic: &Mosaic,
+    corner_mm: [f32; 2],
+    opposite_corner_mm: [f32; 2],
+    gap_between_tesserae: usize,
+) -> Vec<PositionOnGrid> {
+    let [left, right] = sorted(corner_mm[0], opposite_corner_mm[0]);
+    let [top, bottom] = sorted(corner_mm[1], opposite_corner_mm[1]);
+    positions_where(mosaic, gap_between_tesserae, |[x, y]| {
+        (left..=right).contains(&x) && (top..=bottom).contains(&y)
+    })
+}
+
+/// The tesserae with their center inside the polygon, closed from the last point to the first one.
+pub fn get_positions_in_lasso(
+    mosaic: &Mosaic,
+    polygon_mm: &[[f32; 2]],
+    gap_between_tesserae: usize,
+) -> Vec<PositionOnGrid> {
+    if polygon_mm.len() < 3 {
+        return Vec::new();
+    }
+    positions_where(mosaic, gap_between_tesserae, |point| {
+        is_inside_polygon(point, polygon_mm)
+    })
+}
+
+/// The tessera under the point and every tessera connected to it through tesserae of a similar color.
+/// The tolerance is the Oklab distance to the color of the first tessera, 0 is the same color.
+/// Tesserae are connected if they are next to each other in a row, or if they overlap
+/// horizontally in consecutive rows.
+pub fn get_positions_to_flood_fill(
+    mosaic: &Mosaic,
+    point_mm: [f32; 2],
+    tolerance: f32,
+    gap_between_tesserae: usize,
+) -> Vec<PositionOnGrid> {
+    let Some(start) = mosaic.get_position_at_point(point_mm, gap_between_tesserae) else {
+        return Vec::new();
+    };
+    let Some(start_color) = mosaic.get_tessera(&start).map(|tessera| tessera.color) else {
+        return Vec::new();
+    };
+    // Horizontal extents of every tessera, row by row.
+    let mut extents: Vec<Vec<[usize; 2]>> = vec![Vec::new(); mosaic.get_number_of_rows()];
+    for (position, _tessera, placement) in mosaic.get_tesserae_with_placements(gap_between_tesserae)
+    {
+        extents[position.row - 1].push([
+            placement.origin[0],
+            placement.origin[0] + placement.size.horizontal,
+        ]);
+    }
+    let is_similar = |position: &PositionOnGrid| {
+        mosaic
+            .get_tessera(position)
+            .is_some_and(|tessera| colors::oklab_distance(tessera.color, start_color) <= tolerance)
+    };
+
+    let mut visited: Vec<Vec<bool>> = extents.iter().map(|row| vec![false; row.len()]).collect();
+    visited[start.row - 1][start.column - 1] = true;
+    let mut pending = vec![start];
+    let mut filled = Vec::new();
+    while let Some(position) = pending.pop() {
+        filled.push(position);
+        let [left, right] = extents[position.row - 1][position.column - 1];
+        let mut neighbours = vec![
+            PositionOnGrid {
+                column: position.column - 1,
+                ..position
+            },
+            PositionOnGrid {
+                column: position.column + 1,
+                ..position
+            },
+        ];
+        for row in [position.row - 1, position.row + 1] {
+            if row == 0 || row > extents.len() {
+                continue;
+            }
+            for (column_index, [other_left, other_right]) in extents[row - 1].iter().enumerate() {
+                // Touching at a corner is not enough.
+                if *other_left < right && left < *other_right {
+                    neighbours.push(PositionOnGrid {
+                        row,
+                        column: column_index + 1,
+                    });
+                }
+            }
+        }
+        for neighbour in neighbours {
+            let Some(seen) = neighbour
+                .column
+                .checked_sub(1)
+                .and_then(|column_index| visited[neighbour.row - 1].get_mut(column_index))
+            else {
+                continue;
+            };
+            if !*seen && is_similar(&neighbour) {
+                *seen = true;
+                pending.push(neighbour);
+            }
+        }
+    }
+    filled.sort();
+    filled
+}
+
+fn positions_where(
+    mosaic: &Mosaic,
+    gap_between_tesserae: usize,
+    condition: impl Fn([f32; 2]) -> bool,
+) -> Vec<PositionOnGrid> {
+    mosaic
+        .get_tesserae_with_placements(gap_between_tesserae)
+        .into_iter()
+        .filter(|(_position, _tessera, placement)| condition(get_center(placement)))
+        .map(|(position, _tessera, _placement)| position)
+        .collect()
+}
+
+fn get_center(placement: &TesseraPlacement) -> [f32; 2] {
+    [
+        placement.origin[0] as f32 + placement.size.horizontal as f32 / 2.0,
+        placement.origin[1] as f32 + placement.size.vertical as f32 / 2.0,
+    ]
+}
+
+fn sorted(a: f32, b: f32) -> [f32; 2] {
+    [a.min(b), a.max(b)]
+}
+
+// Even-odd rule: a ray to the right crosses the sides an odd number of times.
+fn is_inside_polygon(point: [f32; 2], polygon: &[[f32; 2]]) -> bool {
+    let mut inside = false;
+    let mut previous = polygon[polygon.len() - 1];
+    for current in polygon {
+        if (current[1] > point[1]) != (previous[1] > point[1]) {
+            let crossing_x = current[0]
+                + (point[1] - current[1]) * (previous[0] - current[0]) / (previous[1] - current[1]);
+            if point[0] < crossing_x {
+                inside = !inside;
+            }
+        }
+        previous = *current;
+    }
+    inside
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        mosaic::{a_mosaic_of_colors, RectangleInMm, Tessera},
+        tessera_shape::TesseraShape,
+    };
+
+    // Rows of 10 mm tesserae, no gap used in the tests. 'W' is white, 'B' black, 'G' dark grey.
+    fn a_mosaic_from(rows: &[&str]) -> Mosaic {
+        a_mosaic_of_colors(
+            [10, 10],
+            rows.iter()
+                .map(|row| {
+                    row.chars()
+                        .map(|letter| {
+                            colors::oklch_from_srgb8(match letter {
+                                'W' => [255, 255, 255],
+                                'G' => [20, 20, 20],
+                                _ => [0, 0, 0],
+                            })
+                        })
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+
+    fn at(row: usize, column: usize) -> PositionOnGrid {
+        PositionOnGrid { row, column }
+    }
+
+    #[test]
+    fn the_brush_covers_a_disc_of_tesserae() {
+        let mosaic = a_mosaic_from(&["WWWWW"; 5]);
+        assert_eq!(
+            get_positions_in_brush(&mosaic, [25.0, 25.0], 0, 0),
+            vec![at(3, 3)]
+        );
+        let disc = get_positions_in_brush(&mosaic, [25.0, 25.0], 1, 0);
+        assert_eq!(disc, vec![at(2, 3), at(3, 2), at(3, 3), at(3, 4), at(4, 3)]);
+        assert_eq!(
+            get_positions_in_brush(&mosaic, [25.0, 25.0], 2, 0).len(),
+            21
+        );
+        assert!(get_positions_in_brush(&mosaic, [60.0, 25.0], 1, 0).is_empty());
+    }
+
+    #[test]
+    fn rectangles_and_lassos_take_the_tesserae_with_the_center_inside() {
+        let mosaic = a_mosaic_from(&["WWWW"; 4]);
+        assert_eq!(
+            get_positions_in_rectangle(&mosaic, [22.0, 18.0], [8.0, 0.0], 0),
+            vec![at(1, 2), at(2, 2)]
+        );
+        // A triangle over the top left half.
+        let triangle = [[0.0, 0.0], [38.0, 0.0], [0.0, 38.0]];
+        assert_eq!(
+            get_positions_in_lasso(&mosaic, &triangle, 0),
+            vec![at(1, 1), at(1, 2), at(1, 3), at(2, 1), at(2, 2), at(3, 1)]
+        );
+        assert!(get_positions_in_lasso(&mosaic, &triangle[..2], 0).is_empty());
+    }
+
+    #[test]
+    fn flood_fill_stops_at_different_colors() {
+        let mosaic = a_mosaic_from(&["WWBW", "WBBW", "BBWW", "WGBW"]);
+        assert_eq!(
+            get_positions_to_flood_fill(&mosaic, [0.0, 0.0], 0.01, 0),
+            vec![at(1, 1), at(1, 2), at(2, 1)]
+        );
+        // Diagonals do not connect.
+        assert_eq!(
+            get_positions_to_flood_fill(&mosaic, [5.0, 35.0], 0.01, 0),
+            vec![at(4, 1)]
+        );
+        let black = get_positions_to_flood_fill(&mosaic, [25.0, 5.0], 0.01, 0);
+        assert_eq!(black.len(), 5);
+        let black_and_grey = get_positions_to_flood_fill(&mosaic, [25.0, 5.0], 0.3, 0);
+        assert_eq!(black_and_grey.len(), 7);
+    }
+
+    #[test]
+    fn flood_fill_follows_overlapping_tesserae_between_rows() {
+        let mut mosaic = a_mosaic_from(&["WWW"]);
+        // A second row of offset tesserae, like bricks: 5, 10, 10 and 5 mm wide.
+        mosaic.add_a_row_of_tesserae(
+            [5, 10, 10, 5]
+                .iter()
+                .map(|width| Tessera {
+                    color: colors::oklch_from_srgb8([255, 255, 255]),
+                    size: RectangleInMm {
+                        horizontal: *width,
+                        vertical: 10,
+                    },
+                    shape: TesseraShape::Whole,
+                })
+                .collect(),
+        );
+        let mut black_middle = mosaic.get_tessera(&at(1, 2)).unwrap().clone();
+        black_middle.color = colors::oklch_from_srgb8([0, 0, 0]);
+        mosaic.change_tessera(&at(1, 2), black_middle).unwrap();
+        // From the first white of the top row, down and across under the black one.
+        assert_eq!(
+            get_positions_to_flood_fill(&mosaic, [0.0, 0.0], 0.01, 0),
+            vec![at(1, 1), at(1, 3), at(2, 1), at(2, 2), at(2, 3), at(2, 4)]
+        );
+    }
+}
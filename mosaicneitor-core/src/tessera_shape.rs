@@ -0,0 +1,497 @@
+//! Non rectangular tesserae, to mimic the cuts made with the nippers.
+
+use serde::{Deserialize, Serialize};
+
+use crate::mosaic::RectangleInMm;
+
+/// The outline of a tessera inside its cell, the rectangle given by its size.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub enum TesseraShape {
+    /// The whole cell, uncut.
+    #[default]
+    Whole,
+    /// A usual cut, it follows the size of the tessera.
+    Preset(CutPreset),
+    /// Any convex polygon, vertices in mm from the top left corner of the cell, clockwise.
+    Polygon(Vec<[f32; 2]>),
+    /// Nothing laid, the cell is left to the grout. It keeps the place of the next tesserae.
+    Empty,
+}
+
+/// The usual cuts.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum CutPreset {
+    /// Cut down the middle, the left part is kept.
+    HalfLeft,
+    /// Cut down the middle, the right part is kept.
+    HalfRight,
+    /// Cut across the middle, the top part is kept.
+    HalfTop,
+    /// Cut across the middle, the bottom part is kept.
+    HalfBottom,
+    /// Cut along a diagonal, keeping the top left corner.
+    TriangleTopLeft,
+    /// Cut along a diagonal, keeping the top right corner.
+    TriangleTopRight,
+    /// Cut along a diagonal, keeping the bottom right corner.
+    TriangleBottomRight,
+    /// Cut along a diagonal, keeping the bottom left corner.
+    TriangleBottomLeft,
+    /// Cut in four, keeping the top left quarter.
+    QuarterTopLeft,
+    /// Cut in four, keeping the top right quarter.
+    QuarterTopRight,
+    /// Cut in four, keeping the bottom right quarter.
+    QuarterBottomRight,
+    /// Cut in four, keeping the bottom left quarter.
+    QuarterBottomLeft,
+    /// A triangle on the bottom side, pointing up.
+    WedgeUp,
+    /// A triangle on the left side, pointing right.
+    WedgeRight,
+    /// A triangle on the top side, pointing down.
+    WedgeDown,
+    /// A triangle on the right side, pointing left.
+    WedgeLeft,
+}
+
+impl CutPreset {
+    /// Every preset, to list them on menus.
+    pub const ALL: [CutPreset; 16] = [
+        CutPreset::HalfLeft,
+        CutPreset::HalfRight,
+        CutPreset::HalfTop,
+        CutPreset::HalfBottom,
+        CutPreset::TriangleTopLeft,
+        CutPreset::TriangleTopRight,
+        CutPreset::TriangleBottomRight,
+        CutPreset::TriangleBottomLeft,
+        CutPreset::QuarterTopLeft,
+        CutPreset::QuarterTopRight,
+        CutPreset::QuarterBottomRight,
+        CutPreset::QuarterBottomLeft,
+        CutPreset::WedgeUp,
+        CutPreset::WedgeRight,
+        CutPreset::WedgeDown,
+        CutPreset::WedgeLeft,
+    ];
+
+    // As fractions of the cell, clockwise (with y growing downwards).
+    fn get_unit_polygon(&self) -> Vec<[f32; 2]> {
+        match self {
+            CutPreset::HalfLeft => vec![[0.0, 0.0], [0.5, 0.0], [0.5, 1.0], [0.0, 1.0]],
+            CutPreset::HalfRight => vec![[0.5, 0.0], [1.0, 0.0], [1.0, 1.0], [0.5, 1.0]],
+            CutPreset::HalfTop => vec![[0.0, 0.0], [1.0, 0.0], [1.0, 0.5], [0.0, 0.5]],
+            CutPreset::HalfBottom => vec![[0.0, 0.5], [1.0, 0.5], [1.0, 1.0], [0.0, 1.0]],
+            CutPreset::TriangleTopLeft => vec![[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]],
+            CutPreset::TriangleTopRight => vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0]],
+            CutPreset::TriangleBottomRight => vec![[1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
+            CutPreset::TriangleBottomLeft => vec![[0.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
+            CutPreset::QuarterTopLeft => vec![[0.0, 0.0], [0.5, 0.0], [0.5, 0.5], [0.0, 0.5]],
+            CutPreset::QuarterTopRight => vec![[0.5, 0.0], [1.0, 0.0], [1.0, 0.5], [0.5, 0.5]],
+            CutPreset::QuarterBottomRight => vec![[0.5, 0.5], [1.0, 0.5], [1.0, 1.0], [0.5, 1.0]],
+            CutPreset::QuarterBottomLeft => vec![[0.0, 0.5], [0.5, 0.5], [0.5, 1.0], [0.0, 1.0]],
+            CutPreset::WedgeUp => vec![[0.5, 0.0], [1.0, 1.0], [0.0, 1.0]],
+            CutPreset::WedgeRight => vec![[0.0, 0.0], [1.0, 0.5], [0.0, 1.0]],
+            CutPreset::WedgeDown => vec![[0.0, 0.0], [1.0, 0.0], [0.5, 1.0]],
+            CutPreset::WedgeLeft => vec![[1.0, 0.0], [1.0, 1.0], [0.0, 0.5]],
+        }
+    }
+}
+
+// Vertices closer than this to a side of the cell are on it.
+const EPSILON_MM: f32 = 0.01;
+
+impl TesseraShape {
+    /// Vertices in mm from the top left corner of the cell, clockwise (with y growing downwards).
+    pub fn get_polygon(&self, size: &RectangleInMm) -> Vec<[f32; 2]> {
+        let [width, height] = [size.horizontal as f32, size.vertical as f32];
+        match self {
+            TesseraShape::Whole => vec![[0.0, 0.0], [width, 0.0], [width, height], [0.0, height]],
+            TesseraShape::Preset(preset) => preset
+                .get_unit_polygon()
+                .iter()
+                .map(|[x, y]| [x * width, y * height])
+                .collect(),
+            TesseraShape::Polygon(vertices) => vertices.clone(),
+            TesseraShape::Empty => Vec::new(),
+        }
+    }
+
+    /// Cuts needed to get it from a whole tile of the same size:
+    /// one per side of the polygon that is not on a side of the cell.
+    pub fn get_number_of_cuts(&self, size: &RectangleInMm) -> usize {
+        let [width, height] = [size.horizontal as f32, size.vertical as f32];
+        let on_the_same_side_of_the_cell = |a: [f32; 2], b: [f32; 2]| {
+            [0.0, width]
+                .iter()
+                .any(|x| (a[0] - x).abs() < EPSILON_MM && (b[0] - x).abs() < EPSILON_MM)
+                || [0.0, height]
+                    .iter()
+                    .any(|y| (a[1] - y).abs() < EPSILON_MM && (b[1] - y).abs() < EPSILON_MM)
+        };
+        let polygon = self.get_polygon(size);
+        (0..polygon.len())
+            .filter(|i| {
+                !on_the_same_side_of_the_cell(polygon[*i], polygon[(i + 1) % polygon.len()])
+            })
+            .count()
+    }
+
+    /// In square millimetres.
+    pub fn get_area(&self, size: &RectangleInMm) -> f32 {
+        get_signed_area(&self.get_polygon(size)).abs()
+    }
+
+    /// The same shape for a tessera of another size. Presets already follow the size,
+    /// polygons are stretched.
+    pub fn resized(&self, from: &RectangleInMm, to: &RectangleInMm) -> TesseraShape {
+        match self {
+            TesseraShape::Polygon(vertices) => {
+                let scale = [
+                    to.horizontal as f32 / from.horizontal.max(1) as f32,
+                    to.vertical as f32 / from.vertical.max(1) as f32,
+                ];
+                TesseraShape::Polygon(
+                    vertices
+                        .iter()
+                        .map(|[x, y]| [x * scale[0], y * scale[1]])
+                        .collect(),
+                )
+            }
+            other => other.clone(),
+        }
+    }
+
+    /// Flipped left to right, as seen from the back of the tessera.
+    pub fn mirrored(&self, size: &RectangleInMm) -> TesseraShape {
+        match self {
+            TesseraShape::Whole => TesseraShape::Whole,
+            TesseraShape::Empty => TesseraShape::Empty,
+            other => {
+                // Flipping reverses the order of the vertices, so it is reversed again to keep them clockwise.
+                let width = size.horizontal as f32;
+                let mut vertices: Vec<[f32; 2]> = other
+                    .get_polygon(size)
+                    .iter()
+                    .map(|[x, y]| [width - x, *y])
+                    .collect();
+                vertices.reverse();
+                TesseraShape::Polygon(vertices)
+            }
+        }
+    }
+
+    /// Flipped top to bottom.
+    pub fn flipped_vertically(&self, size: &RectangleInMm) -> TesseraShape {
+        match self {
+            TesseraShape::Whole => TesseraShape::Whole,
+            TesseraShape::Empty => TesseraShape::Empty,
+            other => {
+                let height = size.vertical as f32;
+                let mut vertices: Vec<[f32; 2]> = other
+                    .get_polygon(size)
+                    .iter()
+                    .map(|[x, y]| [*x, height - y])
+                    .collect();
+                vertices.reverse();
+                TesseraShape::Polygon(vertices)
+            }
+        }
+    }
+
+    /// Turned a quarter clockwise, for a cell of the given size before turning.
+    /// The turned cell is as wide as the given one is high.
+    pub fn rotated_clockwise(&self, size: &RectangleInMm) -> TesseraShape {
+        match self {
+            TesseraShape::Whole => TesseraShape::Whole,
+            TesseraShape::Empty => TesseraShape::Empty,
+            other => {
+                // Turning keeps the order of the vertices.
+                let height = size.vertical as f32;
+                TesseraShape::Polygon(
+                    other
+                        .get_polygon(size)
+                        .iter()
+                        .map(|[x, y]| [height - y, *x])
+                        .collect(),
+                )
+            }
+        }
+    }
+
+    /// Whether the point, in mm from the top left corner of the cell, is inside the tessera.
+    pub fn contains(&self, size: &RectangleInMm, point: [f32; 2]) -> bool {
+        *self != TesseraShape::Empty && is_inside_convex_polygon(&self.get_polygon(size), point)
+    }
+
+    /// Reads a polygon written as "x,y x,y x,y", in mm from the top left corner of the cell.
+    /// It must be convex and fit in the cell. The vertices can be given in either direction.
+    pub fn polygon_from_text(text: &str, size: &RectangleInMm) -> Result<TesseraShape, String> {
+        let mut vertices = Vec::new();
+        for pair in text.split_whitespace() {
+            let vertex: Vec<f32> = match pair
+                .split(',')
+                .map(|n| n.trim().parse::<f32>())
+                .collect::<Result<Vec<f32>, _>>()
+            {
+                Ok(numbers) => numbers,
+                Err(_) => return Err(format!("Not a vertex: {pair}")),
+            };
+            if vertex.len() != 2 {
+                return Err(format!("Not a vertex: {pair}"));
+            }
+            if vertex[0] < -EPSILON_MM
+                || vertex[1] < -EPSILON_MM
+                || vertex[0] > size.horizontal as f32 + EPSILON_MM
+                || vertex[1] > size.vertical as f32 + EPSILON_MM
+            {
+                return Err(format!("The vertex {pair} is outside the tessera."));
+            }
+            vertices.push([vertex[0], vertex[1]]);
+        }
+        if vertices.len() < 3 {
+            return Err(String::from("A polygon needs at least 3 vertices."));
+        }
+        if get_signed_area(&vertices) < 0.0 {
+            vertices.reverse();
+        }
+        if !is_convex_clockwise(&vertices) {
+            return Err(String::from("The polygon must be convex."));
+        }
+        Ok(TesseraShape::Polygon(vertices))
+    }
+
+    /// As [`TesseraShape::polygon_from_text`] reads it.
+    pub fn polygon_to_text(&self, size: &RectangleInMm) -> String {
+        self.get_polygon(size)
+            .iter()
+            .map(|[x, y]| format!("{x},{y}"))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+}
+
+/// For convex polygons in clockwise order: the point is on the right of every side.
+pub fn is_inside_convex_polygon(polygon: &[[f32; 2]], point: [f32; 2]) -> bool {
+    (0..polygon.len()).all(|i| {
+        let a = polygon[i];
+        let b = polygon[(i + 1) % polygon.len()];
+        (b[0] - a[0]) * (point[1] - a[1]) - (b[1] - a[1]) * (point[0] - a[0]) >= 0.0
+    })
+}
+
+/// Whether two convex polygons overlap by more than the tolerance, in mm. Touching is not overlapping.
+pub fn convex_polygons_overlap(a: &[[f32; 2]], b: &[[f32; 2]], tolerance: f32) -> bool {
+    // Separating axis theorem: they do not overlap if, along the normal of some side,
+    // their projections do not overlap.
+    let project = |polygon: &[[f32; 2]], axis: [f32; 2]| {
+        polygon
+            .iter()
+            .map(|vertex| vertex[0] * axis[0] + vertex[1] * axis[1])
+            .fold((f32::MAX, f32::MIN), |(min, max), p| {
+                (min.min(p), max.max(p))
+            })
+    };
+    for polygon in [a, b] {
+        for i in 0..polygon.len() {
+            let start = polygon[i];
+            let end = polygon[(i + 1) % polygon.len()];
+            let length = ((end[0] - start[0]).powi(2) + (end[1] - start[1]).powi(2)).sqrt();
+            if length < f32::EPSILON {
+                continue;
+            }
+            let axis = [(start[1] - end[1]) / length, (end[0] - start[0]) / length];
+            let (min_a, max_a) = project(a, axis);
+            let (min_b, max_b) = project(b, axis);
+            if max_a - tolerance <= min_b || max_b - tolerance <= min_a {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+// Positive for clockwise polygons, with y growing downwards.
+fn get_signed_area(polygon: &[[f32; 2]]) -> f32 {
+    (0..polygon.len())
+        .map(|i| {
+            let a = polygon[i];
+            let b = polygon[(i + 1) % polygon.len()];
+            a[0] * b[1] - b[0] * a[1]
+        })
+        .sum::<f32>()
+        / 2.0
+}
+
+fn is_convex_clockwise(polygon: &[[f32; 2]]) -> bool {
+    (0..polygon.len()).all(|i| {
+        let a = polygon[i];
+        let b = polygon[(i + 1) % polygon.len()];
+        let c = polygon[(i + 2) % polygon.len()];
+        (b[0] - a[0]) * (c[1] - b[1]) - (b[1] - a[1]) * (c[0] - b[0]) >= 0.0
+    }) && get_signed_area(polygon) > 0.0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TEN_BY_TWENTY: RectangleInMm = RectangleInMm {
+        horizontal: 10,
+        vertical: 20,
+    };
+
+    #[test]
+    fn presets_follow_the_size_of_the_tessera() {
+        assert_eq!(
+            TesseraShape::Preset(CutPreset::HalfLeft).get_polygon(&TEN_BY_TWENTY),
+            vec![[0.0, 0.0], [5.0, 0.0], [5.0, 20.0], [0.0, 20.0]]
+        );
+        assert_eq!(TesseraShape::Whole.get_area(&TEN_BY_TWENTY), 200.0);
+        assert_eq!(
+            TesseraShape::Preset(CutPreset::TriangleTopRight).get_area(&TEN_BY_TWENTY),
+            100.0
+        );
+        assert_eq!(
+            TesseraShape::Preset(CutPreset::QuarterBottomLeft).get_area(&TEN_BY_TWENTY),
+            50.0
+        );
+    }
+
+    #[test]
+    fn every_preset_is_convex_and_clockwise() {
+        for preset in CutPreset::ALL {
+            let polygon = TesseraShape::Preset(preset).get_polygon(&TEN_BY_TWENTY);
+            assert!(is_convex_clockwise(&polygon), "{:?}", preset);
+        }
+    }
+
+    #[test]
+    fn cuts_are_the_sides_not_on_the_sides_of_the_cell() {
+        let cuts = |shape: TesseraShape| shape.get_number_of_cuts(&TEN_BY_TWENTY);
+        assert_eq!(cuts(TesseraShape::Whole), 0);
+        assert_eq!(cuts(TesseraShape::Preset(CutPreset::HalfTop)), 1);
+        assert_eq!(cuts(TesseraShape::Preset(CutPreset::TriangleBottomLeft)), 1);
+        assert_eq!(cuts(TesseraShape::Preset(CutPreset::QuarterTopRight)), 2);
+        assert_eq!(cuts(TesseraShape::Preset(CutPreset::WedgeLeft)), 2);
+        assert_eq!(
+            cuts(TesseraShape::Polygon(vec![
+                [2.0, 2.0],
+                [8.0, 2.0],
+                [8.0, 18.0],
+                [2.0, 18.0]
+            ])),
+            4
+        );
+    }
+
+    #[test]
+    fn polygons_are_read_from_text_in_either_direction() {
+        let clockwise = TesseraShape::polygon_from_text("0,0 10,0 0,20", &TEN_BY_TWENTY).unwrap();
+        let counter_clockwise =
+            TesseraShape::polygon_from_text("0,0 0,20 10,0", &TEN_BY_TWENTY).unwrap();
+        for shape in [&clockwise, &counter_clockwise] {
+            assert_eq!(shape.get_area(&TEN_BY_TWENTY), 100.0);
+            assert!(shape.contains(&TEN_BY_TWENTY, [2.0, 2.0]));
+        }
+        assert_eq!(clockwise.polygon_to_text(&TEN_BY_TWENTY), "0,0 10,0 0,20");
+    }
+
+    #[test]
+    fn wrong_polygons_are_rejected() {
+        for text in [
+            "0,0 10,0",
+            "0,0 10,0 a,20",
+            "0,0 10,0 0,30",
+            "0,0 10,0 5,5 10,20 0,20",
+        ] {
+            assert!(
+                TesseraShape::polygon_from_text(text, &TEN_BY_TWENTY).is_err(),
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn points_inside_and_outside_a_cut_tessera() {
+        let shape = TesseraShape::Preset(CutPreset::TriangleTopLeft);
+        assert!(shape.contains(&TEN_BY_TWENTY, [2.0, 2.0]));
+        assert!(!shape.contains(&TEN_BY_TWENTY, [8.0, 18.0]));
+    }
+
+    #[test]
+    fn overlapping_polygons_are_told_apart_from_touching_ones() {
+        let square = |left: f32, top: f32| {
+            vec![
+                [left, top],
+                [left + 10.0, top],
+                [left + 10.0, top + 10.0],
+                [left, top + 10.0],
+            ]
+        };
+        assert!(convex_polygons_overlap(
+            &square(0.0, 0.0),
+            &square(5.0, 5.0),
+            0.01
+        ));
+        assert!(!convex_polygons_overlap(
+            &square(0.0, 0.0),
+            &square(10.0, 0.0),
+            0.01
+        ));
+        assert!(!convex_polygons_overlap(
+            &square(0.0, 0.0),
+            &square(30.0, 2.0),
+            0.01
+        ));
+        let diamond = vec![[20.0, 5.0], [25.0, 10.0], [20.0, 15.0], [15.0, 10.0]];
+        assert!(!convex_polygons_overlap(&square(0.0, 0.0), &diamond, 0.01));
+        assert!(convex_polygons_overlap(&square(6.0, 0.0), &diamond, 0.01));
+    }
+
+    #[test]
+    fn mirrored_shapes_keep_clockwise_order() {
+        let mirrored = TesseraShape::Preset(CutPreset::HalfLeft).mirrored(&TEN_BY_TWENTY);
+        let polygon = mirrored.get_polygon(&TEN_BY_TWENTY);
+        assert!(is_convex_clockwise(&polygon));
+        assert!(mirrored.contains(&TEN_BY_TWENTY, [8.0, 10.0]));
+        assert!(!mirrored.contains(&TEN_BY_TWENTY, [2.0, 10.0]));
+        assert_eq!(
+            TesseraShape::Whole.mirrored(&TEN_BY_TWENTY),
+            TesseraShape::Whole
+        );
+    }
+
+    #[test]
+    fn empty_cells_have_nothing_to_cut_nor_to_cover() {
+        let empty = TesseraShape::Empty;
+        assert_eq!(empty.get_area(&TEN_BY_TWENTY), 0.0);
+        assert_eq!(empty.get_number_of_cuts(&TEN_BY_TWENTY), 0);
+        assert!(!empty.contains(&TEN_BY_TWENTY, [5.0, 10.0]));
+        assert_eq!(empty.rotated_clockwise(&TEN_BY_TWENTY), TesseraShape::Empty);
+        assert_eq!(
+            empty.flipped_vertically(&TEN_BY_TWENTY),
+            TesseraShape::Empty
+        );
+    }
+
+    #[test]
+    fn flipped_and_rotated_shapes_keep_clockwise_order() {
+        let top = TesseraShape::Preset(CutPreset::HalfTop);
+        let flipped = top.flipped_vertically(&TEN_BY_TWENTY);
+        assert!(is_convex_clockwise(&flipped.get_polygon(&TEN_BY_TWENTY)));
+        assert!(flipped.contains(&TEN_BY_TWENTY, [5.0, 15.0]));
+        assert!(!flipped.contains(&TEN_BY_TWENTY, [5.0, 5.0]));
+
+        // The top half of a 10x20 cell, turned, is the right half of a 20x10 cell.
+        let twenty_by_ten = RectangleInMm {
+            horizontal: 20,
+            vertical: 10,
+        };
+        let rotated = top.rotated_clockwise(&TEN_BY_TWENTY);
+        assert!(is_convex_clockwise(&rotated.get_polygon(&twenty_by_ten)));
+        assert!(rotated.contains(&twenty_by_ten, [15.0, 5.0]));
+        assert!(!rotated.contains(&twenty_by_ten, [5.0, 5.0]));
+    }
+}
@@ -0,0 +1,117 @@
+//! The tile colors available to build the mosaic.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{colors, materials};
+
+/// The colors of the tesserae you can actually buy (or you already have at the workshop).
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct TilePalette {
+    /// Usually the supplier or the collection.
+    pub name: String,
+    /// One per tile color.
+    pub entries: Vec<PaletteEntry>,
+}
+
+/// A tile color.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct PaletteEntry {
+    /// As the supplier calls it.
+    pub name: String,
+    /// Reference to order it.
+    pub supplier_code: String,
+    /// The color, [red, green, blue], 0 to 255.
+    pub srgb: [u8; 3],
+    /// Zero means unknown, the materials report uses a default value then.
+    #[serde(default)]
+    pub kg_per_square_meter: f32,
+    /// Zero means unknown, the materials report uses a default value then.
+    #[serde(default)]
+    pub price_per_square_meter: f32,
+}
+
+impl PaletteEntry {
+    /// The color in Oklch, as used by the mosaic.
+    pub fn get_color(&self) -> palette::Oklch {
+        colors::oklch_from_srgb8(self.srgb)
+    }
+}
+
+impl Default for TilePalette {
+    fn default() -> Self {
+        Self {
+            name: String::from("-"),
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl TilePalette {
+    /// The entry perceptually nearest to the color, with its index. None if the palette is empty.
+    pub fn get_nearest_entry(&self, color: palette::Oklch) -> Option<(usize, &PaletteEntry)> {
+        self.entries.iter().enumerate().min_by(|a, b| {
+            colors::oklab_distance(a.1.get_color(), color)
+                .total_cmp(&colors::oklab_distance(b.1.get_color(), color))
+        })
+    }
+
+    /// If the palette is empty, the color is kept as is.
+    pub fn snap_to_nearest_color(&self, color: palette::Oklch) -> palette::Oklch {
+        match self.get_nearest_entry(color) {
+            Some((_index, entry)) => entry.get_color(),
+            None => color,
+        }
+    }
+
+    /// Adds an entry named after the color, with no supplier data.
+    pub fn add_entry(&mut self, srgb: [u8; 3]) {
+        self.entries.push(PaletteEntry {
+            name: materials::hex_color(srgb),
+            supplier_code: String::new(),
+            srgb,
+            kg_per_square_meter: 0.0,
+            price_per_square_meter: 0.0,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn black_white_and_red() -> TilePalette {
+        let mut tile_palette = TilePalette::default();
+        tile_palette.add_entry([0, 0, 0]);
+        tile_palette.add_entry([255, 255, 255]);
+        tile_palette.add_entry([255, 0, 0]);
+        tile_palette
+    }
+
+    #[test]
+    fn the_nearest_entry_is_found_by_oklab_distance() {
+        let tile_palette = black_white_and_red();
+        let dark_grey = colors::oklch_from_srgb8([40, 40, 40]);
+        let light_grey = colors::oklch_from_srgb8([200, 200, 200]);
+        let dark_red = colors::oklch_from_srgb8([150, 10, 20]);
+        assert_eq!(tile_palette.get_nearest_entry(dark_grey).unwrap().0, 0);
+        assert_eq!(tile_palette.get_nearest_entry(light_grey).unwrap().0, 1);
+        assert_eq!(tile_palette.get_nearest_entry(dark_red).unwrap().0, 2);
+    }
+
+    #[test]
+    fn snapping_yields_a_palette_color() {
+        let tile_palette = black_white_and_red();
+        let pinkish_red = colors::oklch_from_srgb8([240, 60, 70]);
+        assert_eq!(
+            colors::srgb8_from_oklch(tile_palette.snap_to_nearest_color(pinkish_red)),
+            [255, 0, 0]
+        );
+    }
+
+    #[test]
+    fn snapping_to_an_empty_palette_keeps_the_color() {
+        let tile_palette = TilePalette::default();
+        let color = colors::oklch_from_srgb8([12, 34, 56]);
+        assert_eq!(tile_palette.snap_to_nearest_color(color), color);
+    }
+}
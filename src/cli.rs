@@ -0,0 +1,509 @@
+use mosaicneitor_core::{
+    dithering::{DitheringMethod, DitheringSettings},
+    export_cartoon, export_chart, export_png, export_svg,
+    generator::{self, GenerationSettings, LayoutPattern},
+    materials::{self, MaterialsReport},
+    mosaic::{Mosaic, RectangleInMm},
+    project_file::{self, ProjectFile, ViewSettings},
+    sampling::SamplingMode,
+    tessera_shape::TesseraShape,
+};
+
+use crate::{config, settings::UserSettings};
+
+// To script mosaic generation, for batches of images, without opening the GUI.
+
+pub const USAGE: &str = "Usage:
+  mosaicneitor                              opens the GUI
+  mosaicneitor generate <image> [options]   generates a mosaic from the image and saves it as a project
+  mosaicneitor export <project> [options]   exports a saved project
+  mosaicneitor report <project> [options]   prints the materials report of a saved project
+  mosaicneitor help
+
+Generation options:
+  --size <W>x<H>            mosaic dimensions in mm
+  --width <W>               mosaic width in mm, the height follows the image aspect ratio
+  --tessera <W>x<H>         tessera size in mm (default 10x10)
+  --sampling <mode>         center, average, median or dominant (default average)
+  --pattern <pattern>       regulatum, brick, tessellatum or vermiculatum (default regulatum)
+  --restrict-to-palette     use only the colors of the tile palette
+  --dithering <method>      none, floyd-steinberg, atkinson or ordered, with --restrict-to-palette (default none)
+  --fit-to-edges            cut the last column and row of tesserae to the mosaic size
+
+Outputs:
+  --project <file>          project file (generate saves one next to the image by default)
+  --png <file>              picture of the mosaic
+  --svg <file>              drawing of the mosaic, in mm
+  --chart <file>            color-by-number chart (SVG)
+  --cartoon <file>          full scale cartoon, one SVG per page
+  --csv <file>              materials report
+
+Other options:
+  --settings <file>         settings file with the tile palette, materials and export settings
+";
+
+#[derive(Debug, PartialEq)]
+pub enum CliCommand {
+    Generate(std::path::PathBuf),
+    Export(std::path::PathBuf),
+    Report(std::path::PathBuf),
+    Help,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct CliOptions {
+    pub mosaic_size: Option<[usize; 2]>,
+    pub mosaic_width: Option<usize>,
+    pub tessera_size: Option<[usize; 2]>,
+    pub sampling_mode: Option<SamplingMode>,
+    pub pattern: Option<LayoutPattern>,
+    pub dithering: Option<DitheringMethod>,
+    pub restrict_to_palette: bool,
+    pub fit_to_edges: bool,
+    pub settings_file: Option<std::path::PathBuf>,
+    pub project: Option<std::path::PathBuf>,
+    pub png: Option<std::path::PathBuf>,
+    pub svg: Option<std::path::PathBuf>,
+    pub chart: Option<std::path::PathBuf>,
+    pub cartoon: Option<std::path::PathBuf>,
+    pub csv: Option<std::path::PathBuf>,
+}
+
+impl CliOptions {
+    fn has_generation_options(&self) -> bool {
+        self.mosaic_size.is_some()
+            || self.mosaic_width.is_some()
+            || self.tessera_size.is_some()
+            || self.sampling_mode.is_some()
+            || self.pattern.is_some()
+            || self.dithering.is_some()
+            || self.restrict_to_palette
+            || self.fit_to_edges
+    }
+}
+
+// The arguments do not include the program name.
+pub fn parse_arguments(arguments: &[String]) -> Result<(CliCommand, CliOptions), String> {
+    let mut arguments = arguments.iter();
+    let command = match arguments.next().map(|s| s.as_str()) {
+        Some("help") | Some("--help") | Some("-h") => {
+            return Ok((CliCommand::Help, CliOptions::default()))
+        }
+        Some(name @ ("generate" | "export" | "report")) => {
+            let path = match arguments.next() {
+                Some(path) if !path.starts_with("--") => std::path::PathBuf::from(path),
+                _ => return Err(format!("{name} needs a file to work on")),
+            };
+            match name {
+                "generate" => CliCommand::Generate(path),
+                "export" => CliCommand::Export(path),
+                _ => CliCommand::Report(path),
+            }
+        }
+        Some(other) => return Err(format!("Unknown command: {other}")),
+        None => return Err(String::from("No command given")),
+    };
+
+    let mut options = CliOptions::default();
+    while let Some(option) = arguments.next() {
+        if option == "--restrict-to-palette" {
+            options.restrict_to_palette = true;
+            continue;
+        }
+        if option == "--fit-to-edges" {
+            options.fit_to_edges = true;
+            continue;
+        }
+        let value = match arguments.next() {
+            Some(value) => value.as_str(),
+            None => return Err(format!("{option} needs a value")),
+        };
+        let path = || Some(std::path::PathBuf::from(value));
+        match option.as_str() {
+            "--size" => options.mosaic_size = Some(parse_dimensions(value)?),
+            "--width" => options.mosaic_width = Some(parse_number(value)?),
+            "--tessera" => options.tessera_size = Some(parse_dimensions(value)?),
+            "--sampling" => options.sampling_mode = Some(parse_sampling_mode(value)?),
+            "--pattern" => options.pattern = Some(parse_pattern(value)?),
+            "--dithering" => options.dithering = Some(parse_dithering(value)?),
+            "--settings" => options.settings_file = path(),
+            "--project" => options.project = path(),
+            "--png" => options.png = path(),
+            "--svg" => options.svg = path(),
+            "--chart" => options.chart = path(),
+            "--cartoon" => options.cartoon = path(),
+            "--csv" => options.csv = path(),
+            other => return Err(format!("Unknown option: {other}")),
+        }
+    }
+    if options.has_generation_options() && !matches!(command, CliCommand::Generate(_)) {
+        return Err(String::from("Generation options only apply to generate"));
+    }
+    if options.mosaic_size.is_some() && options.mosaic_width.is_some() {
+        return Err(String::from("Use either --size or --width, not both"));
+    }
+    if options.dithering.is_some() && !options.restrict_to_palette {
+        return Err(String::from(
+            "--dithering needs --restrict-to-palette, there is nothing to dither to",
+        ));
+    }
+    Ok((command, options))
+}
+
+fn parse_number(text: &str) -> Result<usize, String> {
+    match text.parse::<usize>() {
+        Ok(number) if number > 0 => Ok(number),
+        _ => Err(format!("Not a valid size in mm: {text}")),
+    }
+}
+
+// "500x300" -> [500, 300]
+fn parse_dimensions(text: &str) -> Result<[usize; 2], String> {
+    match text.split_once(['x', 'X']) {
+        Some((horizontal, vertical)) => Ok([parse_number(horizontal)?, parse_number(vertical)?]),
+        None => Err(format!("Not valid dimensions, use WxH: {text}")),
+    }
+}
+
+fn parse_sampling_mode(text: &str) -> Result<SamplingMode, String> {
+    match text {
+        "center" => Ok(SamplingMode::CenterPixel),
+        "average" => Ok(SamplingMode::Average),
+        "median" => Ok(SamplingMode::Median),
+        "dominant" => Ok(SamplingMode::DominantColor),
+        other => Err(format!("Unknown sampling mode: {other}")),
+    }
+}
+
+fn parse_pattern(text: &str) -> Result<LayoutPattern, String> {
+    match text {
+        "regulatum" => Ok(LayoutPattern::Regulatum),
+        "brick" => Ok(LayoutPattern::Brick),
+        "tessellatum" => Ok(LayoutPattern::Tessellatum),
+        "vermiculatum" => Ok(LayoutPattern::Vermiculatum),
+        other => Err(format!("Unknown pattern: {other}")),
+    }
+}
+
+fn parse_dithering(text: &str) -> Result<DitheringMethod, String> {
+    match text {
+        "none" => Ok(DitheringMethod::None),
+        "floyd-steinberg" => Ok(DitheringMethod::FloydSteinberg),
+        "atkinson" => Ok(DitheringMethod::Atkinson),
+        "ordered" => Ok(DitheringMethod::Ordered),
+        other => Err(format!("Unknown dithering method: {other}")),
+    }
+}
+
+// Yields the lines to print.
+pub fn run(command: CliCommand, options: &CliOptions) -> Result<Vec<String>, String> {
+    // Not for help, so it works even with a broken settings file.
+    let settings = || load_settings(options);
+    match command {
+        CliCommand::Help => Ok(vec![String::from(USAGE)]),
+        CliCommand::Generate(image_path) => generate(&image_path, options, &settings()?),
+        CliCommand::Export(project_path) => {
+            let settings = settings()?;
+            let (mosaic, mosaic_dimensions) = load_project(&project_path)?;
+            write_outputs(&mosaic, mosaic_dimensions, None, options, &settings)
+        }
+        CliCommand::Report(project_path) => {
+            let settings = settings()?;
+            let (mosaic, _mosaic_dimensions) = load_project(&project_path)?;
+            let report = MaterialsReport::new(&mosaic, &settings.tile_palette, &settings.materials);
+            let mut lines = report_lines(&report);
+            if let Some(path) = &options.csv {
+                report.save_csv_to_file(path)?;
+                lines.push(format!("Written {}", path.display()));
+            }
+            Ok(lines)
+        }
+    }
+}
+
+fn load_settings(options: &CliOptions) -> Result<UserSettings, String> {
+    match &options.settings_file {
+        Some(path) => UserSettings::load_from_file(path),
+        None if config::settings_file().exists() => {
+            UserSettings::load_from_file(&config::settings_file())
+        }
+        None => Ok(UserSettings::default()),
+    }
+}
+
+fn load_project(path: &std::path::Path) -> Result<(Mosaic, [usize; 2]), String> {
+    let project = ProjectFile::load_from_file(path)?;
+    // Exports do not need the base image.
+    Ok((project.to_mosaic(None), project.mosaic_dimensions_mm))
+}
+
+fn generate(
+    image_path: &std::path::Path,
+    options: &CliOptions,
+    settings: &UserSettings,
+) -> Result<Vec<String>, String> {
+    let base_image = generator::load_base_image(image_path)?;
+    let mosaic_dimensions = match (options.mosaic_size, options.mosaic_width) {
+        (Some(size), _) => size,
+        (None, width) => generator::round_preserving_aspect_ratio(
+            [
+                width.unwrap_or(config::DEFAULT_OVERAL_MOSAIC_DIMENSIONS_HORIZONTAL_MM),
+                config::DEFAULT_OVERAL_MOSAIC_DIMENSIONS_VERTICAL_MM,
+            ],
+            [base_image.width() as usize, base_image.height() as usize],
+        ),
+    };
+    let tessera_size = options.tessera_size.unwrap_or([
+        config::DEFAULT_BASE_TESSERA_SIZE_HORIZONTAL_MM,
+        config::DEFAULT_BASE_TESSERA_SIZE_VERTICAL_MM,
+    ]);
+    let generation_settings = GenerationSettings {
+        mosaic_dimensions_mm: mosaic_dimensions,
+        tessera_size: RectangleInMm {
+            horizontal: tessera_size[0],
+            vertical: tessera_size[1],
+        },
+        gap_between_tesserae: config::DEFAULT_GAP_BETWEEN_TESSSELAE,
+        sampling_mode: options.sampling_mode.unwrap_or(SamplingMode::Average),
+        fit_to_edges: options.fit_to_edges,
+        pattern: options.pattern.unwrap_or(LayoutPattern::Regulatum),
+        dithering: DitheringSettings {
+            method: options.dithering.unwrap_or(DitheringMethod::None),
+            ..Default::default()
+        },
+    };
+    generation_settings.check()?;
+    let mosaic = generator::generate_mosaic_from_image(
+        Some(std::sync::Arc::new(base_image)),
+        &generation_settings,
+        options
+            .restrict_to_palette
+            .then_some(&settings.tile_palette),
+    );
+    let mut lines = vec![format!(
+        "Generated a {}x{} mm mosaic with {} tesserae",
+        mosaic_dimensions[0],
+        mosaic_dimensions[1],
+        mosaic
+            .get_all_tesserae()
+            .filter(|tessera| tessera.shape != TesseraShape::Empty)
+            .count()
+    )];
+    let default_project_path =
+        project_file::add_extension_if_missing(image_path.with_extension(""));
+    lines.extend(write_outputs(
+        &mosaic,
+        mosaic_dimensions,
+        Some((image_path, default_project_path)),
+        options,
+        settings,
+    )?);
+    Ok(lines)
+}
+
+// The base image path, and where to save the project if no --project is given, only come from generate.
+fn write_outputs(
+    mosaic: &Mosaic,
+    mosaic_dimensions: [usize; 2],
+    generated_from: Option<(&std::path::Path, std::path::PathBuf)>,
+    options: &CliOptions,
+    settings: &UserSettings,
+) -> Result<Vec<String>, String> {
+    let gap = config::DEFAULT_GAP_BETWEEN_TESSSELAE;
+    let mut written = Vec::new();
+    let project_path = match (&options.project, &generated_from) {
+        (Some(path), _) => Some(project_file::add_extension_if_missing(path.clone())),
+        (None, Some((_image_path, default_project_path))) => Some(default_project_path.clone()),
+        (None, None) => None,
+    };
+    if let Some(path) = project_path {
+        let base_image_path = generated_from.map(|(image_path, _)| image_path.to_path_buf());
+        let project = ProjectFile::new(
+            mosaic,
+            base_image_path,
+            mosaic_dimensions,
+            ViewSettings {
+                show_image: false,
+                show_tesserae_grid: true,
+                show_actual_tesserae: true,
+                zoom_factor: 1.0,
+            },
+        );
+        project.save_to_file(&path)?;
+        written.push(path);
+    }
+    if let Some(path) = &options.png {
+        export_png::export_mosaic_to_png(mosaic, gap, &settings.render, path)?;
+        written.push(path.clone());
+    }
+    if let Some(path) = &options.svg {
+        export_svg::export_mosaic_to_svg(
+            mosaic,
+            gap,
+            &settings.tile_palette,
+            settings.render.grout_srgb,
+            path,
+        )?;
+        written.push(path.clone());
+    }
+    if let Some(path) = &options.chart {
+        export_chart::export_chart(mosaic, &settings.tile_palette, &settings.chart, path)?;
+        written.push(path.clone());
+    }
+    if let Some(path) = &options.cartoon {
+        written.extend(export_cartoon::export_cartoon(
+            mosaic,
+            gap,
+            &settings.tile_palette,
+            &settings.cartoon,
+            path,
+        )?);
+    }
+    if let Some(path) = &options.csv {
+        MaterialsReport::new(mosaic, &settings.tile_palette, &settings.materials)
+            .save_csv_to_file(path)?;
+        written.push(path.clone());
+    }
+    if written.is_empty() {
+        return Err(String::from("Nothing to export, give at least one output"));
+    }
+    Ok(written
+        .iter()
+        .map(|path| format!("Written {}", path.display()))
+        .collect())
+}
+
+fn report_lines(report: &MaterialsReport) -> Vec<String> {
+    let mut lines = vec![format!(
+        "{:<8} {:<24} {:>9} {:>11} {:>9} {:>9} {:>6}",
+        "color", "name", "tesserae", "with waste", "kg", "cost", "cuts"
+    )];
+    for line in &report.lines {
+        lines.push(format!(
+            "{:<8} {:<24} {:>9} {:>11} {:>9.2} {:>9.2} {:>6}",
+            materials::hex_color(line.srgb),
+            line.palette_entry_name.as_deref().unwrap_or("-"),
+            line.tesserae_count,
+            line.tesserae_count_with_waste,
+            line.weight_kg,
+            line.cost,
+            line.cuts
+        ));
+    }
+    lines.push(format!(
+        "{:<8} {:<24} {:>9} {:>11} {:>9.2} {:>9.2} {:>6}",
+        "total",
+        "",
+        report.get_total_tesserae(),
+        report.get_total_tesserae_with_waste(),
+        report.get_total_weight_kg(),
+        report.get_total_cost(),
+        report.get_total_cuts()
+    ));
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn arguments(text: &str) -> Vec<String> {
+        text.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn generation_options_are_parsed() {
+        let (command, options) = parse_arguments(&arguments(
+            "generate photo.jpg --size 500x300 --tessera 8x12 --sampling median --pattern brick --dithering atkinson --restrict-to-palette --fit-to-edges --png out.png",
+        ))
+        .unwrap();
+        assert_eq!(
+            command,
+            CliCommand::Generate(std::path::PathBuf::from("photo.jpg"))
+        );
+        assert_eq!(options.mosaic_size, Some([500, 300]));
+        assert_eq!(options.tessera_size, Some([8, 12]));
+        assert_eq!(options.sampling_mode, Some(SamplingMode::Median));
+        assert_eq!(options.pattern, Some(LayoutPattern::Brick));
+        assert_eq!(options.dithering, Some(DitheringMethod::Atkinson));
+        assert!(options.restrict_to_palette);
+        assert!(options.fit_to_edges);
+        assert_eq!(options.png, Some(std::path::PathBuf::from("out.png")));
+    }
+
+    #[test]
+    fn wrong_arguments_are_reported() {
+        assert!(parse_arguments(&arguments("")).is_err());
+        assert!(parse_arguments(&arguments("paint photo.jpg")).is_err());
+        assert!(parse_arguments(&arguments("generate --size 500x300")).is_err());
+        assert!(parse_arguments(&arguments("generate photo.jpg --size 500")).is_err());
+        assert!(parse_arguments(&arguments("generate photo.jpg --size 0x300")).is_err());
+        assert!(parse_arguments(&arguments("generate photo.jpg --png")).is_err());
+        assert!(parse_arguments(&arguments("export project.mosaic.json --size 500x300")).is_err());
+        assert!(parse_arguments(&arguments("generate photo.jpg --size 5x3 --width 5")).is_err());
+        assert!(parse_arguments(&arguments("generate photo.jpg --dithering atkinson")).is_err());
+    }
+
+    #[test]
+    fn help_does_not_need_the_settings() {
+        let options = CliOptions {
+            settings_file: Some(std::path::PathBuf::from("/nowhere/settings.json")),
+            ..CliOptions::default()
+        };
+        assert_eq!(run(CliCommand::Help, &options).unwrap(), vec![USAGE]);
+    }
+
+    #[test]
+    fn generate_writes_the_project_and_the_exports_then_export_reads_it_back() {
+        let folder =
+            std::env::temp_dir().join(format!("mosaicneitor-cli-test-{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        let image_path = folder.join("red.png");
+        image::RgbImage::from_pixel(40, 20, image::Rgb([255, 0, 0]))
+            .save(&image_path)
+            .unwrap();
+        let settings_path = folder.join("settings.json");
+        UserSettings::default()
+            .save_to_file(&settings_path)
+            .unwrap();
+
+        let (command, options) = parse_arguments(&arguments(&format!(
+            "generate {} --width 100 --settings {} --svg {}",
+            image_path.display(),
+            settings_path.display(),
+            folder.join("red.svg").display()
+        )))
+        .unwrap();
+        let lines = run(command, &options).unwrap();
+        assert!(lines[0].starts_with("Generated a 100x50 mm mosaic"));
+        let project_path = folder.join("red.mosaic.json");
+        assert!(project_path.exists());
+        assert!(folder.join("red.svg").exists());
+
+        let (command, options) = parse_arguments(&arguments(&format!(
+            "report {} --settings {}",
+            project_path.display(),
+            settings_path.display()
+        )))
+        .unwrap();
+        let lines = run(command, &options).unwrap();
+        assert!(lines[1].starts_with("#ff0000"));
+
+        // A page per file, all of them listed.
+        let (command, options) = parse_arguments(&arguments(&format!(
+            "export {} --settings {} --cartoon {}",
+            project_path.display(),
+            settings_path.display(),
+            folder.join("cartoon.svg").display()
+        )))
+        .unwrap();
+        let lines = run(command, &options).unwrap();
+        assert!(!lines.is_empty());
+        for line in &lines {
+            let path = line.strip_prefix("Written ").unwrap();
+            assert!(std::path::Path::new(path).exists());
+        }
+
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+}
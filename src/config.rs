@@ -14,13 +14,32 @@ pub fn default_working_folder() -> std::path::PathBuf {
     // A list of available folders is in: https://docs.rs/dirs/latest/dirs/#functions
 }
 
+pub fn settings_file() -> std::path::PathBuf {
+    match dirs::config_dir() {
+        Some(x) => x.join("mosaicneitor").join("settings.json"),
+        None => std::path::PathBuf::from("mosaicneitor_settings.json"),
+    }
+}
+
 pub const DEFAULT_OVERAL_MOSAIC_DIMENSIONS_HORIZONTAL_MM: usize = 500;
 pub const DEFAULT_OVERAL_MOSAIC_DIMENSIONS_VERTICAL_MM: usize = 300;
 pub const DEFAULT_BASE_TESSERA_SIZE_HORIZONTAL_MM: usize = 10;
 pub const DEFAULT_BASE_TESSERA_SIZE_VERTICAL_MM: usize = 10;
 pub const DEFAULT_GAP_BETWEEN_TESSSELAE: usize = 1;
+// Every step to undo a regeneration keeps a whole mosaic.
+pub const DEFAULT_MAX_UNDO_STEPS: usize = 100;
+pub const MIN_DISTANCE_BETWEEN_GUIDE_CURVE_POINTS: f32 = 2.0;
+pub const MIN_DISTANCE_BETWEEN_LASSO_POINTS: f32 = 2.0;
+// Screen points per mm of the mosaic.
+pub const MIN_ZOOM_FACTOR: f32 = 0.1;
+pub const MAX_ZOOM_FACTOR: f32 = 20.0;
+// Operating systems assume this resolution for a scale factor of 1, the real one is not known
+// unless it is measured and set in the user settings.
+pub const REFERENCE_DPI: f32 = 96.0;
+pub const MM_PER_INCH: f32 = 25.4;
 
 pub const COLOR_FOR_GRID: egui::Color32 = egui::Color32::LIGHT_RED;
+pub const COLOR_FOR_HIGHLIGHTING: egui::Color32 = egui::Color32::GOLD;
 
 #[cfg(test)]
 mod test {
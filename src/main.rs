@@ -5,10 +5,59 @@ i18n!("locales", fallback = "en");
 
 mod user_interface;
 mod user_interface_app;
-mod mosaic;
+mod user_interface_canvas;
+mod user_interface_color_replacement;
+mod user_interface_image_adjustments;
+mod user_interface_materials;
+mod user_interface_palette;
+mod user_interface_proposed_palette;
+mod user_interface_tessera;
+mod cli;
+mod settings;
 mod config;
 mod utils;
 
 fn main() -> eframe::Result<()> {
-    user_interface::lauch_user_interface()
+    // Without arguments, the usual GUI. With them, the command line mode.
+    let arguments: Vec<String> = std::env::args().skip(1).collect();
+    if arguments.is_empty() {
+        return user_interface::lauch_user_interface();
+    }
+    #[cfg(windows)]
+    attach_to_parent_console();
+    let (command, options) = match cli::parse_arguments(&arguments) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{e}\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    match cli::run(command, &options) {
+        Ok(lines) => {
+            for line in lines {
+                println!("{line}");
+            }
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+// Release builds on Windows have no console of their own, so the command line mode writes
+// to the one it was run from. Nothing happens if there is none, or if it already has one.
+#[cfg(windows)]
+fn attach_to_parent_console() {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    // SAFETY: AttachConsole takes a plain process id and no pointers. It only fails,
+    // returning 0, when there is no parent console or the process already has one.
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
 }
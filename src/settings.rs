@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use mosaicneitor_core::{
+    export_cartoon::CartoonSettings, export_chart::ChartSettings, export_png::RenderSettings,
+    materials::MaterialsSettings, tile_palette::TilePalette,
+};
+
+// User preferences that are not tied to any project.
+// Every field must have a #[serde(default)], so older settings files keep opening.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct UserSettings {
+    #[serde(default)]
+    pub tile_palette: TilePalette,
+    #[serde(default)]
+    pub materials: MaterialsSettings,
+    // None means unbounded.
+    #[serde(default = "default_max_undo_steps")]
+    pub max_undo_steps: Option<usize>,
+    #[serde(default)]
+    pub render: RenderSettings,
+    #[serde(default)]
+    pub cartoon: CartoonSettings,
+    #[serde(default)]
+    pub chart: ChartSettings,
+    // Pixels per inch of the screen, as measured, for the real size zoom.
+    // None trusts the scale of the operating system.
+    #[serde(default)]
+    pub screen_dpi: Option<f32>,
+}
+
+impl Default for UserSettings {
+    fn default() -> Self {
+        Self {
+            tile_palette: TilePalette::default(),
+            materials: MaterialsSettings::default(),
+            max_undo_steps: default_max_undo_steps(),
+            render: RenderSettings::default(),
+            cartoon: CartoonSettings::default(),
+            chart: ChartSettings::default(),
+            screen_dpi: None,
+        }
+    }
+}
+
+fn default_max_undo_steps() -> Option<usize> {
+    Some(config::DEFAULT_MAX_UNDO_STEPS)
+}
+
+impl UserSettings {
+    pub fn load_from_file(path: &std::path::Path) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => match serde_json::from_str(&text) {
+                Ok(settings) => Ok(settings),
+                Err(e) => Err(format!("The settings file is damaged: {e}")),
+            },
+            Err(e) => Err(format!("Cannot read {}: {e}", path.display())),
+        }
+    }
+
+    pub fn save_to_file(&self, path: &std::path::Path) -> Result<(), String> {
+        if let Some(folder) = path.parent() {
+            if let Err(e) = std::fs::create_dir_all(folder) {
+                return Err(format!("Cannot create {}: {e}", folder.display()));
+            }
+        }
+        let text = match serde_json::to_string_pretty(self) {
+            Ok(text) => text,
+            Err(e) => return Err(format!("Cannot convert the settings to JSON: {e}")),
+        };
+        match std::fs::write(path, text) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Cannot write {}: {e}", path.display())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn settings_survive_a_round_trip_through_a_file() {
+        let mut settings = UserSettings::default();
+        settings.tile_palette.name = String::from("Glass from the usual supplier");
+        settings.tile_palette.add_entry([10, 20, 30]);
+        settings.tile_palette.entries[0].supplier_code = String::from("VT-123");
+
+        let path = std::env::temp_dir()
+            .join(format!("mosaicneitor-test-{}", std::process::id()))
+            .join("settings.json");
+        settings.save_to_file(&path).unwrap();
+        let reloaded = UserSettings::load_from_file(&path).unwrap();
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+        assert_eq!(reloaded, settings);
+    }
+
+    #[test]
+    fn missing_fields_take_default_values() {
+        let settings: UserSettings = serde_json::from_str("{}").unwrap();
+        assert_eq!(settings, UserSettings::default());
+        assert_eq!(
+            settings.max_undo_steps,
+            Some(config::DEFAULT_MAX_UNDO_STEPS)
+        );
+    }
+}
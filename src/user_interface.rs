@@ -1,11 +1,21 @@
 use eframe::egui;
-use palette::convert::FromColor;
+
+use mosaicneitor_core::{
+    andamento::GuideCurve,
+    colors,
+    dithering::DitheringMethod,
+    export_cartoon::PaperSize,
+    export_chart::ChartKeys,
+    generator::LayoutPattern,
+    mosaic::{Mosaic, PositionOnGrid, TesseraPlacement},
+    region::{GridRectangle, RegionTransform},
+    sampling::SamplingMode,
+};
 
 use crate::{
     config,
-    mosaic::{Mosaic, PositionOnGrid, Tessera},
-    user_interface_app::{MosaicneitorApp, Zoom},
-    utils,
+    user_interface_app::{CanvasTool, MosaicneitorApp},
+    user_interface_canvas, utils,
 };
 
 pub fn lauch_user_interface() -> eframe::Result<()> {
@@ -31,10 +41,171 @@ impl eframe::App for MosaicneitorApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         ctx.set_pixels_per_point(1.5);
 
+        if !ctx.wants_keyboard_input() {
+            let (redo_requested, undo_requested) = ctx.input_mut(|i| {
+                (
+                    i.consume_shortcut(&egui::KeyboardShortcut::new(
+                        egui::Modifiers::COMMAND | egui::Modifiers::SHIFT,
+                        egui::Key::Z,
+                    )),
+                    i.consume_shortcut(&egui::KeyboardShortcut::new(
+                        egui::Modifiers::COMMAND,
+                        egui::Key::Z,
+                    )),
+                )
+            });
+            if redo_requested {
+                self.redo();
+            } else if undo_requested {
+                self.undo();
+            }
+        }
+
         egui::TopBottomPanel::top("controls").show(ctx, |ui| {
-            if ui.button(t!("btn_choose_image")).clicked() {
-                self.file_dialog.select_file();
+            ui.horizontal(|ui| {
+                if ui.button(t!("btn_open_project")).clicked() {
+                    let _ = self.project_file_dialog.open(
+                        egui_file_dialog::DialogMode::SelectFile,
+                        true,
+                        Some("open_project"),
+                    );
+                }
+                if ui.button(t!("btn_save_project")).clicked() {
+                    let _ = self.project_file_dialog.open(
+                        egui_file_dialog::DialogMode::SaveFile,
+                        true,
+                        Some("save_project"),
+                    );
+                }
+                ui.add_enabled_ui(self.history.can_undo(), |ui| {
+                    if ui.button(t!("btn_undo")).on_hover_text("Ctrl+Z").clicked() {
+                        self.undo();
+                    }
+                });
+                ui.add_enabled_ui(self.history.can_redo(), |ui| {
+                    if ui
+                        .button(t!("btn_redo"))
+                        .on_hover_text("Ctrl+Shift+Z")
+                        .clicked()
+                    {
+                        self.redo();
+                    }
+                });
+                ui.menu_button(t!("undo_history"), |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{}:", t!("max_undo_steps")));
+                        // 0 stands for no limit.
+                        let mut max_undo_steps = self.settings.max_undo_steps.unwrap_or(0);
+                        if ui
+                            .add(egui::DragValue::new(&mut max_undo_steps).range(0..=10_000))
+                            .on_hover_text(t!("max_undo_steps_help"))
+                            .changed()
+                        {
+                            self.set_max_undo_steps(
+                                Some(max_undo_steps).filter(|steps| *steps > 0),
+                            );
+                        }
+                    });
+                    if ui.button(t!("btn_save_settings")).clicked() {
+                        self.save_settings();
+                        ui.close_menu();
+                    }
+                });
+                ui.menu_button(t!("export"), |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{}:", t!("pixels_per_mm")));
+                        ui.add(
+                            egui::DragValue::new(&mut self.settings.render.pixels_per_mm)
+                                .range(0.1..=100.0)
+                                .speed(0.1),
+                        );
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{}:", t!("grout_color")));
+                        ui.color_edit_button_srgb(&mut self.settings.render.grout_srgb);
+                    });
+                    if ui.button(t!("btn_export_png")).clicked() {
+                        self.open_export_dialog("export_png", "mosaic.png");
+                        ui.close_menu();
+                    }
+                    if ui.button(t!("btn_export_svg")).clicked() {
+                        self.open_export_dialog("export_svg", "mosaic.svg");
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    let cartoon = &mut self.settings.cartoon;
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{}:", t!("paper_size")));
+                        for paper_size in PaperSize::ALL {
+                            ui.selectable_value(
+                                &mut cartoon.paper_size,
+                                paper_size,
+                                paper_size.get_name(),
+                            );
+                        }
+                        ui.checkbox(&mut cartoon.landscape, t!("landscape"));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{} (mm):", t!("page_margin")));
+                        ui.add(egui::DragValue::new(&mut cartoon.margin_mm).range(0.0..=50.0));
+                        ui.label(format!("{} (mm):", t!("page_overlap")));
+                        ui.add(egui::DragValue::new(&mut cartoon.overlap_mm).range(0.0..=50.0));
+                    });
+                    ui.checkbox(&mut cartoon.mirrored, t!("mirrored_for_reverse_method"));
+                    if ui.button(t!("btn_export_cartoon")).clicked() {
+                        self.open_export_dialog("export_cartoon", "cartoon.svg");
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    let chart = &mut self.settings.chart;
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{}:", t!("chart_keys")));
+                        ui.selectable_value(&mut chart.keys, ChartKeys::Numbers, t!("numbers"));
+                        ui.selectable_value(&mut chart.keys, ChartKeys::Symbols, t!("symbols"));
+                        ui.checkbox(&mut chart.colored_cells, t!("colored_cells"));
+                    });
+                    if ui.button(t!("btn_export_chart")).clicked() {
+                        self.open_export_dialog("export_chart", "chart.svg");
+                        ui.close_menu();
+                    }
+                });
+                ui.toggle_value(&mut self.show_materials_report, t!("materials_report"));
+                ui.label(&self.status_message);
+            });
+            self.project_file_dialog.update(ctx);
+            if let Some(path) = self.project_file_dialog.take_selected() {
+                match self.project_file_dialog.operation_id() {
+                    Some("open_project") => self.open_project(path),
+                    Some("save_project") => self.save_project(path),
+                    _ => (),
+                }
             }
+            self.export_file_dialog.update(ctx);
+            if let Some(path) = self.export_file_dialog.take_selected() {
+                match self.export_file_dialog.operation_id() {
+                    Some("export_materials_report") => self.export_materials_report(path),
+                    Some("export_png") => self.export_png(path),
+                    Some("export_svg") => self.export_svg(path),
+                    Some("export_cartoon") => self.export_cartoon(path),
+                    Some("export_chart") => self.export_chart(path),
+                    _ => (),
+                }
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button(t!("btn_choose_image")).clicked() {
+                    self.file_dialog.select_file();
+                }
+                if ui
+                    .add_enabled(
+                        self.original_image.is_some(),
+                        egui::Button::new(t!("btn_adjust_image")),
+                    )
+                    .clicked()
+                {
+                    self.show_image_adjustments = true;
+                }
+            });
             self.file_dialog.update(ctx);
             if let Some(path) = self.file_dialog.take_selected() {
                 self.selected_file = Some(path.to_path_buf());
@@ -82,19 +253,177 @@ impl eframe::App for MosaicneitorApp {
                     .button(t!("btn_generate_a_new_mosaic_from_image"))
                     .clicked()
                 {
-                    self.mosaic = self.get_mosaic_from_loaded_image();
+                    self.generate_mosaic_from_loaded_image();
                     self.show_tesserae_grid = true;
                     self.show_actual_tesserae = true;
                 }
+                ui.checkbox(&mut self.restrict_to_palette, t!("restrict_to_palette"));
+                ui.checkbox(&mut self.fit_to_edges, t!("fit_to_edges"))
+                    .on_hover_text(t!("fit_to_edges_help"));
+                if ui.button(t!("btn_edit_palette")).clicked() {
+                    self.show_palette_editor = true;
+                }
+                if ui.button(t!("btn_propose_a_palette")).clicked() {
+                    self.show_proposed_palette = true;
+                }
+                egui::ComboBox::from_label(t!("layout_pattern"))
+                    .selected_text(layout_pattern_name(self.pattern))
+                    .show_ui(ui, |ui| {
+                        for pattern in LayoutPattern::ALL {
+                            ui.selectable_value(
+                                &mut self.pattern,
+                                pattern,
+                                layout_pattern_name(pattern),
+                            );
+                        }
+                    });
+                egui::ComboBox::from_label(t!("sampling_mode"))
+                    .selected_text(sampling_mode_name(self.sampling_mode))
+                    .show_ui(ui, |ui| {
+                        for mode in SamplingMode::ALL {
+                            ui.selectable_value(
+                                &mut self.sampling_mode,
+                                mode,
+                                sampling_mode_name(mode),
+                            );
+                        }
+                    });
                 ui.add_space(75.0);
                 if ui.button(t!("btn_generate_a_new_blank_mosaic")).clicked() {
-                    self.mosaic =
-                        self.get_a_blank_mosaic_with_all_tesserae_equal_color(egui::Color32::WHITE);
+                    self.replace_mosaic(
+                        self.get_a_blank_mosaic_with_all_tesserae_equal_color(egui::Color32::WHITE),
+                    );
                     self.show_tesserae_grid = true;
                     self.show_actual_tesserae = true;
                 }
             });
+            ui.horizontal(|ui| {
+                ui.label(format!("{}:", t!("dithering")));
+                egui::ComboBox::from_id_source("dithering_method")
+                    .selected_text(dithering_method_name(self.dithering.method))
+                    .show_ui(ui, |ui| {
+                        for method in DitheringMethod::ALL {
+                            ui.selectable_value(
+                                &mut self.dithering.method,
+                                method,
+                                dithering_method_name(method),
+                            );
+                        }
+                    })
+                    .response
+                    .on_hover_text(t!("dithering_help"));
+                ui.add(
+                    egui::Slider::new(&mut self.dithering.strength, 0.0..=1.0)
+                        .text(t!("dithering_strength")),
+                );
+                ui.add_enabled_ui(!self.colors_without_dithering.is_empty(), |ui| {
+                    ui.toggle_value(&mut self.comparing_dithering, t!("btn_compare_dithering"))
+                        .on_hover_text(t!("compare_dithering_help"))
+                        .on_disabled_hover_text(t!("compare_dithering_disabled_help"));
+                });
+                if self.comparing_dithering {
+                    ui.add(
+                        egui::Slider::new(&mut self.comparison_split, 0.0..=1.0).show_value(false),
+                    );
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label(format!("{}:", t!("andamento")));
+                ui.toggle_value(&mut self.drawing_guide_curves, t!("btn_draw_guide_curves"))
+                    .on_hover_text(t!("draw_guide_curves_help"));
+                if ui.button(t!("btn_guide_curves_from_image_edges")).clicked() {
+                    self.set_guide_curves_from_image_edges();
+                }
+                if ui.button(t!("btn_clear_guide_curves")).clicked() {
+                    self.guide_curves.clear();
+                }
+                ui.label(t!("rows_on_each_side_of_a_curve"));
+                ui.add(egui::DragValue::new(&mut self.andamento.rows_on_each_side).range(0..=50));
+                ui.checkbox(&mut self.andamento.fill_background, t!("fill_background"));
+                if ui.button(t!("btn_lay_tesserae_along_the_curves")).clicked() {
+                    self.replace_mosaic(self.get_andamento_mosaic_from_loaded_image());
+                    self.show_actual_tesserae = true;
+                }
+            });
             ui.separator();
+            ui.horizontal(|ui| {
+                ui.label(format!("{}:", t!("tools")));
+                for tool in CanvasTool::ALL {
+                    if ui
+                        .selectable_value(&mut self.tool, tool, canvas_tool_name(tool))
+                        .changed()
+                    {
+                        self.brush_stroke.clear();
+                        self.tool_path.clear();
+                        self.moving_region_from = None;
+                    }
+                }
+                match self.tool {
+                    CanvasTool::Brush => {
+                        ui.label(format!("{}:", t!("brush_radius")));
+                        ui.add(egui::DragValue::new(&mut self.brush_radius).range(0..=20))
+                            .on_hover_text(t!("brush_radius_help"));
+                    }
+                    CanvasTool::FloodFill => {
+                        ui.add(
+                            egui::Slider::new(&mut self.fill_tolerance, 0.0..=0.5)
+                                .text(t!("fill_tolerance")),
+                        )
+                        .on_hover_text(t!("fill_tolerance_help"));
+                    }
+                    CanvasTool::Region => {
+                        let has_region = self.selected_region.is_some();
+                        if ui
+                            .add_enabled(has_region, egui::Button::new(t!("btn_copy")))
+                            .clicked()
+                        {
+                            self.copy_region();
+                        }
+                        if ui
+                            .add_enabled(has_region, egui::Button::new(t!("btn_cut")))
+                            .clicked()
+                        {
+                            self.cut_region();
+                        }
+                        if ui
+                            .add_enabled(
+                                self.clipboard.is_some(),
+                                egui::Button::new(t!("btn_paste")),
+                            )
+                            .on_hover_text(t!("paste_help"))
+                            .clicked()
+                        {
+                            self.paste_region();
+                        }
+                        for (transform, text) in [
+                            (RegionTransform::FlipHorizontal, t!("btn_flip_horizontal")),
+                            (RegionTransform::FlipVertical, t!("btn_flip_vertical")),
+                            (RegionTransform::RotateClockwise, t!("btn_rotate_clockwise")),
+                        ] {
+                            if ui
+                                .add_enabled(has_region, egui::Button::new(text))
+                                .clicked()
+                            {
+                                self.transform_region(transform);
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+                ui.separator();
+                if ui.button(t!("btn_replace_color")).clicked() {
+                    self.show_color_replacement = true;
+                }
+                match self.tool {
+                    CanvasTool::Select => (),
+                    CanvasTool::Region => {
+                        ui.label(t!("region_help"));
+                    }
+                    _ => {
+                        ui.label(t!("tools_help"));
+                    }
+                }
+            });
             ui.horizontal(|ui| {
                 ui.label(format!("{}: ", t!("show")));
                 ui.checkbox(&mut self.show_image, t!("image"));
@@ -102,28 +431,88 @@ impl eframe::App for MosaicneitorApp {
                 ui.checkbox(&mut self.show_actual_tesserae, t!("actual_tesserae"));
                 ui.add_space(45.0);
                 ui.label("Zoom: ");
-                ui.selectable_value(&mut self.zoom_level, Zoom::X1, "x1");
-                ui.selectable_value(&mut self.zoom_level, Zoom::X2, "x2");
-                ui.selectable_value(&mut self.zoom_level, Zoom::X3, "x3");
-                ui.selectable_value(&mut self.zoom_level, Zoom::X4, "x4");
-                ui.selectable_value(&mut self.zoom_level, Zoom::X5, "x5");
+                let mut zoom_factor = self.get_zoom_factor();
+                if ui
+                    .add(
+                        egui::Slider::new(
+                            &mut zoom_factor,
+                            config::MIN_ZOOM_FACTOR..=config::MAX_ZOOM_FACTOR,
+                        )
+                        .logarithmic(true)
+                        .max_decimals(2)
+                        .prefix("x"),
+                    )
+                    .on_hover_text(t!("zoom_help"))
+                    .changed()
+                {
+                    self.set_zoom_factor(zoom_factor);
+                }
+                if ui.button(t!("btn_fit_to_window")).clicked() {
+                    self.fit_zoom_to(self.canvas_size);
+                }
+                if ui
+                    .button(t!("btn_real_size"))
+                    .on_hover_text(t!("real_size_help"))
+                    .clicked()
+                {
+                    self.set_real_size_zoom(
+                        ctx.native_pixels_per_point().unwrap_or(1.0),
+                        ctx.pixels_per_point(),
+                    );
+                }
+                ui.menu_button(t!("screen_calibration"), |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{}:", t!("screen_dpi")));
+                        // 0 stands for the operating system scale.
+                        let mut screen_dpi = self.settings.screen_dpi.unwrap_or(0.0);
+                        if ui
+                            .add(egui::DragValue::new(&mut screen_dpi).range(0.0..=1000.0))
+                            .on_hover_text(t!("screen_dpi_help"))
+                            .changed()
+                        {
+                            self.settings.screen_dpi = Some(screen_dpi).filter(|dpi| *dpi > 0.0);
+                        }
+                    });
+                    if ui.button(t!("btn_save_settings")).clicked() {
+                        self.save_settings();
+                        ui.close_menu();
+                    }
+                });
             });
         });
 
+        if self.show_palette_editor {
+            self.show_palette_editor_window(ctx);
+        }
+        if self.show_proposed_palette {
+            self.show_proposed_palette_window(ctx);
+        }
+        if self.show_image_adjustments {
+            self.show_image_adjustments_window(ctx);
+        }
+        if self.show_color_replacement {
+            self.show_color_replacement_window(ctx);
+        }
+        if self.show_materials_report {
+            self.show_materials_report_panel(ctx);
+        }
+        if self.selected_tessera.is_some() {
+            self.show_tessera_editor_window(ctx);
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
-            egui::ScrollArea::both().show(ui, |ui| {
-                let mosaic_dimensions = [
-                    self.get_mosaic_dimensions()[0] * self.get_zoom_factor(),
-                    self.get_mosaic_dimensions()[1] * self.get_zoom_factor(),
-                ];
-                let tessera_size = [
-                    self.get_tessera_size()[0] * self.get_zoom_factor(),
-                    self.get_tessera_size()[1] * self.get_zoom_factor(),
-                ];
-                let gap_between_tesserae =
-                    config::DEFAULT_GAP_BETWEEN_TESSSELAE * self.get_zoom_factor();
-                let display_size =
-                    egui::Vec2::new(mosaic_dimensions[0] as f32, mosaic_dimensions[1] as f32);
+            self.canvas_size = ui.available_size();
+            let mut scroll_area = egui::ScrollArea::both();
+            if let Some(offset) = self.pending_scroll_offset.take() {
+                scroll_area = scroll_area.scroll_offset(offset);
+            }
+            scroll_area.show(ui, |ui| {
+                let display_size = egui::Vec2::new(
+                    self.get_mosaic_dimensions()[0] as f32 * self.get_zoom_factor(),
+                    self.get_mosaic_dimensions()[1] as f32 * self.get_zoom_factor(),
+                );
+                let gap_between_tesserae = config::DEFAULT_GAP_BETWEEN_TESSSELAE;
+                let scroll_offset = ui.clip_rect().min - ui.min_rect().min;
                 let start_position = egui::Pos2 {
                     x: ui.next_widget_position().x + 1.0,
                     y: ui.next_widget_position().y + 1.0,
@@ -132,16 +521,25 @@ impl eframe::App for MosaicneitorApp {
                     x: start_position.x + display_size.x,
                     y: start_position.y + display_size.y,
                 };
-                let (_response, painter) = ui.allocate_painter(display_size, egui::Sense::hover());
+                let (response, painter) =
+                    ui.allocate_painter(display_size, egui::Sense::click_and_drag());
+                let visible_area = user_interface_canvas::get_visible_area(
+                    ui.clip_rect(),
+                    start_position,
+                    self.get_zoom_factor(),
+                );
                 if self.show_image {
                     match &self.image {
                         None => (),
                         Some(img) => {
-                            let handle = ctx.load_texture(
-                                "image-to-display",
-                                egui::ImageData::from(img.clone()),
-                                egui::TextureOptions::default(),
-                            );
+                            // Uploaded once, until the image changes.
+                            let handle = self.image_texture.get_or_insert_with(|| {
+                                ctx.load_texture(
+                                    "image-to-display",
+                                    egui::ImageData::from(img.clone()),
+                                    egui::TextureOptions::default(),
+                                )
+                            });
                             painter.image(
                                 handle.id(),
                                 egui::Rect::from_min_max(start_position, end_position),
@@ -154,221 +552,506 @@ impl eframe::App for MosaicneitorApp {
                         }
                     }
                 };
-                if self.show_tesserae_grid {
-                    let tesserae_grid = generate_shapes_to_paint_tesserae_grid(
+                self.canvas_cache.update(
+                    self.canvas_revision,
+                    &self.mosaic,
+                    gap_between_tesserae,
+                    &self.colors_without_dithering,
+                );
+                // Before generating, the grid previews the tesserae of the general size, in the pattern.
+                if self.show_tesserae_grid
+                    && self.mosaic.get_number_of_rows() == 0
+                    && self.mosaic.get_free_tesserae().is_empty()
+                {
+                    let settings = self.get_generation_settings();
+                    painter.extend(self.canvas_cache.grid_preview_shapes(
+                        &settings,
+                        visible_area,
                         start_position,
-                        end_position,
-                        tessera_size,
-                        gap_between_tesserae,
-                    );
-                    painter.extend(tesserae_grid);
-                };
+                        self.get_zoom_factor(),
+                    ));
+                } else if self.show_tesserae_grid {
+                    painter.extend(self.canvas_cache.outline_shapes(
+                        visible_area,
+                        start_position,
+                        self.get_zoom_factor(),
+                    ));
+                }
                 if self.show_actual_tesserae {
-                    let actual_tesserae = generate_shapes_to_paint_mosaic(
-                        &self.mosaic,
+                    let split = self.comparison_split * self.get_mosaic_dimensions()[0] as f32;
+                    painter.extend(self.canvas_cache.fill_shapes(
+                        visible_area,
+                        start_position,
+                        self.get_zoom_factor(),
+                        self.comparing_dithering.then_some(split),
+                    ));
+                    if self.comparing_dithering {
+                        let x = start_position.x + split * self.get_zoom_factor();
+                        painter.vline(
+                            x,
+                            start_position.y..=end_position.y,
+                            egui::Stroke::new(2.0, config::COLOR_FOR_HIGHLIGHTING),
+                        );
+                    }
+                }
+                if self.show_color_replacement {
+                    painter.extend(self.canvas_cache.highlight_shapes(
+                        &self.tesserae_to_replace,
+                        visible_area,
+                        start_position,
+                        self.get_zoom_factor(),
+                    ));
+                }
+                for curve in &self.guide_curves {
+                    painter.add(egui::epaint::Shape::line(
+                        curve
+                            .points
+                            .iter()
+                            .map(|point| {
+                                get_point_on_screen(*point, start_position, self.get_zoom_factor())
+                            })
+                            .collect(),
+                        egui::Stroke::new(2.0, config::COLOR_FOR_HIGHLIGHTING),
+                    ));
+                }
+                if let Some(rectangle) = self.selected_tessera.and_then(|position| {
+                    get_tessera_rectangle_on_screen(
+                        &position,
                         start_position,
                         self.get_zoom_factor(),
+                        &self.mosaic,
                         gap_between_tesserae,
+                    )
+                }) {
+                    painter.rect_stroke(
+                        rectangle,
+                        eframe::egui::Rounding::ZERO,
+                        egui::Stroke::new(2.0, config::COLOR_FOR_HIGHLIGHTING),
                     );
-                    painter.extend(actual_tesserae);
                 }
-                if ui.rect_contains_pointer(egui::Rect::from_min_max(start_position, end_position))
-                {
-                    ctx.input(|i| {
-                        if i.pointer.button_clicked(egui::PointerButton::Secondary) {
-                            if let Some(pos) = i.pointer.interact_pos() {
-                                let tessera_position = get_tessera_position(
-                                    pos,
-                                    start_position,
-                                    tessera_size,
-                                    gap_between_tesserae,
-                                );
-                                let dummy_color_srgba: palette::Srgba<f32> = palette::Srgba::from(
-                                    egui::Color32::LIGHT_BLUE.to_srgba_unmultiplied(),
-                                )
-                                .into();
-                                let dummy_color = palette::Oklch::from_color(dummy_color_srgba);
-                                match self.mosaic.change_tessera(
-                                    &tessera_position,
-                                    Tessera { color: dummy_color },
-                                ) {
-                                    Ok(_s) => (),
-                                    Err(e) => println!("{e}"),
-                                };
+                self.paint_tool_stroke(&painter, start_position, gap_between_tesserae);
+                if self.drawing_guide_curves {
+                    self.draw_guide_curve(&response, start_position);
+                } else if self.tool != CanvasTool::Select {
+                    self.use_tool(&response, start_position);
+                } else if let Some(pos) = response.interact_pointer_pos() {
+                    let tessera_position = get_tessera_position(
+                        pos,
+                        start_position,
+                        self.get_zoom_factor(),
+                        &self.mosaic,
+                        gap_between_tesserae,
+                    );
+                    if response.clicked() {
+                        if self.eyedropper_active {
+                            let zoom_factor = self.get_zoom_factor();
+                            self.pick_color_from_image([
+                                ((pos.x - start_position.x) / zoom_factor) as usize,
+                                ((pos.y - start_position.y) / zoom_factor) as usize,
+                            ]);
+                            self.eyedropper_active = false;
+                        } else if self.picking_replacement_source {
+                            if let Some(tessera) = tessera_position
+                                .and_then(|position| self.mosaic.get_tessera(&position))
+                            {
+                                self.replacement_source = Some(tessera.color);
+                                self.picking_replacement_source = false;
                             }
+                        } else if tessera_position.is_some() {
+                            self.selected_tessera = tessera_position;
                         }
-                    });
+                    } else if response.secondary_clicked() {
+                        if let Some(position) = tessera_position {
+                            self.apply_color_to_tessera(&position);
+                        }
+                    }
+                }
+                // Ctrl + wheel, or pinch.
+                let zoom_delta = ui.input(|i| i.zoom_delta());
+                if zoom_delta != 1.0 {
+                    if let Some(pointer) = response.hover_pos() {
+                        self.zoom_around(zoom_delta, pointer, start_position, scroll_offset);
+                    }
                 }
             });
         });
     }
 }
 
-fn generate_shapes_to_paint_tesserae_grid(
-    start_position: egui::Pos2,
-    end_position: egui::Pos2,
-    tessera_size: [usize; 2],
-    gap_between_tesserae: usize,
-) -> Vec<egui::epaint::Shape> {
-    let mut shapes = Vec::new();
-    let stroke_width = 1.0;
-    let stroke_color = config::COLOR_FOR_GRID;
-    for tessera_origin_x in ((start_position.x as usize)..(end_position.x as usize))
-        .step_by(tessera_size[0] + gap_between_tesserae)
-    {
-        for tessera_origin_y in ((start_position.y as usize)..(end_position.y as usize))
-            .step_by(tessera_size[1] + gap_between_tesserae)
-        {
-            let start_point = egui::Pos2 {
-                x: (tessera_origin_x) as f32,
-                y: (tessera_origin_y) as f32,
-            };
-            let end_point = egui::Pos2 {
-                x: (tessera_origin_x + tessera_size[0]) as f32,
-                y: (tessera_origin_y + tessera_size[1]) as f32,
+impl MosaicneitorApp {
+    // A curve for each stroke on the canvas.
+    fn draw_guide_curve(&mut self, response: &egui::Response, start_position: egui::Pos2) {
+        if let Some(pos) = response.interact_pointer_pos() {
+            let zoom_factor = self.get_zoom_factor();
+            let point = [
+                (pos.x - start_position.x) / zoom_factor,
+                (pos.y - start_position.y) / zoom_factor,
+            ];
+            if response.drag_started() {
+                self.guide_curves.push(GuideCurve::default());
+            }
+            if response.dragged() {
+                self.add_point_to_last_guide_curve(point);
+            }
+        }
+    }
+
+    fn use_tool(&mut self, response: &egui::Response, start_position: egui::Pos2) {
+        if response.drag_started() {
+            self.brush_stroke.clear();
+            self.tool_path.clear();
+        }
+        if let Some(pos) = response.interact_pointer_pos() {
+            let zoom_factor = self.get_zoom_factor();
+            let point = [
+                (pos.x - start_position.x) / zoom_factor,
+                (pos.y - start_position.y) / zoom_factor,
+            ];
+            if response.dragged() {
+                self.add_point_to_tool_stroke(point);
+            }
+            if response.clicked() {
+                if self.tool == CanvasTool::FloodFill {
+                    self.flood_fill_at(point);
+                } else {
+                    self.add_point_to_tool_stroke(point);
+                    self.finish_tool_stroke();
+                }
+            }
+        }
+        if response.drag_stopped() {
+            self.finish_tool_stroke();
+        }
+    }
+
+    // What the current stroke would paint, until the button is released.
+    fn paint_tool_stroke(
+        &self,
+        painter: &egui::Painter,
+        start_position: egui::Pos2,
+        gap_between_tesserae: usize,
+    ) {
+        let stroke = egui::Stroke::new(2.0, config::COLOR_FOR_HIGHLIGHTING);
+        let [r, g, b] = colors::srgb8_from_oklch(self.color_to_apply);
+        for position in &self.brush_stroke {
+            if let Some(rectangle) = get_tessera_rectangle_on_screen(
+                position,
+                start_position,
+                self.get_zoom_factor(),
+                &self.mosaic,
+                gap_between_tesserae,
+            ) {
+                painter.rect_filled(
+                    rectangle,
+                    eframe::egui::Rounding::ZERO,
+                    egui::Color32::from_rgb(r, g, b),
+                );
+            }
+        }
+        let points: Vec<egui::Pos2> = self
+            .tool_path
+            .iter()
+            .map(|point| get_point_on_screen(*point, start_position, self.get_zoom_factor()))
+            .collect();
+        if let Some(rectangle) = self.selected_region.and_then(|region| {
+            get_region_rectangle_on_screen(
+                &region,
+                start_position,
+                self.get_zoom_factor(),
+                &self.mosaic,
+                gap_between_tesserae,
+            )
+        }) {
+            // Follows the pointer while moving it.
+            let offset = match (self.moving_region_from, points.as_slice()) {
+                (Some(_from), [start, .., end]) => *end - *start,
+                _ => egui::Vec2::ZERO,
             };
-            shapes.push(egui::epaint::Shape::Rect(egui::epaint::RectShape {
-                rect: egui::Rect {
-                    min: start_point,
-                    max: end_point,
-                },
-                rounding: eframe::egui::Rounding::ZERO,
-                fill: egui::Color32::TRANSPARENT,
-                stroke: egui::epaint::Stroke::new(stroke_width, stroke_color),
-                blur_width: 0.0,
-                fill_texture_id: egui::TextureId::default(),
-                uv: egui::Rect::ZERO,
-            }));
+            let rectangle = rectangle.translate(offset);
+            painter.extend(egui::epaint::Shape::dashed_line(
+                &[
+                    rectangle.left_top(),
+                    rectangle.right_top(),
+                    rectangle.right_bottom(),
+                    rectangle.left_bottom(),
+                    rectangle.left_top(),
+                ],
+                stroke,
+                6.0,
+                4.0,
+            ));
+        }
+        match (self.tool, points.as_slice()) {
+            (CanvasTool::Region, [corner, .., opposite_corner])
+                if self.moving_region_from.is_none() =>
+            {
+                painter.rect_stroke(
+                    egui::Rect::from_two_pos(*corner, *opposite_corner),
+                    eframe::egui::Rounding::ZERO,
+                    stroke,
+                );
+            }
+            (CanvasTool::Rectangle, [corner, .., opposite_corner]) => {
+                painter.rect_stroke(
+                    egui::Rect::from_two_pos(*corner, *opposite_corner),
+                    eframe::egui::Rounding::ZERO,
+                    stroke,
+                );
+            }
+            (CanvasTool::Lasso, [_, _, ..]) => {
+                painter.add(egui::epaint::Shape::closed_line(points, stroke));
+            }
+            _ => (),
         }
     }
-    shapes
 }
 
-fn generate_shapes_to_paint_mosaic(
-    mosaic: &Mosaic,
+fn canvas_tool_name(tool: CanvasTool) -> String {
+    match tool {
+        CanvasTool::Select => t!("tool_select").to_string(),
+        CanvasTool::Brush => t!("tool_brush").to_string(),
+        CanvasTool::FloodFill => t!("tool_flood_fill").to_string(),
+        CanvasTool::Rectangle => t!("tool_rectangle").to_string(),
+        CanvasTool::Lasso => t!("tool_lasso").to_string(),
+        CanvasTool::Region => t!("tool_region").to_string(),
+    }
+}
+
+fn sampling_mode_name(mode: SamplingMode) -> String {
+    match mode {
+        SamplingMode::CenterPixel => t!("sampling_center_pixel").to_string(),
+        SamplingMode::Average => t!("sampling_average").to_string(),
+        SamplingMode::Median => t!("sampling_median").to_string(),
+        SamplingMode::DominantColor => t!("sampling_dominant_color").to_string(),
+    }
+}
+
+fn dithering_method_name(method: DitheringMethod) -> String {
+    match method {
+        DitheringMethod::None => t!("dithering_none").to_string(),
+        DitheringMethod::FloydSteinberg => t!("dithering_floyd_steinberg").to_string(),
+        DitheringMethod::Atkinson => t!("dithering_atkinson").to_string(),
+        DitheringMethod::Ordered => t!("dithering_ordered").to_string(),
+    }
+}
+
+fn layout_pattern_name(pattern: LayoutPattern) -> String {
+    match pattern {
+        LayoutPattern::Regulatum => t!("pattern_regulatum").to_string(),
+        LayoutPattern::Brick => t!("pattern_brick").to_string(),
+        LayoutPattern::Tessellatum => t!("pattern_tessellatum").to_string(),
+        LayoutPattern::Vermiculatum => t!("pattern_vermiculatum").to_string(),
+    }
+}
+
+fn get_rectangle_on_screen(
+    placement: &TesseraPlacement,
     start_position: egui::Pos2,
-    zoom_factor: usize,
-    gap_between_tesserae: usize,
-) -> Vec<egui::Shape> {
-    let mut shapes = Vec::new();
-    let tessera_size = [
-        mosaic.get_general_tessera_size().horizontal * zoom_factor,
-        mosaic.get_general_tessera_size().vertical * zoom_factor,
-    ];
-    let mut y = start_position.y;
-    for row in mosaic.get_contents() {
-        let mut x = start_position.x;
-        for tessera in row {
-            let rgbcolor_for_tessera: palette::Srgba<u8> =
-                palette::Srgba::from_color(tessera.color).into();
-            let egui_color_for_tessera = egui::Color32::from_rgb(
-                rgbcolor_for_tessera.red,
-                rgbcolor_for_tessera.green,
-                rgbcolor_for_tessera.blue,
-            );
-            shapes.push(egui::epaint::Shape::Rect(egui::epaint::RectShape {
-                rect: egui::Rect {
-                    min: egui::pos2(x, y),
-                    max: egui::pos2(x + tessera_size[0] as f32, y + tessera_size[1] as f32),
-                },
-                rounding: eframe::egui::Rounding::ZERO,
-                fill: egui_color_for_tessera,
-                stroke: egui::epaint::Stroke::new(1.0, egui_color_for_tessera),
-                blur_width: 0.0,
-                fill_texture_id: egui::TextureId::default(),
-                uv: egui::Rect::ZERO,
-            }));
-            x = x + (tessera_size[0] + gap_between_tesserae) as f32;
-        }
-        y = y + (tessera_size[1] + gap_between_tesserae) as f32;
+    zoom_factor: f32,
+) -> egui::Rect {
+    egui::Rect::from_min_size(
+        egui::Pos2 {
+            x: start_position.x + placement.origin[0] as f32 * zoom_factor,
+            y: start_position.y + placement.origin[1] as f32 * zoom_factor,
+        },
+        egui::Vec2::new(
+            placement.size.horizontal as f32 * zoom_factor,
+            placement.size.vertical as f32 * zoom_factor,
+        ),
+    )
+}
+
+fn get_point_on_screen(
+    point_in_mm: [f32; 2],
+    start_position: egui::Pos2,
+    zoom_factor: f32,
+) -> egui::Pos2 {
+    egui::Pos2 {
+        x: start_position.x + point_in_mm[0] * zoom_factor,
+        y: start_position.y + point_in_mm[1] * zoom_factor,
     }
-    shapes
 }
 
+// None when clicking outside the tesserae.
 fn get_tessera_position(
     clicked_position: egui::Pos2,
     start_position: egui::Pos2,
-    tessera_size: [usize; 2],
+    zoom_factor: f32,
+    mosaic: &Mosaic,
     gap_between_tesserae: usize,
-) -> PositionOnGrid {
-    let normalized_position = clicked_position - start_position;
-    PositionOnGrid {
-        column: (normalized_position.x / (tessera_size[0] + gap_between_tesserae) as f32).ceil()
-            as usize,
-        row: (normalized_position.y / (tessera_size[1] + gap_between_tesserae) as f32).ceil()
-            as usize,
-    }
+) -> Option<PositionOnGrid> {
+    let normalized_position = (clicked_position - start_position) / zoom_factor;
+    mosaic.get_position_at_point(
+        [normalized_position.x, normalized_position.y],
+        gap_between_tesserae,
+    )
+}
+
+fn get_tessera_rectangle_on_screen(
+    position: &PositionOnGrid,
+    start_position: egui::Pos2,
+    zoom_factor: f32,
+    mosaic: &Mosaic,
+    gap_between_tesserae: usize,
+) -> Option<egui::Rect> {
+    mosaic
+        .get_tessera_placement(position, gap_between_tesserae)
+        .map(|placement| get_rectangle_on_screen(&placement, start_position, zoom_factor))
+}
+
+// Around the tesserae at its corners, rows can be of different sizes.
+fn get_region_rectangle_on_screen(
+    region: &GridRectangle,
+    start_position: egui::Pos2,
+    zoom_factor: f32,
+    mosaic: &Mosaic,
+    gap_between_tesserae: usize,
+) -> Option<egui::Rect> {
+    [
+        region.first,
+        region.last,
+        PositionOnGrid {
+            row: region.first.row,
+            column: region.last.column,
+        },
+        PositionOnGrid {
+            row: region.last.row,
+            column: region.first.column,
+        },
+    ]
+    .iter()
+    .filter_map(|corner| {
+        get_tessera_rectangle_on_screen(
+            corner,
+            start_position,
+            zoom_factor,
+            mosaic,
+            gap_between_tesserae,
+        )
+    })
+    .reduce(|a, b| a.union(b))
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use mosaicneitor_core::{
+        mosaic::{RectangleInMm, Tessera},
+        tessera_shape::TesseraShape,
+    };
+
+    fn a_mosaic_with_rows_of_tesserae_of_sizes(rows: &[(usize, [usize; 2])]) -> Mosaic {
+        let mut mosaic = Mosaic::new(
+            None,
+            RectangleInMm {
+                horizontal: 10,
+                vertical: 10,
+            },
+        );
+        for (count, [horizontal, vertical]) in rows {
+            mosaic.add_a_row_of_tesserae(vec![
+                Tessera {
+                    color: palette::Oklch::new(0.5, 0.0, 0.0),
+                    size: RectangleInMm {
+                        horizontal: *horizontal,
+                        vertical: *vertical,
+                    },
+                    shape: TesseraShape::Whole,
+                };
+                *count
+            ]);
+        }
+        mosaic
+    }
 
     #[test]
     fn translation_from_clicked_position_to_mosaic_position_yieds_correct_row_and_colum() {
         let start_position = egui::Pos2 { x: 0.0, y: 0.0 };
-        let tessera_size = [10, 10];
+        let mosaic = a_mosaic_with_rows_of_tesserae_of_sizes(&[(10, [10, 10]); 6]);
         let gap_size = 1;
-
-        assert_eq!(
+        let position_at = |x: f32, y: f32, zoom_factor: f32| {
             get_tessera_position(
-                egui::Pos2 { x: 5.0, y: 5.0 },
+                egui::Pos2 { x, y },
                 start_position,
-                tessera_size,
-                gap_size
-            ),
-            PositionOnGrid { row: 1, column: 1 }
-        );
+                zoom_factor,
+                &mosaic,
+                gap_size,
+            )
+        };
 
         assert_eq!(
-            get_tessera_position(
-                egui::Pos2 { x: 5.0, y: 15.0 },
-                start_position,
-                tessera_size,
-                gap_size
-            ),
-            PositionOnGrid { row: 2, column: 1 }
+            position_at(5.0, 5.0, 1.0),
+            Some(PositionOnGrid { row: 1, column: 1 })
         );
-
         assert_eq!(
-            get_tessera_position(
-                egui::Pos2 { x: 15.0, y: 5.0 },
-                start_position,
-                tessera_size,
-                gap_size
-            ),
-            PositionOnGrid { row: 1, column: 2 }
+            position_at(5.0, 15.0, 1.0),
+            Some(PositionOnGrid { row: 2, column: 1 })
         );
-
         assert_eq!(
-            get_tessera_position(
-                egui::Pos2 { x: 87.0, y: 54.0 },
-                start_position,
-                tessera_size,
-                gap_size
-            ),
-            PositionOnGrid { row: 5, column: 8 }
+            position_at(15.0, 5.0, 1.0),
+            Some(PositionOnGrid { row: 1, column: 2 })
         );
+        assert_eq!(
+            position_at(87.0, 54.0, 1.0),
+            Some(PositionOnGrid { row: 5, column: 8 })
+        );
+        assert_eq!(
+            position_at(174.0, 108.0, 2.0),
+            Some(PositionOnGrid { row: 5, column: 8 })
+        );
+        assert_eq!(
+            position_at(43.5, 27.0, 0.5),
+            Some(PositionOnGrid { row: 5, column: 8 })
+        );
+        assert_eq!(position_at(200.0, 5.0, 1.0), None);
+        assert_eq!(position_at(5.0, 200.0, 1.0), None);
+    }
 
-        let start = [987, 789];
-        let column = 354;
-        let x = start[0] + column * tessera_size[0] + (column - 1) * gap_size;
-        let row = 2451;
-        let y = start[1] + row * tessera_size[1] + (row - 1) * gap_size;
+    #[test]
+    fn the_rectangle_on_screen_of_a_tessera_contains_the_positions_that_select_it() {
+        let start_position = egui::Pos2 { x: 30.0, y: 40.0 };
+        let mosaic =
+            a_mosaic_with_rows_of_tesserae_of_sizes(&[(40, [10, 20]), (20, [25, 5]), (3, [7, 9])]);
+        let gap_size = 2;
+        for zoom_factor in [0.5, 1.0, 3.0] {
+            for (row, column, size) in [
+                (1, 1, [10, 20]),
+                (1, 33, [10, 20]),
+                (2, 7, [25, 5]),
+                (3, 3, [7, 9]),
+            ] {
+                let position = PositionOnGrid { row, column };
+                let rectangle = get_tessera_rectangle_on_screen(
+                    &position,
+                    start_position,
+                    zoom_factor,
+                    &mosaic,
+                    gap_size,
+                )
+                .unwrap();
+                assert_eq!(rectangle.width(), size[0] as f32 * zoom_factor);
+                assert_eq!(rectangle.height(), size[1] as f32 * zoom_factor);
+                assert_eq!(
+                    get_tessera_position(
+                        rectangle.center(),
+                        start_position,
+                        zoom_factor,
+                        &mosaic,
+                        gap_size
+                    ),
+                    Some(position)
+                );
+            }
+        }
         assert_eq!(
-            get_tessera_position(
-                egui::Pos2 {
-                    x: x as f32,
-                    y: y as f32
-                },
-                egui::Pos2 {
-                    x: start[0] as f32,
-                    y: start[1] as f32
-                },
-                tessera_size,
+            get_tessera_rectangle_on_screen(
+                &PositionOnGrid { row: 3, column: 4 },
+                start_position,
+                1.0,
+                &mosaic,
                 gap_size
             ),
-            PositionOnGrid { row, column }
+            None
         );
     }
 }
@@ -2,26 +2,114 @@ use eframe::egui;
 use egui_file_dialog::FileDialog;
 use palette::convert::FromColor;
 
-use crate::{
-    config,
-    mosaic::{Mosaic, RectangleInMm, Tessera},
-    utils,
+use mosaicneitor_core::{
+    andamento::{self, AndamentoSettings, GuideCurve},
+    colors,
+    dithering::DitheringSettings,
+    export_cartoon, export_chart, export_png, export_svg,
+    generator::{self, GenerationSettings, LayoutPattern},
+    history::{EditHistory, MosaicEdit, TesseraChange},
+    materials::{MaterialsReport, MaterialsSettings},
+    mosaic::{BaseImage, Mosaic, PositionOnGrid, RectangleInMm, Tessera},
+    preprocessing::{self, ImageOperation},
+    project_file::{self, ProjectFile, ViewSettings},
+    quantization::ProposedPalette,
+    region::{self, GridRectangle, RegionTransform, TesseraRegion},
+    sampling::{self, PixelArea, SamplingMode},
+    selection,
+    tessera_shape::TesseraShape,
+    tile_palette::TilePalette,
 };
 
+use crate::{config, settings::UserSettings, user_interface_canvas::CanvasCache};
+
 pub(crate) struct MosaicneitorApp {
     pub(crate) file_dialog: FileDialog,
+    pub(crate) project_file_dialog: FileDialog,
+    pub(crate) export_file_dialog: FileDialog,
     pub(crate) selected_file: Option<std::path::PathBuf>,
-    loaded_image: Option<image::Rgba32FImage>,
+    // As in the file. The loaded image and the image on the canvas have the image operations applied.
+    pub(crate) original_image: Option<image::Rgba32FImage>,
+    pub(crate) image_operations: Vec<ImageOperation>,
+    pub(crate) show_image_adjustments: bool,
+    loaded_image: Option<BaseImage>,
     pub(crate) image: Option<egui::ColorImage>,
+    pub(crate) image_texture: Option<egui::TextureHandle>,
     pub(crate) mosaic: Mosaic,
+    pub(crate) history: EditHistory,
+    // Goes up with every change to what is painted on the canvas, so the cached shapes are rebuilt.
+    pub(crate) canvas_revision: u64,
+    pub(crate) canvas_cache: CanvasCache,
     pub(crate) mosaic_dimension_h: String,
     pub(crate) mosaic_dimension_v: String,
     pub(crate) tessera_size_h: String,
     pub(crate) tessera_size_v: String,
-    pub(crate) zoom_level: Zoom,
+    // Screen points per mm of the mosaic.
+    zoom_factor: f32,
+    // To apply on the next frame, after zooming.
+    pub(crate) pending_scroll_offset: Option<egui::Vec2>,
+    // Space for the canvas in the last frame, to fit the mosaic in it.
+    pub(crate) canvas_size: egui::Vec2,
     pub(crate) show_image: bool,
     pub(crate) show_tesserae_grid: bool,
     pub(crate) show_actual_tesserae: bool,
+    pub(crate) sampling_mode: SamplingMode,
+    pub(crate) pattern: LayoutPattern,
+    pub(crate) dithering: DitheringSettings,
+    pub(crate) comparing_dithering: bool,
+    // From 0 (left) to 1 (right) of the mosaic.
+    pub(crate) comparison_split: f32,
+    pub(crate) colors_without_dithering: Vec<palette::Oklch>,
+    pub(crate) settings: UserSettings,
+    pub(crate) restrict_to_palette: bool,
+    pub(crate) fit_to_edges: bool,
+    pub(crate) andamento: AndamentoSettings,
+    pub(crate) guide_curves: Vec<GuideCurve>,
+    pub(crate) drawing_guide_curves: bool,
+    pub(crate) show_palette_editor: bool,
+    pub(crate) show_materials_report: bool,
+    // With the canvas revision, materials settings and tile palette it was made for.
+    materials_report: Option<(
+        u64,
+        MaterialsSettings,
+        TilePalette,
+        std::sync::Arc<MaterialsReport>,
+    )>,
+    pub(crate) show_proposed_palette: bool,
+    pub(crate) proposed_palette: ProposedPalette,
+    pub(crate) number_of_proposed_colors: usize,
+    pub(crate) clusters_to_merge: std::collections::BTreeSet<usize>,
+    pub(crate) selected_tessera: Option<PositionOnGrid>,
+    pub(crate) color_to_apply: palette::Oklch,
+    pub(crate) size_to_apply: RectangleInMm,
+    pub(crate) shape_to_apply: TesseraShape,
+    pub(crate) custom_shape_text: String,
+    pub(crate) eyedropper_active: bool,
+    pub(crate) tool: CanvasTool,
+    pub(crate) brush_radius: usize,
+    // Oklab distance.
+    pub(crate) fill_tolerance: f32,
+    // What the brush has gone over in the current stroke.
+    pub(crate) brush_stroke: std::collections::BTreeSet<PositionOnGrid>,
+    // Corners of the rectangle, or points of the lasso, in mm.
+    pub(crate) tool_path: Vec<[f32; 2]>,
+    pub(crate) selected_region: Option<GridRectangle>,
+    // Where a drag that moves the selected region started.
+    pub(crate) moving_region_from: Option<PositionOnGrid>,
+    // Not cleared when another project is opened, to take motifs from one to another.
+    pub(crate) clipboard: Option<TesseraRegion>,
+    pub(crate) show_color_replacement: bool,
+    pub(crate) replacement_source: Option<palette::Oklch>,
+    pub(crate) replacement_target: Option<palette::Oklch>,
+    // Oklab distance.
+    pub(crate) replacement_tolerance: f32,
+    // The next click on the canvas takes the color of the tessera as the source.
+    pub(crate) picking_replacement_source: bool,
+    // Indices in the order of Mosaic::get_all_tesserae, highlighted on the canvas.
+    pub(crate) tesserae_to_replace: Vec<usize>,
+    // Canvas revision, source and tolerance they were found for.
+    tesserae_to_replace_for: Option<(u64, palette::Oklch, f32)>,
+    pub(crate) status_message: String,
 }
 
 impl Default for MosaicneitorApp {
@@ -40,9 +128,32 @@ impl Default for MosaicneitorApp {
                     std::sync::Arc::new(|path| path.extension().unwrap_or_default() == "jpg"),
                 )
                 .default_file_filter("JPEG"),
+            project_file_dialog: FileDialog::new()
+                .default_pos([20.0, 30.0])
+                .initial_directory(crate::config::default_working_folder())
+                .default_file_name(&format!(
+                    "{}{}",
+                    t!("default_project_name"),
+                    project_file::PROJECT_FILE_EXTENSION
+                ))
+                .add_file_filter(
+                    "Mosaicneitor",
+                    std::sync::Arc::new(|path| {
+                        path.to_string_lossy()
+                            .ends_with(project_file::PROJECT_FILE_EXTENSION)
+                    }),
+                )
+                .default_file_filter("Mosaicneitor"),
+            export_file_dialog: FileDialog::new()
+                .default_pos([20.0, 30.0])
+                .initial_directory(crate::config::default_working_folder()),
             selected_file: None,
+            original_image: None,
+            image_operations: Vec::new(),
+            show_image_adjustments: false,
             loaded_image: None,
             image: None,
+            image_texture: None,
             mosaic: Mosaic::new(
                 None,
                 RectangleInMm {
@@ -50,25 +161,89 @@ impl Default for MosaicneitorApp {
                     vertical: config::DEFAULT_BASE_TESSERA_SIZE_VERTICAL_MM,
                 },
             ),
+            history: EditHistory::new(Some(config::DEFAULT_MAX_UNDO_STEPS)),
+            canvas_revision: 0,
+            canvas_cache: CanvasCache::default(),
             mosaic_dimension_h: config::DEFAULT_OVERAL_MOSAIC_DIMENSIONS_HORIZONTAL_MM.to_string(),
             mosaic_dimension_v: config::DEFAULT_OVERAL_MOSAIC_DIMENSIONS_VERTICAL_MM.to_string(),
             tessera_size_h: config::DEFAULT_BASE_TESSERA_SIZE_HORIZONTAL_MM.to_string(),
             tessera_size_v: config::DEFAULT_BASE_TESSERA_SIZE_VERTICAL_MM.to_string(),
-            zoom_level: Zoom::X1,
+            zoom_factor: 1.0,
+            pending_scroll_offset: None,
+            canvas_size: egui::Vec2::ZERO,
             show_image: false,
             show_tesserae_grid: true,
             show_actual_tesserae: true,
+            sampling_mode: SamplingMode::Average,
+            pattern: LayoutPattern::Regulatum,
+            dithering: DitheringSettings::default(),
+            comparing_dithering: false,
+            comparison_split: 0.5,
+            colors_without_dithering: Vec::new(),
+            settings: UserSettings::default(),
+            restrict_to_palette: false,
+            fit_to_edges: false,
+            andamento: AndamentoSettings::default(),
+            guide_curves: Vec::new(),
+            drawing_guide_curves: false,
+            show_palette_editor: false,
+            show_materials_report: false,
+            materials_report: None,
+            show_proposed_palette: false,
+            proposed_palette: ProposedPalette::default(),
+            number_of_proposed_colors: 12,
+            clusters_to_merge: std::collections::BTreeSet::new(),
+            selected_tessera: None,
+            color_to_apply: palette::Oklch::new(1.0, 0.0, 0.0),
+            size_to_apply: RectangleInMm {
+                horizontal: config::DEFAULT_BASE_TESSERA_SIZE_HORIZONTAL_MM,
+                vertical: config::DEFAULT_BASE_TESSERA_SIZE_VERTICAL_MM,
+            },
+            shape_to_apply: TesseraShape::Whole,
+            custom_shape_text: String::new(),
+            eyedropper_active: false,
+            tool: CanvasTool::Select,
+            brush_radius: 0,
+            fill_tolerance: 0.05,
+            brush_stroke: std::collections::BTreeSet::new(),
+            tool_path: Vec::new(),
+            selected_region: None,
+            moving_region_from: None,
+            clipboard: None,
+            show_color_replacement: false,
+            replacement_source: None,
+            replacement_target: None,
+            replacement_tolerance: 0.05,
+            picking_replacement_source: false,
+            tesserae_to_replace: Vec::new(),
+            tesserae_to_replace_for: None,
+            status_message: String::new(),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
-pub(crate) enum Zoom {
-    X1,
-    X2,
-    X3,
-    X4,
-    X5,
+// What clicking and dragging on the canvas does.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum CanvasTool {
+    // A tessera to edit, right click paints it.
+    Select,
+    Brush,
+    FloodFill,
+    Rectangle,
+    Lasso,
+    // A rectangle of the grid to copy, paste, move or turn.
+    Region,
+}
+
+impl CanvasTool {
+    pub(crate) const ALL: [CanvasTool; 6] = [
+        CanvasTool::Select,
+        CanvasTool::Brush,
+        CanvasTool::FloodFill,
+        CanvasTool::Rectangle,
+        CanvasTool::Lasso,
+        CanvasTool::Region,
+    ];
 }
 
 impl MosaicneitorApp {
@@ -80,10 +255,32 @@ impl MosaicneitorApp {
         // This is also where you can customize the look and feel of egui using
         // `cc.egui_ctx.set_visuals` and `cc.egui_ctx.set_fonts`.
 
-        Default::default()
+        let mut app = Self::default();
+        if config::settings_file().exists() {
+            match UserSettings::load_from_file(&config::settings_file()) {
+                Ok(settings) => {
+                    let max_undo_steps = settings.max_undo_steps;
+                    app.settings = settings;
+                    app.set_max_undo_steps(max_undo_steps);
+                }
+                Err(e) => app.status_message = e,
+            }
+        }
+        app
+    }
+
+    pub(crate) fn save_settings(&mut self) {
+        self.status_message = match self.settings.save_to_file(&config::settings_file()) {
+            Ok(_) => t!("settings_saved").to_string(),
+            Err(e) => e,
+        };
     }
 
     pub(crate) fn load_image_from_selected_file(&mut self) {
+        self.original_image = None;
+        self.image_texture = None;
+        self.image_operations.clear();
+        self.loaded_image = None;
         match &self.selected_file {
             None => self.image = None,
             Some(path) => {
@@ -95,24 +292,23 @@ impl MosaicneitorApp {
                         match decoded_image {
                             Err(_) => self.image = None,
                             Ok(img) => {
-                                self.loaded_image = Some(img.to_rgba32f());
-                                let buffered_image = img.to_rgb8();
-                                let pixels = buffered_image.as_flat_samples();
-                                let egui_color_image = egui::ColorImage::from_rgb(
-                                    [img.width() as usize, img.height() as usize],
-                                    pixels.as_slice(),
-                                );
-                                self.image = Some(egui_color_image);
+                                self.original_image = Some(img.to_rgba32f());
+                                self.update_processed_image();
                                 self.adjust_mosaic_dimensions_to_image_aspect_ratio();
                                 self.show_image = true;
                                 self.show_tesserae_grid = true;
+                                // A new start, as when opening a project: not an edit to undo.
                                 self.mosaic = Mosaic::new(
-                                    Some(img.to_rgba32f()),
+                                    self.loaded_image.clone(),
                                     RectangleInMm {
                                         horizontal: self.get_tessera_size()[0],
                                         vertical: self.get_tessera_size()[1],
                                     },
                                 );
+                                self.forget_colors_without_dithering();
+                                self.invalidate_canvas();
+                                self.history.clear();
+                                self.selected_tessera = None;
                             }
                         }
                     }
@@ -121,8 +317,28 @@ impl MosaicneitorApp {
         }
     }
 
+    // The operations can change the aspect ratio, the mosaic follows. Generate again to see them.
+    pub(crate) fn apply_image_operations(&mut self) {
+        self.update_processed_image();
+        self.adjust_mosaic_dimensions_to_image_aspect_ratio();
+    }
+
+    fn update_processed_image(&mut self) {
+        self.image_texture = None;
+        self.loaded_image = self.original_image.as_ref().map(|img| {
+            BaseImage::new(preprocessing::apply_operations(img, &self.image_operations))
+        });
+        self.image = self.loaded_image.as_ref().map(|img| {
+            let buffered_image = image::DynamicImage::ImageRgba32F(img.as_ref().clone()).to_rgb8();
+            egui::ColorImage::from_rgb(
+                [img.width() as usize, img.height() as usize],
+                buffered_image.as_flat_samples().as_slice(),
+            )
+        });
+    }
+
     pub(crate) fn adjust_mosaic_dimensions_to_image_aspect_ratio(&mut self) {
-        let adjusted_dimensions = utils::round_preserving_aspect_ratio(
+        let adjusted_dimensions = generator::round_preserving_aspect_ratio(
             self.get_mosaic_dimensions(),
             self.get_image_dimensions(),
         );
@@ -139,148 +355,1214 @@ impl MosaicneitorApp {
 
     pub fn get_mosaic_dimensions(&self) -> [usize; 2] {
         [
-            match self.mosaic_dimension_h.parse::<usize>() {
-                Ok(dimension) => dimension,
-                Err(_error) => 1,
-            },
-            match self.mosaic_dimension_v.parse::<usize>() {
-                Ok(dimension) => dimension,
-                Err(_error) => 1,
-            },
+            self.mosaic_dimension_h.parse::<usize>().unwrap_or(1),
+            self.mosaic_dimension_v.parse::<usize>().unwrap_or(1),
         ]
     }
 
+    // Tesserae of no size cannot be laid, so 0 is taken as 1 too.
     pub fn get_tessera_size(&self) -> [usize; 2] {
-        [
-            match self.tessera_size_h.parse::<usize>() {
-                Ok(size) => size,
-                Err(_error) => 1,
-            },
-            match self.tessera_size_v.parse::<usize>() {
-                Ok(size) => size,
-                Err(_error) => 1,
+        [&self.tessera_size_h, &self.tessera_size_v].map(|text| {
+            text.parse::<usize>()
+                .ok()
+                .filter(|size| *size > 0)
+                .unwrap_or(1)
+        })
+    }
+
+    pub fn get_zoom_factor(&self) -> f32 {
+        self.zoom_factor
+    }
+
+    pub(crate) fn set_zoom_factor(&mut self, zoom_factor: f32) {
+        self.zoom_factor = if zoom_factor.is_finite() {
+            zoom_factor.clamp(config::MIN_ZOOM_FACTOR, config::MAX_ZOOM_FACTOR)
+        } else {
+            1.0
+        };
+    }
+
+    // The point of the mosaic under the pointer stays under it. The scroll offset is the current one.
+    pub(crate) fn zoom_around(
+        &mut self,
+        factor: f32,
+        pointer: egui::Pos2,
+        start_position: egui::Pos2,
+        scroll_offset: egui::Vec2,
+    ) {
+        let previous_zoom_factor = self.zoom_factor;
+        let point_in_mm = (pointer - start_position) / previous_zoom_factor;
+        self.set_zoom_factor(previous_zoom_factor * factor);
+        self.pending_scroll_offset =
+            Some(scroll_offset + point_in_mm * (self.zoom_factor - previous_zoom_factor));
+    }
+
+    // The whole mosaic in the space given, in screen points.
+    pub(crate) fn fit_zoom_to(&mut self, available_size: egui::Vec2) {
+        let mosaic_dimensions = self.get_mosaic_dimensions();
+        // The canvas starts a point in, and a bit of room keeps the scroll bars away.
+        let margin = 4.0;
+        self.set_zoom_factor(f32::min(
+            (available_size.x - margin) / mosaic_dimensions[0] as f32,
+            (available_size.y - margin) / mosaic_dimensions[1] as f32,
+        ));
+        self.pending_scroll_offset = Some(egui::Vec2::ZERO);
+    }
+
+    // A mm of the mosaic is a mm on the screen, as far as the measured screen resolution,
+    // or else the operating system scale, tells.
+    pub(crate) fn set_real_size_zoom(
+        &mut self,
+        native_pixels_per_point: f32,
+        pixels_per_point: f32,
+    ) {
+        let screen_dpi = self
+            .settings
+            .screen_dpi
+            .unwrap_or(native_pixels_per_point * config::REFERENCE_DPI);
+        self.set_zoom_factor(screen_dpi / config::MM_PER_INCH / pixels_per_point);
+    }
+
+    pub(crate) fn save_project(&mut self, path: std::path::PathBuf) {
+        let path = project_file::add_extension_if_missing(path);
+        let mut project = ProjectFile::new(
+            &self.mosaic,
+            self.selected_file.clone(),
+            self.get_mosaic_dimensions(),
+            ViewSettings {
+                show_image: self.show_image,
+                show_tesserae_grid: self.show_tesserae_grid,
+                show_actual_tesserae: self.show_actual_tesserae,
+                zoom_factor: self.get_zoom_factor(),
             },
-        ]
+        );
+        project.guide_curves = self.guide_curves.clone();
+        project.image_operations = self.image_operations.clone();
+        project.base_image_size_px = self
+            .original_image
+            .as_ref()
+            .map(|img| [img.width(), img.height()]);
+        self.status_message = match project.save_to_file(&path) {
+            Ok(_) => format!("{}: {}", t!("project_saved"), path.display()),
+            Err(e) => e,
+        };
     }
 
-    pub fn get_zoom_factor(&self) -> usize {
-        match self.zoom_level {
-            Zoom::X1 => 1,
-            Zoom::X2 => 2,
-            Zoom::X3 => 3,
-            Zoom::X4 => 4,
-            Zoom::X5 => 5,
+    pub(crate) fn open_project(&mut self, path: std::path::PathBuf) {
+        let project = match ProjectFile::load_from_file(&path) {
+            Ok(project) => project,
+            Err(e) => {
+                self.status_message = e;
+                return;
+            }
+        };
+        self.status_message = format!("{}: {}", t!("project_opened"), path.display());
+        self.selected_file = project.base_image_path.clone();
+        self.load_image_from_selected_file();
+        self.image_operations = project.image_operations.clone();
+        self.update_processed_image();
+        if let (Some(image_path), None) = (&project.base_image_path, &self.loaded_image) {
+            self.status_message =
+                format!("{}: {}", t!("base_image_not_found"), image_path.display());
+        }
+        if let (Some(expected), Some(img)) = (project.base_image_size_px, &self.original_image) {
+            if expected != [img.width(), img.height()] {
+                self.status_message = t!("base_image_has_changed").to_string();
+            }
         }
+        self.mosaic_dimension_h = project.mosaic_dimensions_mm[0].to_string();
+        self.mosaic_dimension_v = project.mosaic_dimensions_mm[1].to_string();
+        self.tessera_size_h = project.general_tessera_size_mm[0].to_string();
+        self.tessera_size_v = project.general_tessera_size_mm[1].to_string();
+        self.mosaic = project.to_mosaic(self.loaded_image.clone());
+        self.forget_colors_without_dithering();
+        self.invalidate_canvas();
+        self.guide_curves = project.guide_curves.clone();
+        self.history.clear();
+        self.selected_tessera = None;
+        self.show_image = project.view.show_image;
+        self.show_tesserae_grid = project.view.show_tesserae_grid;
+        self.show_actual_tesserae = project.view.show_actual_tesserae;
+        self.set_zoom_factor(project.view.zoom_factor);
     }
 
-    pub fn get_a_blank_mosaic_with_all_tesserae_equal_color(
-        &self,
-        choosen_color: egui::Color32,
-    ) -> Mosaic {
-        let general_tessera_size = RectangleInMm {
-            horizontal: self.get_tessera_size()[0],
-            vertical: self.get_tessera_size()[1],
+    pub(crate) fn open_export_dialog(&mut self, operation_id: &str, default_file_name: &str) {
+        self.export_file_dialog.config_mut().default_file_name = String::from(default_file_name);
+        let _ = self.export_file_dialog.open(
+            egui_file_dialog::DialogMode::SaveFile,
+            true,
+            Some(operation_id),
+        );
+    }
+
+    pub(crate) fn export_png(&mut self, path: std::path::PathBuf) {
+        self.status_message = match export_png::export_mosaic_to_png(
+            &self.mosaic,
+            config::DEFAULT_GAP_BETWEEN_TESSSELAE,
+            &self.settings.render,
+            &path,
+        ) {
+            Ok(_) => format!("{}: {}", t!("file_exported"), path.display()),
+            Err(e) => e,
         };
-        let mosaic_size = RectangleInMm {
-            horizontal: self.get_mosaic_dimensions()[0],
-            vertical: self.get_mosaic_dimensions()[1],
+    }
+
+    pub(crate) fn export_svg(&mut self, path: std::path::PathBuf) {
+        self.status_message = match export_svg::export_mosaic_to_svg(
+            &self.mosaic,
+            config::DEFAULT_GAP_BETWEEN_TESSSELAE,
+            &self.settings.tile_palette,
+            self.settings.render.grout_srgb,
+            &path,
+        ) {
+            Ok(_) => format!("{}: {}", t!("file_exported"), path.display()),
+            Err(e) => e,
         };
-        let color_srgba: palette::Srgba<f32> =
-            palette::Srgba::from(choosen_color.to_srgba_unmultiplied()).into();
-        let color_oklch = palette::Oklch::from_color(color_srgba);
-        let mut mosaic = Mosaic::new(None, general_tessera_size);
-        for _vertical_position in (1..mosaic_size.vertical)
-            .step_by(general_tessera_size.vertical + config::DEFAULT_GAP_BETWEEN_TESSSELAE)
-        {
-            let mut row: Vec<Tessera> = Vec::new();
-            for _horizontal_position in (1..mosaic_size.horizontal)
-                .step_by(general_tessera_size.horizontal + config::DEFAULT_GAP_BETWEEN_TESSSELAE)
+    }
+
+    pub(crate) fn export_chart(&mut self, path: std::path::PathBuf) {
+        self.status_message = match export_chart::export_chart(
+            &self.mosaic,
+            &self.settings.tile_palette,
+            &self.settings.chart,
+            &path,
+        ) {
+            Ok(_) => format!("{}: {}", t!("file_exported"), path.display()),
+            Err(e) => e,
+        };
+    }
+
+    pub(crate) fn export_cartoon(&mut self, path: std::path::PathBuf) {
+        self.status_message = match export_cartoon::export_cartoon(
+            &self.mosaic,
+            config::DEFAULT_GAP_BETWEEN_TESSSELAE,
+            &self.settings.tile_palette,
+            &self.settings.cartoon,
+            &path,
+        ) {
+            Ok(pages) => format!(
+                "{}: {} ({}...)",
+                t!("cartoon_exported"),
+                pages.len(),
+                export_cartoon::get_page_file_path(&path, 1, 1).display()
+            ),
+            Err(e) => e,
+        };
+    }
+
+    // Made again only when the mosaic, the materials settings or the tile palette change.
+    pub(crate) fn get_materials_report(&mut self) -> std::sync::Arc<MaterialsReport> {
+        match &self.materials_report {
+            Some((revision, settings, tile_palette, report))
+                if *revision == self.canvas_revision
+                    && *settings == self.settings.materials
+                    && *tile_palette == self.settings.tile_palette =>
             {
-                row.push(Tessera { color: color_oklch });
+                report.clone()
+            }
+            _ => {
+                let report = std::sync::Arc::new(MaterialsReport::new(
+                    &self.mosaic,
+                    &self.settings.tile_palette,
+                    &self.settings.materials,
+                ));
+                self.materials_report = Some((
+                    self.canvas_revision,
+                    self.settings.materials,
+                    self.settings.tile_palette.clone(),
+                    report.clone(),
+                ));
+                report
             }
-            mosaic.add_a_row_of_tesserae(row);
         }
-        mosaic
     }
 
-    pub fn get_mosaic_from_loaded_image(&self) -> Mosaic {
-        let tessera_size = RectangleInMm {
-            horizontal: self.get_tessera_size()[0],
-            vertical: self.get_tessera_size()[1],
+    pub(crate) fn export_materials_report(&mut self, path: std::path::PathBuf) {
+        self.status_message = match self.get_materials_report().save_csv_to_file(&path) {
+            Ok(_) => format!("{}: {}", t!("file_exported"), path.display()),
+            Err(e) => e,
         };
-        let mosaic_size = RectangleInMm {
-            horizontal: self.get_mosaic_dimensions()[0],
-            vertical: self.get_mosaic_dimensions()[1],
+    }
+
+    pub(crate) fn apply_color_to_tessera(&mut self, position: &PositionOnGrid) {
+        let tessera = match self.mosaic.get_tessera(position) {
+            Some(tessera) => Tessera {
+                color: self.color_to_apply,
+                ..tessera.clone()
+            },
+            None => Tessera {
+                color: self.color_to_apply,
+                size: *self.mosaic.get_general_tessera_size(),
+                shape: TesseraShape::Whole,
+            },
         };
-        let mut mosaic = Mosaic::new(self.loaded_image.clone(), tessera_size);
-        for vertical_position in (1..mosaic_size.vertical)
-            .step_by(tessera_size.vertical + config::DEFAULT_GAP_BETWEEN_TESSSELAE)
-        {
-            let mut row: Vec<Tessera> = Vec::new();
-            for horizontal_position in (1..mosaic_size.horizontal)
-                .step_by(tessera_size.horizontal + config::DEFAULT_GAP_BETWEEN_TESSSELAE)
-            {
-                let sample_point = [
-                    horizontal_position + tessera_size.horizontal / 2,
-                    vertical_position + tessera_size.horizontal / 2,
-                ];
-                let color_srgba: palette::Srgba<f32> = match self
-                    .get_pixel_color(self.get_pixel_position_on_image(sample_point))
-                {
-                    Ok(color) => palette::Srgba::from(color.to_srgba_unmultiplied()).into(),
-                    Err(_) => {
-                        palette::Srgba::from(egui::Color32::YELLOW.to_srgba_unmultiplied()).into()
-                    }
-                };
-                let color_oklch = palette::Oklch::from_color(color_srgba);
-                row.push(Tessera { color: color_oklch });
+        self.apply_edit(MosaicEdit::ChangeTesserae(vec![TesseraChange {
+            position: *position,
+            tessera,
+        }]));
+    }
+
+    // Keeps the colors, only the sizes change. Cut shapes are stretched to the new size.
+    pub(crate) fn apply_size_to_tesserae(&mut self, positions: &[PositionOnGrid]) {
+        let changes = positions
+            .iter()
+            .filter_map(|position| {
+                self.mosaic
+                    .get_tessera(position)
+                    .map(|tessera| TesseraChange {
+                        position: *position,
+                        tessera: Tessera {
+                            color: tessera.color,
+                            size: self.size_to_apply,
+                            shape: tessera.shape.resized(&tessera.size, &self.size_to_apply),
+                        },
+                    })
+            })
+            .collect();
+        self.apply_edit(MosaicEdit::ChangeTesserae(changes));
+    }
+
+    pub(crate) fn apply_size_to_row(&mut self, row: usize) {
+        let positions: Vec<PositionOnGrid> = (1..=self
+            .mosaic
+            .get_number_of_tesserae_in_row(row.saturating_sub(1)))
+            .map(|column| PositionOnGrid { row, column })
+            .collect();
+        self.apply_size_to_tesserae(&positions);
+    }
+
+    pub(crate) fn apply_shape_to_tessera(&mut self, position: &PositionOnGrid) {
+        if let Some(tessera) = self.mosaic.get_tessera(position) {
+            let tessera = Tessera {
+                shape: self.shape_to_apply.clone(),
+                ..tessera.clone()
+            };
+            self.apply_edit(MosaicEdit::ChangeTesserae(vec![TesseraChange {
+                position: *position,
+                tessera,
+            }]));
+        }
+    }
+
+    // The custom polygon is measured on the selected tessera.
+    pub(crate) fn set_custom_shape_to_apply(&mut self, position: &PositionOnGrid) {
+        let size = match self.mosaic.get_tessera(position) {
+            Some(tessera) => tessera.size,
+            None => return,
+        };
+        match TesseraShape::polygon_from_text(&self.custom_shape_text, &size) {
+            Ok(shape) => self.shape_to_apply = shape,
+            Err(e) => self.status_message = e,
+        }
+    }
+
+    // Points closer than this to the previous one are not kept, they only add noise.
+    pub(crate) fn add_point_to_last_guide_curve(&mut self, point_in_mm: [f32; 2]) {
+        if let Some(curve) = self.guide_curves.last_mut() {
+            let is_far_enough = curve.points.last().is_none_or(|last| {
+                (point_in_mm[0] - last[0]).hypot(point_in_mm[1] - last[1])
+                    >= config::MIN_DISTANCE_BETWEEN_GUIDE_CURVE_POINTS
+            });
+            if is_far_enough {
+                curve.points.push(point_in_mm);
             }
-            mosaic.add_a_row_of_tesserae(row);
         }
-        mosaic
     }
 
-    fn get_pixel_position_on_image(&self, point_position_on_mosaic: [usize; 2]) -> [usize; 2] {
-        let mosaic_dimensions = self.get_mosaic_dimensions();
-        let image_dimensions = self.get_image_dimensions();
-        [
-            image_dimensions[0] * point_position_on_mosaic[0] / mosaic_dimensions[0],
-            image_dimensions[1] * point_position_on_mosaic[1] / mosaic_dimensions[1],
-        ]
+    // The tesserae keep their size and shape. All of them are one edit, undone at once.
+    pub(crate) fn paint_tesserae(&mut self, positions: &[PositionOnGrid]) {
+        let changes: Vec<TesseraChange> = positions
+            .iter()
+            .filter_map(|position| {
+                self.mosaic
+                    .get_tessera(position)
+                    .map(|tessera| TesseraChange {
+                        position: *position,
+                        tessera: Tessera {
+                            color: self.color_to_apply,
+                            ..tessera.clone()
+                        },
+                    })
+            })
+            .collect();
+        if !changes.is_empty() {
+            self.apply_edit(MosaicEdit::ChangeTesserae(changes));
+        }
     }
 
-    fn get_pixel_color(
-        &self,
-        pixel_position_on_image: [usize; 2],
-    ) -> Result<egui::Color32, String> {
-        match &self.image {
-            Some(img) => {
-                let avance_rows_for_vertical_pixel =
-                    pixel_position_on_image[1] * self.get_image_dimensions()[0];
-                let avance_for_horizontal_pixel = pixel_position_on_image[0];
-                match img
-                    .pixels
-                    .get(avance_rows_for_vertical_pixel + avance_for_horizontal_pixel)
+    pub(crate) fn flood_fill_at(&mut self, point_in_mm: [f32; 2]) {
+        let positions = selection::get_positions_to_flood_fill(
+            &self.mosaic,
+            point_in_mm,
+            self.fill_tolerance,
+            config::DEFAULT_GAP_BETWEEN_TESSSELAE,
+        );
+        self.paint_tesserae(&positions);
+    }
+
+    // While dragging the brush, the rectangle or the lasso.
+    pub(crate) fn add_point_to_tool_stroke(&mut self, point_in_mm: [f32; 2]) {
+        match self.tool {
+            CanvasTool::Brush => self.brush_stroke.extend(selection::get_positions_in_brush(
+                &self.mosaic,
+                point_in_mm,
+                self.brush_radius,
+                config::DEFAULT_GAP_BETWEEN_TESSSELAE,
+            )),
+            CanvasTool::Rectangle => {
+                self.tool_path.truncate(1);
+                self.tool_path.push(point_in_mm);
+            }
+            CanvasTool::Region => {
+                if self.tool_path.is_empty() {
+                    // Dragging from inside the selected region moves it.
+                    self.moving_region_from = self
+                        .mosaic
+                        .get_position_at_point(point_in_mm, config::DEFAULT_GAP_BETWEEN_TESSSELAE)
+                        .filter(|position| {
+                            self.selected_region
+                                .is_some_and(|region| region.contains(position))
+                        });
+                }
+                self.tool_path.truncate(1);
+                self.tool_path.push(point_in_mm);
+            }
+            CanvasTool::Lasso => {
+                let is_far_enough = self.tool_path.last().is_none_or(|last| {
+                    (point_in_mm[0] - last[0]).hypot(point_in_mm[1] - last[1])
+                        >= config::MIN_DISTANCE_BETWEEN_LASSO_POINTS
+                });
+                if is_far_enough {
+                    self.tool_path.push(point_in_mm);
+                }
+            }
+            CanvasTool::Select | CanvasTool::FloodFill => (),
+        }
+    }
+
+    // The tesserae the stroke went over, or inside it, get the color to apply.
+    pub(crate) fn finish_tool_stroke(&mut self) {
+        let gap_between_tesserae = config::DEFAULT_GAP_BETWEEN_TESSSELAE;
+        let positions: Vec<PositionOnGrid> = match (self.tool, self.tool_path.as_slice()) {
+            (CanvasTool::Brush, _) => self.brush_stroke.iter().copied().collect(),
+            (CanvasTool::Rectangle, [corner, .., opposite_corner]) => {
+                selection::get_positions_in_rectangle(
+                    &self.mosaic,
+                    *corner,
+                    *opposite_corner,
+                    gap_between_tesserae,
+                )
+            }
+            (CanvasTool::Lasso, polygon) => {
+                selection::get_positions_in_lasso(&self.mosaic, polygon, gap_between_tesserae)
+            }
+            (CanvasTool::Region, path) => {
+                self.finish_region_stroke(path.to_vec());
+                Vec::new()
+            }
+            _ => Vec::new(),
+        };
+        self.paint_tesserae(&positions);
+        self.brush_stroke.clear();
+        self.tool_path.clear();
+    }
+
+    // A click out of the selected region unselects it.
+    fn finish_region_stroke(&mut self, path: Vec<[f32; 2]>) {
+        let gap_between_tesserae = config::DEFAULT_GAP_BETWEEN_TESSSELAE;
+        let moving_from = self.moving_region_from.take();
+        match (moving_from, path.as_slice()) {
+            (Some(from), [_, .., end]) => {
+                if let Some(to) = self
+                    .mosaic
+                    .get_position_at_point(*end, gap_between_tesserae)
                 {
-                    Some(color) => Ok(*color),
-                    None => Err(String::from("no pixel")),
+                    self.move_region(
+                        to.row as isize - from.row as isize,
+                        to.column as isize - from.column as isize,
+                    );
                 }
             }
+            (Some(_from), _) => (),
+            (None, [corner, .., opposite_corner]) => {
+                self.selected_region =
+                    GridRectangle::around(&selection::get_positions_in_rectangle(
+                        &self.mosaic,
+                        *corner,
+                        *opposite_corner,
+                        gap_between_tesserae,
+                    ));
+            }
+            (None, _) => self.selected_region = None,
+        }
+    }
+
+    pub(crate) fn copy_region(&mut self) {
+        if let Some(rectangle) = self.selected_region {
+            self.clipboard = Some(TesseraRegion::copy_from(&self.mosaic, &rectangle));
+        }
+    }
+
+    // The tesserae left behind get the grout color, as if they were taken away.
+    pub(crate) fn cut_region(&mut self) {
+        if let Some(rectangle) = self.selected_region {
+            self.copy_region();
+            let changes =
+                region::get_changes_to_clear(&self.mosaic, &rectangle, self.get_grout_color());
+            self.apply_edit(MosaicEdit::ChangeTesserae(changes));
+        }
+    }
+
+    // At the top left of the selected region, or at the selected tessera. The pasted tesserae
+    // become the selected region, without the ones that fall out of the mosaic.
+    pub(crate) fn paste_region(&mut self) {
+        let Some(clipboard) = &self.clipboard else {
+            return;
+        };
+        let at = self
+            .selected_region
+            .map(|rectangle| rectangle.first)
+            .or(self.selected_tessera)
+            .unwrap_or(PositionOnGrid { row: 1, column: 1 });
+        let changes = clipboard.get_changes_to_paste(&self.mosaic, at);
+        if changes.is_empty() {
+            return;
+        }
+        let last = changes.iter().fold(at, |last, change| PositionOnGrid {
+            row: last.row.max(change.position.row),
+            column: last.column.max(change.position.column),
+        });
+        self.selected_region = Some(GridRectangle::new(at, last));
+        self.apply_edit(MosaicEdit::ChangeTesserae(changes));
+    }
+
+    // All in one edit, the tesserae left behind leave empty cells.
+    pub(crate) fn move_region(&mut self, rows: isize, columns: isize) {
+        let Some(rectangle) = self.selected_region else {
+            return;
+        };
+        let Some(moved) = rectangle.moved(rows, columns) else {
+            return;
+        };
+        if moved == rectangle {
+            return;
+        }
+        let content = TesseraRegion::copy_from(&self.mosaic, &rectangle);
+        let mut changes =
+            region::get_changes_to_clear(&self.mosaic, &rectangle, self.get_grout_color());
+        changes.extend(content.get_changes_to_paste(&self.mosaic, moved.first));
+        self.selected_region = Some(moved);
+        self.apply_edit(MosaicEdit::ChangeTesserae(changes));
+    }
+
+    // In place, from the top left corner. A turn swaps the rows and the columns of the region.
+    pub(crate) fn transform_region(&mut self, transform: RegionTransform) {
+        let Some(rectangle) = self.selected_region else {
+            return;
+        };
+        let mut content = TesseraRegion::copy_from(&self.mosaic, &rectangle);
+        content.transform(transform);
+        let mut changes =
+            region::get_changes_to_clear(&self.mosaic, &rectangle, self.get_grout_color());
+        changes.extend(content.get_changes_to_paste(&self.mosaic, rectangle.first));
+        let [rows, columns] = content.get_size();
+        self.selected_region = Some(GridRectangle::new(
+            rectangle.first,
+            PositionOnGrid {
+                row: rectangle.first.row + rows - 1,
+                column: rectangle.first.column + columns - 1,
+            },
+        ));
+        self.apply_edit(MosaicEdit::ChangeTesserae(changes));
+    }
+
+    fn get_grout_color(&self) -> palette::Oklch {
+        colors::oklch_from_srgb8(self.settings.render.grout_srgb)
+    }
+
+    pub(crate) fn propose_palette(&mut self) {
+        self.proposed_palette
+            .run(&self.mosaic, self.number_of_proposed_colors);
+        self.clusters_to_merge.clear();
+    }
+
+    // Into the first of them.
+    pub(crate) fn merge_selected_clusters(&mut self) {
+        let mut selected = std::mem::take(&mut self.clusters_to_merge).into_iter();
+        if let Some(into) = selected.next() {
+            // From the last one, so the indices of the rest do not change.
+            for other in selected.rev() {
+                if let Err(e) = self.proposed_palette.merge(into, other) {
+                    self.status_message = e;
+                }
+            }
+        }
+    }
+
+    pub(crate) fn apply_proposed_palette(&mut self) {
+        let colors = self.proposed_palette.get_quantized_colors(&self.mosaic);
+        self.apply_edit(MosaicEdit::ChangeColors(colors));
+    }
+
+    // Found again only when the mosaic, the source or the tolerance change.
+    pub(crate) fn update_tesserae_to_replace(&mut self) {
+        let Some(source) = self.replacement_source else {
+            self.tesserae_to_replace.clear();
+            self.tesserae_to_replace_for = None;
+            return;
+        };
+        let key = (self.canvas_revision, source, self.replacement_tolerance);
+        if self.tesserae_to_replace_for != Some(key) {
+            self.tesserae_to_replace = self
+                .mosaic
+                .get_tesserae_of_similar_color(source, self.replacement_tolerance);
+            self.tesserae_to_replace_for = Some(key);
+        }
+    }
+
+    pub(crate) fn replace_color(&mut self) {
+        let (Some(source), Some(target)) = (self.replacement_source, self.replacement_target)
+        else {
+            return;
+        };
+        let (colors, replaced) =
+            self.mosaic
+                .get_colors_with_replacement(source, target, self.replacement_tolerance);
+        if replaced > 0 {
+            self.apply_edit(MosaicEdit::ChangeColors(colors));
+        }
+        self.status_message = format!("{}: {replaced}", t!("tesserae_replaced"));
+    }
+
+    pub(crate) fn replace_mosaic(&mut self, mosaic: Mosaic) {
+        self.apply_edit(MosaicEdit::ReplaceMosaic(Box::new(mosaic)));
+    }
+
+    fn apply_edit(&mut self, edit: MosaicEdit) {
+        if let Err(e) = self.history.apply(&mut self.mosaic, edit) {
+            self.status_message = e;
+        }
+        self.forget_colors_without_dithering();
+        self.invalidate_canvas();
+    }
+
+    // They are only right for the mosaic just generated.
+    fn forget_colors_without_dithering(&mut self) {
+        self.colors_without_dithering.clear();
+        self.comparing_dithering = false;
+    }
+
+    pub(crate) fn undo(&mut self) {
+        if !self.history.undo(&mut self.mosaic) {
+            self.status_message = t!("nothing_to_undo").to_string();
+        }
+        self.forget_colors_without_dithering();
+        self.invalidate_canvas();
+    }
+
+    pub(crate) fn redo(&mut self) {
+        if !self.history.redo(&mut self.mosaic) {
+            self.status_message = t!("nothing_to_redo").to_string();
+        }
+        self.forget_colors_without_dithering();
+        self.invalidate_canvas();
+    }
+
+    // None means unbounded. The oldest steps are forgotten if there are more.
+    pub(crate) fn set_max_undo_steps(&mut self, max_undo_steps: Option<usize>) {
+        self.settings.max_undo_steps = max_undo_steps;
+        self.history.set_max_depth(max_undo_steps);
+    }
+
+    pub(crate) fn invalidate_canvas(&mut self) {
+        self.canvas_revision = self.canvas_revision.wrapping_add(1);
+    }
+
+    pub(crate) fn pick_color_from_image(&mut self, point_position_on_mosaic: [usize; 2]) {
+        let pixel_position = self.get_pixel_position_on_image(point_position_on_mosaic);
+        let picked_color = match &self.loaded_image {
+            Some(img) => sampling::sample_color(
+                img,
+                PixelArea {
+                    left: pixel_position[0] as u32,
+                    top: pixel_position[1] as u32,
+                    right: pixel_position[0] as u32 + 1,
+                    bottom: pixel_position[1] as u32 + 1,
+                },
+                SamplingMode::CenterPixel,
+            ),
             None => Err(String::from("no image")),
+        };
+        match picked_color {
+            Ok(color) => self.color_to_apply = color,
+            Err(e) => self.status_message = e,
+        }
+    }
+
+    pub fn get_a_blank_mosaic_with_all_tesserae_equal_color(
+        &self,
+        choosen_color: egui::Color32,
+    ) -> Mosaic {
+        let color_srgba: palette::Srgba<f32> =
+            palette::Srgba::from(choosen_color.to_srgba_unmultiplied()).into();
+        generator::generate_blank_mosaic(
+            palette::Oklch::from_color(color_srgba),
+            &self.get_generation_settings(),
+        )
+    }
+
+    // Keeps, to compare, the tile colors every tessera would have without dithering.
+    pub(crate) fn generate_mosaic_from_loaded_image(&mut self) {
+        let (mosaic, colors_without_dithering) =
+            generator::generate_mosaic_and_colors_without_dithering(
+                self.loaded_image.clone(),
+                &self.get_generation_settings(),
+                self.restrict_to_palette
+                    .then_some(&self.settings.tile_palette),
+            );
+        self.replace_mosaic(mosaic);
+        self.colors_without_dithering = colors_without_dithering.unwrap_or_default();
+    }
+
+    pub fn get_andamento_mosaic_from_loaded_image(&self) -> Mosaic {
+        andamento::generate_andamento_mosaic(
+            self.loaded_image.clone(),
+            &self.get_generation_settings(),
+            &self.andamento,
+            &self.guide_curves,
+            self.restrict_to_palette
+                .then_some(&self.settings.tile_palette),
+        )
+    }
+
+    // Looks at the image in cells of half a tessera.
+    pub(crate) fn set_guide_curves_from_image_edges(&mut self) {
+        match &self.loaded_image {
+            Some(img) => {
+                let tessera_size = self.get_tessera_size();
+                self.guide_curves = andamento::guide_curves_from_image_edges(
+                    img,
+                    self.get_mosaic_dimensions(),
+                    tessera_size[0].min(tessera_size[1]) as f32 / 2.0,
+                );
+            }
+            None => self.status_message = t!("no_image_loaded").to_string(),
         }
     }
+
+    pub(crate) fn get_generation_settings(&self) -> GenerationSettings {
+        GenerationSettings {
+            mosaic_dimensions_mm: self.get_mosaic_dimensions(),
+            tessera_size: RectangleInMm {
+                horizontal: self.get_tessera_size()[0],
+                vertical: self.get_tessera_size()[1],
+            },
+            gap_between_tesserae: config::DEFAULT_GAP_BETWEEN_TESSSELAE,
+            sampling_mode: self.sampling_mode,
+            fit_to_edges: self.fit_to_edges,
+            pattern: self.pattern,
+            dithering: self.dithering,
+        }
+    }
+
+    fn get_pixel_position_on_image(&self, point_position_on_mosaic: [usize; 2]) -> [usize; 2] {
+        generator::get_pixel_position_on_image(
+            point_position_on_mosaic,
+            self.get_mosaic_dimensions(),
+            self.get_image_dimensions(),
+        )
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use mosaicneitor_core::dithering::DitheringMethod;
 
     #[test]
-    fn get_mosaic_dimensions_yields_correct_values_or_defaults() {
+    fn get_mosaic_from_base_image_uses_only_palette_colors_when_restricted_to_palette() {
         let mut app = MosaicneitorApp::default();
+        let orange_image = egui::ColorImage::new([50, 30], egui::Color32::from_rgb(250, 140, 20));
+        app.loaded_image = Some(loaded_image_from(&orange_image));
+        app.image = Some(orange_image);
+        app.settings.tile_palette.add_entry([0, 0, 255]);
+        app.settings.tile_palette.add_entry([255, 160, 0]);
+        app.restrict_to_palette = true;
+        app.generate_mosaic_from_loaded_image();
+        let mosaic = &app.mosaic;
+        for row in mosaic.get_contents() {
+            for tessera in row {
+                assert_eq!(colors::srgb8_from_oklch(tessera.color), [255, 160, 0]);
+            }
+        }
+    }
+
+    #[test]
+    fn pick_color_from_image_takes_the_color_under_the_given_point() {
+        let mut app = MosaicneitorApp::default();
+        let mut two_colors_image = egui::ColorImage::new([100, 50], egui::Color32::RED);
+        for y in 0..50 {
+            for x in 50..100 {
+                two_colors_image.pixels[y * 100 + x] = egui::Color32::BLUE;
+            }
+        }
+        app.loaded_image = Some(loaded_image_from(&two_colors_image));
+        app.image = Some(two_colors_image);
+        app.mosaic_dimension_h = String::from("200");
+        app.mosaic_dimension_v = String::from("100");
+
+        app.pick_color_from_image([20, 50]);
+        assert_eq!(colors::srgb8_from_oklch(app.color_to_apply), [255, 0, 0]);
+        app.pick_color_from_image([150, 50]);
+        assert_eq!(colors::srgb8_from_oklch(app.color_to_apply), [0, 0, 255]);
+    }
+
+    #[test]
+    fn regenerating_the_mosaic_and_changing_tesserae_can_be_undone() {
+        let mut app = MosaicneitorApp {
+            mosaic_dimension_h: String::from("100"),
+            mosaic_dimension_v: String::from("100"),
+            ..MosaicneitorApp::default()
+        };
+        let white = egui::Color32::WHITE;
+        app.replace_mosaic(app.get_a_blank_mosaic_with_all_tesserae_equal_color(white));
+        let position = PositionOnGrid { row: 2, column: 2 };
+        app.color_to_apply = colors::oklch_from_srgb8([255, 0, 0]);
+        app.apply_color_to_tessera(&position);
+        let color_at = |app: &MosaicneitorApp| {
+            colors::srgb8_from_oklch(app.mosaic.get_tessera(&position).unwrap().color)
+        };
+        assert_eq!(color_at(&app), [255, 0, 0]);
+
+        app.undo();
+        assert_eq!(color_at(&app), [255, 255, 255]);
+        app.undo();
+        assert_eq!(app.mosaic.get_number_of_rows(), 0);
+        app.redo();
+        app.redo();
+        assert_eq!(color_at(&app), [255, 0, 0]);
+    }
+
+    #[test]
+    fn only_the_latest_steps_are_kept_to_undo() {
+        let mut app = MosaicneitorApp::default();
+        assert_eq!(
+            app.settings.max_undo_steps,
+            Some(config::DEFAULT_MAX_UNDO_STEPS)
+        );
+        app.mosaic_dimension_h = String::from("30");
+        app.mosaic_dimension_v = String::from("30");
+        for _regeneration in 0..3 {
+            app.replace_mosaic(
+                app.get_a_blank_mosaic_with_all_tesserae_equal_color(egui::Color32::WHITE),
+            );
+        }
+        app.set_max_undo_steps(Some(1));
+        app.undo();
+        assert!(!app.history.can_undo());
+        assert_eq!(app.mosaic.get_number_of_rows(), 3);
+    }
+
+    #[test]
+    fn resizing_a_row_keeps_the_colors_and_can_be_undone() {
+        let mut app = MosaicneitorApp {
+            mosaic_dimension_h: String::from("100"),
+            mosaic_dimension_v: String::from("100"),
+            ..MosaicneitorApp::default()
+        };
+        let white = egui::Color32::WHITE;
+        app.replace_mosaic(app.get_a_blank_mosaic_with_all_tesserae_equal_color(white));
+        app.size_to_apply = RectangleInMm {
+            horizontal: 5,
+            vertical: 15,
+        };
+        app.apply_size_to_row(2);
+        let row = &app.mosaic.get_contents()[1];
+        assert!(row.iter().all(|tessera| tessera.size == app.size_to_apply));
+        assert_eq!(colors::srgb8_from_oklch(row[0].color), [255, 255, 255]);
+        assert_eq!(app.mosaic.get_contents()[0][0].size.vertical, 10);
+
+        app.undo();
+        assert_eq!(app.mosaic.get_contents()[1][0].size.vertical, 10);
+    }
+
+    #[test]
+    fn cutting_a_tessera_keeps_its_color_and_colouring_it_keeps_its_shape() {
+        let mut app = MosaicneitorApp {
+            mosaic_dimension_h: String::from("100"),
+            mosaic_dimension_v: String::from("100"),
+            ..MosaicneitorApp::default()
+        };
+        let white = egui::Color32::WHITE;
+        app.replace_mosaic(app.get_a_blank_mosaic_with_all_tesserae_equal_color(white));
+        let position = PositionOnGrid { row: 3, column: 4 };
+        app.custom_shape_text = String::from("0,0 10,0 10,5");
+        app.set_custom_shape_to_apply(&position);
+        app.apply_shape_to_tessera(&position);
+        app.color_to_apply = colors::oklch_from_srgb8([255, 0, 0]);
+        app.apply_color_to_tessera(&position);
+
+        let tessera = app.mosaic.get_tessera(&position).unwrap();
+        assert_eq!(colors::srgb8_from_oklch(tessera.color), [255, 0, 0]);
+        assert_eq!(tessera.shape.get_number_of_cuts(&tessera.size), 1);
+        app.undo();
+        app.undo();
+        let tessera = app.mosaic.get_tessera(&position).unwrap();
+        assert_eq!(tessera.shape, TesseraShape::Whole);
+    }
+
+    #[test]
+    fn a_tool_stroke_paints_the_tesserae_and_is_undone_at_once() {
+        let mut app = MosaicneitorApp {
+            mosaic_dimension_h: String::from("100"),
+            mosaic_dimension_v: String::from("100"),
+            ..MosaicneitorApp::default()
+        };
+        let white = egui::Color32::WHITE;
+        app.replace_mosaic(app.get_a_blank_mosaic_with_all_tesserae_equal_color(white));
+        let red = colors::oklch_from_srgb8([255, 0, 0]);
+        let count_of_red = |app: &MosaicneitorApp| {
+            app.mosaic
+                .get_all_tesserae()
+                .filter(|tessera| tessera.color == red)
+                .count()
+        };
+        app.color_to_apply = red;
+
+        app.tool = CanvasTool::Brush;
+        for x in [5.0, 15.0, 25.0] {
+            app.add_point_to_tool_stroke([x, 5.0]);
+        }
+        assert_eq!(count_of_red(&app), 0);
+        app.finish_tool_stroke();
+        assert_eq!(count_of_red(&app), 3);
+        app.undo();
+        assert_eq!(count_of_red(&app), 0);
+
+        app.tool = CanvasTool::Rectangle;
+        for corner in [[1.0, 1.0], [50.0, 50.0], [19.0, 29.0]] {
+            app.add_point_to_tool_stroke(corner);
+        }
+        app.finish_tool_stroke();
+        assert_eq!(count_of_red(&app), 6);
+
+        // The rest of the white tesserae are all connected.
+        app.color_to_apply = colors::oklch_from_srgb8([0, 0, 255]);
+        app.fill_tolerance = 0.01;
+        app.flood_fill_at([95.0, 95.0]);
+        assert_eq!(count_of_red(&app), 6);
+        assert!(app
+            .mosaic
+            .get_all_tesserae()
+            .all(|tessera| colors::srgb8_from_oklch(tessera.color) != [255, 255, 255]));
+    }
+
+    #[test]
+    fn a_region_is_selected_copied_pasted_moved_and_turned() {
+        let mut app = MosaicneitorApp {
+            mosaic_dimension_h: String::from("100"),
+            mosaic_dimension_v: String::from("100"),
+            ..MosaicneitorApp::default()
+        };
+        let white = egui::Color32::WHITE;
+        app.replace_mosaic(app.get_a_blank_mosaic_with_all_tesserae_equal_color(white));
+        let red = colors::oklch_from_srgb8([255, 0, 0]);
+        app.color_to_apply = red;
+        app.apply_color_to_tessera(&PositionOnGrid { row: 1, column: 1 });
+        let is_red = |app: &MosaicneitorApp, row, column| {
+            app.mosaic
+                .get_tessera(&PositionOnGrid { row, column })
+                .is_some_and(|tessera| tessera.color == red)
+        };
+
+        // The first two tesserae of the first row.
+        app.tool = CanvasTool::Region;
+        app.add_point_to_tool_stroke([1.0, 1.0]);
+        app.add_point_to_tool_stroke([20.0, 8.0]);
+        app.finish_tool_stroke();
+        let region = app.selected_region.unwrap();
+        assert_eq!(region.get_size(), [1, 2]);
+        app.copy_region();
+
+        // Another project, the clipboard is kept.
+        app.replace_mosaic(app.get_a_blank_mosaic_with_all_tesserae_equal_color(white));
+        app.selected_region = None;
+        app.selected_tessera = Some(PositionOnGrid { row: 3, column: 3 });
+        app.paste_region();
+        assert!(is_red(&app, 3, 3));
+        assert!(!is_red(&app, 3, 4));
+        let laid_tesserae = app.get_materials_report().get_total_tesserae();
+
+        // Dragged from inside, two rows down.
+        app.add_point_to_tool_stroke([25.0, 25.0]);
+        app.add_point_to_tool_stroke([25.0, 47.0]);
+        app.finish_tool_stroke();
+        assert!(is_red(&app, 5, 3));
+        assert!(!is_red(&app, 3, 3));
+        assert_eq!(
+            app.selected_region.unwrap().first,
+            PositionOnGrid { row: 5, column: 3 }
+        );
+        // The cells left behind are empty, nothing to buy for them.
+        let left_behind = app
+            .mosaic
+            .get_tessera(&PositionOnGrid { row: 3, column: 3 })
+            .unwrap();
+        assert_eq!(left_behind.shape, TesseraShape::Empty);
+        assert_eq!(
+            app.get_materials_report().get_total_tesserae(),
+            laid_tesserae - 2
+        );
+
+        app.transform_region(RegionTransform::RotateClockwise);
+        assert_eq!(app.selected_region.unwrap().get_size(), [2, 1]);
+        assert!(is_red(&app, 5, 3));
+        app.transform_region(RegionTransform::FlipVertical);
+        assert!(is_red(&app, 6, 3));
+        assert!(!is_red(&app, 5, 3));
+        app.undo();
+        app.undo();
+        app.undo();
+        assert!(is_red(&app, 3, 3));
+
+        // Pasted at the last column, only what fits is selected.
+        app.selected_region = None;
+        app.selected_tessera = Some(PositionOnGrid { row: 1, column: 9 });
+        app.paste_region();
+        assert!(is_red(&app, 1, 9));
+        let pasted = app.selected_region.unwrap();
+        assert_eq!(pasted.first, PositionOnGrid { row: 1, column: 9 });
+        assert_eq!(pasted.get_size(), [1, 1]);
+    }
+
+    #[test]
+    fn the_materials_report_is_made_again_only_after_a_change() {
+        let mut app = MosaicneitorApp {
+            mosaic_dimension_h: String::from("50"),
+            mosaic_dimension_v: String::from("50"),
+            ..MosaicneitorApp::default()
+        };
+        let white = egui::Color32::WHITE;
+        app.replace_mosaic(app.get_a_blank_mosaic_with_all_tesserae_equal_color(white));
+        let report = app.get_materials_report();
+        assert_eq!(report.lines.len(), 1);
+        assert!(std::sync::Arc::ptr_eq(&report, &app.get_materials_report()));
+
+        app.settings.materials.waste_percentage = 50.0;
+        let with_more_waste = app.get_materials_report();
+        assert!(!std::sync::Arc::ptr_eq(&report, &with_more_waste));
+        app.color_to_apply = colors::oklch_from_srgb8([255, 0, 0]);
+        app.apply_color_to_tessera(&PositionOnGrid { row: 1, column: 1 });
+        assert_eq!(app.get_materials_report().lines.len(), 2);
+    }
+
+    #[test]
+    fn similar_colors_are_replaced_at_once_and_can_be_undone() {
+        let mut app = MosaicneitorApp {
+            mosaic_dimension_h: String::from("50"),
+            mosaic_dimension_v: String::from("50"),
+            ..MosaicneitorApp::default()
+        };
+        let greenish_grey = egui::Color32::from_rgb(120, 130, 120);
+        app.replace_mosaic(app.get_a_blank_mosaic_with_all_tesserae_equal_color(greenish_grey));
+        app.color_to_apply = colors::oklch_from_srgb8([118, 134, 118]);
+        app.apply_color_to_tessera(&PositionOnGrid { row: 1, column: 1 });
+        app.color_to_apply = colors::oklch_from_srgb8([200, 0, 0]);
+        app.apply_color_to_tessera(&PositionOnGrid { row: 1, column: 2 });
+
+        app.replacement_source = Some(colors::oklch_from_srgb8([120, 130, 120]));
+        app.replacement_tolerance = 0.05;
+        app.update_tesserae_to_replace();
+        assert_eq!(app.tesserae_to_replace.len(), 24);
+        let green = colors::oklch_from_srgb8([0, 128, 0]);
+        app.replacement_target = Some(green);
+        app.replace_color();
+        let count_of_green = |app: &MosaicneitorApp| {
+            app.mosaic
+                .get_all_tesserae()
+                .filter(|tessera| tessera.color == green)
+                .count()
+        };
+        assert_eq!(count_of_green(&app), 24);
+        assert!(app.status_message.ends_with("24"));
+        // Found again for the new colors.
+        app.update_tesserae_to_replace();
+        assert!(app.tesserae_to_replace.is_empty());
+        app.undo();
+        assert_eq!(count_of_green(&app), 0);
+    }
+
+    #[test]
+    fn the_proposed_palette_recolors_the_mosaic_and_can_be_undone() {
+        let mut app = MosaicneitorApp {
+            mosaic_dimension_h: String::from("30"),
+            mosaic_dimension_v: String::from("30"),
+            ..MosaicneitorApp::default()
+        };
+        let white = egui::Color32::WHITE;
+        app.replace_mosaic(app.get_a_blank_mosaic_with_all_tesserae_equal_color(white));
+        for (column, srgb) in [(1, [250, 0, 0]), (2, [240, 10, 0]), (3, [0, 0, 250])] {
+            app.color_to_apply = colors::oklch_from_srgb8(srgb);
+            app.apply_color_to_tessera(&PositionOnGrid { row: 1, column });
+        }
+        app.number_of_proposed_colors = 4;
+        app.propose_palette();
+        assert_eq!(app.proposed_palette.clusters.len(), 4);
+        // Both reds together.
+        app.clusters_to_merge = (0..4)
+            .filter(|index| {
+                let [r, _g, b] =
+                    colors::srgb8_from_oklch(app.proposed_palette.clusters[*index].color);
+                r > 200 && b < 50
+            })
+            .collect();
+        app.merge_selected_clusters();
+        assert_eq!(app.proposed_palette.clusters.len(), 3);
+        app.apply_proposed_palette();
+        let first_row = &app.mosaic.get_contents()[0];
+        assert_eq!(first_row[0].color, first_row[1].color);
+        assert_ne!(first_row[0].color, first_row[2].color);
+        app.undo();
+        assert_ne!(
+            app.mosaic.get_contents()[0][0].color,
+            app.mosaic.get_contents()[0][1].color
+        );
+    }
+
+    #[test]
+    fn the_colors_without_dithering_are_the_nearest_tile_colors() {
+        let mut app = MosaicneitorApp::default();
+        let grey_image = egui::ColorImage::new([50, 50], egui::Color32::from_gray(99));
+        app.loaded_image = Some(loaded_image_from(&grey_image));
+        app.image = Some(grey_image);
+        app.mosaic_dimension_h = String::from("100");
+        app.mosaic_dimension_v = String::from("100");
+        app.settings.tile_palette = TilePalette::default();
+        app.settings.tile_palette.add_entry([0, 0, 0]);
+        app.settings.tile_palette.add_entry([255, 255, 255]);
+        app.restrict_to_palette = true;
+        app.dithering.method = DitheringMethod::FloydSteinberg;
+        app.generate_mosaic_from_loaded_image();
+
+        let dithered: Vec<palette::Oklch> = app
+            .mosaic
+            .get_all_tesserae()
+            .map(|tessera| tessera.color)
+            .collect();
+        assert_eq!(app.colors_without_dithering.len(), dithered.len());
+        assert!(app
+            .colors_without_dithering
+            .iter()
+            .all(|color| *color == app.colors_without_dithering[0]));
+        assert!(dithered.iter().any(|color| *color != dithered[0]));
+
+        // Not right any more after an edit.
+        app.comparing_dithering = true;
+        app.apply_color_to_tessera(&PositionOnGrid { row: 1, column: 1 });
+        assert!(app.colors_without_dithering.is_empty());
+        assert!(!app.comparing_dithering);
+    }
+
+    #[test]
+    fn the_mosaic_is_generated_from_the_adjusted_image() {
+        let mut app = MosaicneitorApp {
+            // Red on the left half, blue on the right one.
+            original_image: Some(image::Rgba32FImage::from_fn(100, 50, |x, _y| {
+                if x < 50 {
+                    image::Rgba([1.0, 0.0, 0.0, 1.0])
+                } else {
+                    image::Rgba([0.0, 0.0, 1.0, 1.0])
+                }
+            })),
+            mosaic_dimension_h: String::from("200"),
+            mosaic_dimension_v: String::from("100"),
+            image_operations: vec![ImageOperation::Crop {
+                left: 0.0,
+                top: 0.0,
+                right: 0.5,
+                bottom: 0.0,
+            }],
+            ..MosaicneitorApp::default()
+        };
+        app.apply_image_operations();
+        assert_eq!(app.get_image_dimensions(), [50, 50]);
+        assert_eq!(
+            app.get_mosaic_dimensions()[0],
+            app.get_mosaic_dimensions()[1]
+        );
+        app.generate_mosaic_from_loaded_image();
+        let mosaic = &app.mosaic;
+        assert!(mosaic
+            .get_all_tesserae()
+            .all(|tessera| colors::srgb8_from_oklch(tessera.color) == [255, 0, 0]));
+
+        app.image_operations.clear();
+        app.apply_image_operations();
+        assert_eq!(app.get_image_dimensions(), [100, 50]);
+    }
+
+    #[test]
+    fn zooming_keeps_the_point_under_the_pointer() {
+        let mut app = MosaicneitorApp::default();
+        app.set_zoom_factor(2.0);
+        let start_position = egui::pos2(10.0, 20.0);
+        let scroll_offset = egui::vec2(30.0, 0.0);
+        // 50 mm right and 25 mm down.
+        let pointer = egui::pos2(110.0, 70.0);
+        app.zoom_around(1.5, pointer, start_position, scroll_offset);
+        assert_eq!(app.get_zoom_factor(), 3.0);
+        let new_offset = app.pending_scroll_offset.unwrap();
+        // After scrolling, the canvas starts that much further left and up.
+        let new_start_position = start_position - (new_offset - scroll_offset);
+        assert_eq!(new_start_position + egui::vec2(50.0, 25.0) * 3.0, pointer);
+
+        app.zoom_around(1000.0, pointer, start_position, scroll_offset);
+        assert_eq!(app.get_zoom_factor(), config::MAX_ZOOM_FACTOR);
+    }
+
+    #[test]
+    fn the_zoom_fits_the_mosaic_in_the_window_or_shows_it_at_real_size() {
+        let mut app = MosaicneitorApp {
+            mosaic_dimension_h: String::from("2000"),
+            mosaic_dimension_v: String::from("500"),
+            ..MosaicneitorApp::default()
+        };
+        app.fit_zoom_to(egui::vec2(804.0, 604.0));
+        assert_eq!(app.get_zoom_factor(), 0.4);
+        assert_eq!(app.pending_scroll_offset, Some(egui::Vec2::ZERO));
+
+        app.set_real_size_zoom(2.0, 1.5);
+        let points_per_inch = app.get_zoom_factor() * 25.4;
+        assert!((points_per_inch * 1.5 - 192.0).abs() < 0.01);
+
+        // A measured screen wins over the operating system scale.
+        app.settings.screen_dpi = Some(141.0);
+        app.set_real_size_zoom(2.0, 1.5);
+        let points_per_inch = app.get_zoom_factor() * 25.4;
+        assert!((points_per_inch * 1.5 - 141.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn drawn_guide_curves_skip_the_points_too_close_to_the_previous_one() {
+        let mut app = MosaicneitorApp::default();
+        app.guide_curves.push(GuideCurve::default());
+        for point in [[0.0, 0.0], [0.5, 0.5], [3.0, 0.0], [3.0, 1.0], [3.0, 5.0]] {
+            app.add_point_to_last_guide_curve(point);
+        }
+        assert_eq!(
+            app.guide_curves[0].points,
+            vec![[0.0, 0.0], [3.0, 0.0], [3.0, 5.0]]
+        );
+    }
+
+    #[test]
+    fn get_mosaic_dimensions_yields_correct_values_or_defaults() {
+        let mut app = MosaicneitorApp {
+            mosaic_dimension_h: String::from("500"),
+            mosaic_dimension_v: String::from("300"),
+            ..MosaicneitorApp::default()
+        };
 
-        app.mosaic_dimension_h = String::from("500");
-        app.mosaic_dimension_v = String::from("300");
         assert_eq!(app.get_mosaic_dimensions(), [500, 300]);
 
         app.mosaic_dimension_h = String::from("500.8");
@@ -293,10 +1575,12 @@ mod test {
     }
     #[test]
     fn get_tessera_size_yields_correct_values_or_defaults() {
-        let mut app = MosaicneitorApp::default();
+        let mut app = MosaicneitorApp {
+            tessera_size_h: String::from("10"),
+            tessera_size_v: String::from("20"),
+            ..MosaicneitorApp::default()
+        };
 
-        app.tessera_size_h = String::from("10");
-        app.tessera_size_v = String::from("20");
         assert_eq!(app.get_tessera_size(), [10, 20]);
 
         app.tessera_size_h = String::from("10.8");
@@ -306,12 +1590,18 @@ mod test {
         app.tessera_size_h = String::from("eqwer");
         app.tessera_size_v = String::from("asdf");
         assert_eq!(app.get_tessera_size(), [1, 1]);
+
+        app.tessera_size_h = String::from("0");
+        app.tessera_size_v = String::from("0");
+        assert_eq!(app.get_tessera_size(), [1, 1]);
     }
 
     #[test]
     fn translation_from_mosaic_position_to_pixel_position_yieds_correct_positions() {
-        let mut app = MosaicneitorApp::default();
-        app.image = Some(egui::ColorImage::example());
+        let mut app = MosaicneitorApp {
+            image: Some(egui::ColorImage::example()),
+            ..MosaicneitorApp::default()
+        };
 
         let image_dimensions = app.get_image_dimensions();
         app.mosaic_dimension_h = image_dimensions[0].to_string();
@@ -330,38 +1620,50 @@ mod test {
         assert_eq!(app.get_pixel_position_on_image(point_on_mosaic), [3, 1]);
     }
 
-    #[test]
-    fn get_pixel_color_yields_error_if_image_is_none() {
-        let app = MosaicneitorApp::default();
-        assert_eq!(
-            app.get_pixel_color([234, 567]),
-            Err(String::from("no image"))
-        );
+    fn loaded_image_from(color_image: &egui::ColorImage) -> BaseImage {
+        BaseImage::new(image::Rgba32FImage::from_fn(
+            color_image.width() as u32,
+            color_image.height() as u32,
+            |x, y| {
+                let color = color_image.pixels[y as usize * color_image.width() + x as usize]
+                    .to_srgba_unmultiplied();
+                image::Rgba(color.map(|channel| channel as f32 / 255.0))
+            },
+        ))
     }
 
     #[test]
-    fn get_pixel_color_yields_error_if_you_ask_for_pixels_outside_image_boundaries() {
+    fn get_mosaic_from_base_image_takes_the_colors_from_the_loaded_image() {
         let mut app = MosaicneitorApp::default();
-        app.image = Some(egui::ColorImage::example());
-        let pixel_position = [
-            egui::ColorImage::example().width() + 5,
-            egui::ColorImage::example().height() + 5,
-        ];
-        assert_eq!(
-            app.get_pixel_color(pixel_position),
-            Err(String::from("no pixel"))
-        );
+        let red_image = egui::ColorImage::new([50, 30], egui::Color32::RED);
+        app.loaded_image = Some(loaded_image_from(&red_image));
+        app.image = Some(red_image);
+        let red = palette::Oklch::from_color(palette::Srgb::new(1.0, 0.0, 0.0));
+        for mode in SamplingMode::ALL {
+            app.sampling_mode = mode;
+            app.generate_mosaic_from_loaded_image();
+            let mosaic = &app.mosaic;
+            for row in mosaic.get_contents() {
+                for tessera in row {
+                    assert!((tessera.color.l - red.l).abs() < 0.001, "{:?}", mode);
+                }
+            }
+        }
     }
 
     #[test]
     fn get_mosaic_from_base_image_yields_correct_mosaic_dimensions_for_happy_path_case() {
-        let mut app = MosaicneitorApp::default();
-        app.mosaic_dimension_h = String::from("500");
-        app.mosaic_dimension_v = String::from("300");
-        app.tessera_size_h = String::from("10");
-        app.tessera_size_v = String::from("10");
-        app.image = Some(egui::ColorImage::example());
-        let mosaic = app.get_mosaic_from_loaded_image();
+        let mut app = MosaicneitorApp {
+            mosaic_dimension_h: String::from("500"),
+            mosaic_dimension_v: String::from("300"),
+            tessera_size_h: String::from("10"),
+            tessera_size_v: String::from("10"),
+            image: Some(egui::ColorImage::example()),
+            loaded_image: Some(loaded_image_from(&egui::ColorImage::example())),
+            ..MosaicneitorApp::default()
+        };
+        app.generate_mosaic_from_loaded_image();
+        let mosaic = &app.mosaic;
         assert_eq!(
             mosaic.get_number_of_rows(),
             300 / (10 + config::DEFAULT_GAP_BETWEEN_TESSSELAE) + 1
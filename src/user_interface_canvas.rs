@@ -0,0 +1,345 @@
+use eframe::egui;
+
+use mosaicneitor_core::{
+    colors,
+    generator::{self, GenerationSettings},
+    mosaic::Mosaic,
+    tessera_shape::TesseraShape,
+};
+
+use crate::config;
+
+// The geometry of the tesserae, in mm, kept between frames. It is rebuilt only when the canvas
+// revision changes, and only the tesserae in view are turned into shapes.
+#[derive(Default)]
+pub(crate) struct CanvasCache {
+    built_for_revision: Option<u64>,
+    tesserae: Vec<CachedTessera>,
+    grid_preview: Option<(GenerationSettings, Vec<egui::Rect>)>,
+}
+
+struct CachedTessera {
+    bounds: egui::Rect,
+    // None for whole tesserae, their bounds are their shape.
+    polygon: Option<Vec<egui::Pos2>>,
+    fill: egui::Color32,
+    // The color without dithering, to compare.
+    comparison_fill: Option<egui::Color32>,
+}
+
+impl CanvasCache {
+    // The comparison colors are in the order of Mosaic::get_all_tesserae, ignored if they do not match.
+    pub(crate) fn update(
+        &mut self,
+        revision: u64,
+        mosaic: &Mosaic,
+        gap_between_tesserae: usize,
+        comparison_colors: &[palette::Oklch],
+    ) {
+        if self.built_for_revision == Some(revision) {
+            return;
+        }
+        self.built_for_revision = Some(revision);
+        let to_egui = |color: palette::Oklch| {
+            let [r, g, b] = colors::srgb8_from_oklch(color);
+            egui::Color32::from_rgb(r, g, b)
+        };
+        let comparing = comparison_colors.len() == mosaic.get_all_tesserae().count();
+        let comparison_fill = |index: usize| {
+            comparing
+                .then(|| comparison_colors.get(index).copied().map(to_egui))
+                .flatten()
+        };
+        let mut tesserae = Vec::new();
+        for (_position, tessera, placement) in
+            mosaic.get_tesserae_with_placements(gap_between_tesserae)
+        {
+            let bounds = egui::Rect::from_min_size(
+                egui::pos2(placement.origin[0] as f32, placement.origin[1] as f32),
+                egui::vec2(
+                    placement.size.horizontal as f32,
+                    placement.size.vertical as f32,
+                ),
+            );
+            // Empty cells get a polygon without points, so nothing is drawn for them.
+            let polygon = (tessera.shape != TesseraShape::Whole).then(|| {
+                tessera
+                    .shape
+                    .get_polygon(&placement.size)
+                    .iter()
+                    .map(|[x, y]| bounds.min + egui::vec2(*x, *y))
+                    .collect()
+            });
+            tesserae.push(CachedTessera {
+                bounds,
+                polygon,
+                fill: to_egui(tessera.color),
+                comparison_fill: comparison_fill(tesserae.len()),
+            });
+        }
+        for free_tessera in mosaic.get_free_tesserae() {
+            let polygon: Vec<egui::Pos2> = free_tessera
+                .get_polygon()
+                .into_iter()
+                .map(|[x, y]| egui::pos2(x, y))
+                .collect();
+            tesserae.push(CachedTessera {
+                bounds: egui::Rect::from_points(&polygon),
+                polygon: Some(polygon),
+                fill: to_egui(free_tessera.tessera.color),
+                comparison_fill: comparison_fill(tesserae.len()),
+            });
+        }
+        self.tesserae = tesserae;
+    }
+
+    // With a split, in mm, the tesserae right of it get their comparison colors.
+    pub(crate) fn fill_shapes(
+        &self,
+        visible_area: egui::Rect,
+        start_position: egui::Pos2,
+        zoom_factor: f32,
+        comparison_split: Option<f32>,
+    ) -> Vec<egui::Shape> {
+        self.visible_tesserae(visible_area)
+            .map(|tessera| {
+                let fill = match (comparison_split, tessera.comparison_fill) {
+                    (Some(split), Some(comparison_fill)) if tessera.bounds.center().x > split => {
+                        comparison_fill
+                    }
+                    _ => tessera.fill,
+                };
+                match &tessera.polygon {
+                    None => egui::epaint::Shape::Rect(egui::epaint::RectShape {
+                        rect: to_screen(tessera.bounds, start_position, zoom_factor),
+                        rounding: eframe::egui::Rounding::ZERO,
+                        fill,
+                        stroke: egui::epaint::Stroke::new(1.0, fill),
+                        blur_width: 0.0,
+                        fill_texture_id: egui::TextureId::default(),
+                        uv: egui::Rect::ZERO,
+                    }),
+                    Some(polygon) => egui::epaint::Shape::convex_polygon(
+                        points_to_screen(polygon, start_position, zoom_factor),
+                        fill,
+                        egui::epaint::Stroke::NONE,
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    pub(crate) fn outline_shapes(
+        &self,
+        visible_area: egui::Rect,
+        start_position: egui::Pos2,
+        zoom_factor: f32,
+    ) -> Vec<egui::Shape> {
+        let stroke = egui::epaint::Stroke::new(1.0, config::COLOR_FOR_GRID);
+        self.visible_tesserae(visible_area)
+            .map(|tessera| match &tessera.polygon {
+                None => egui::epaint::Shape::rect_stroke(
+                    to_screen(tessera.bounds, start_position, zoom_factor),
+                    eframe::egui::Rounding::ZERO,
+                    stroke,
+                ),
+                Some(polygon) => egui::epaint::Shape::closed_line(
+                    points_to_screen(polygon, start_position, zoom_factor),
+                    stroke,
+                ),
+            })
+            .collect()
+    }
+
+    // A thick outline around the tesserae with the given indices, in the order of
+    // Mosaic::get_all_tesserae.
+    pub(crate) fn highlight_shapes(
+        &self,
+        indices: &[usize],
+        visible_area: egui::Rect,
+        start_position: egui::Pos2,
+        zoom_factor: f32,
+    ) -> Vec<egui::Shape> {
+        let stroke = egui::epaint::Stroke::new(2.0, config::COLOR_FOR_HIGHLIGHTING);
+        indices
+            .iter()
+            .filter_map(|index| self.tesserae.get(*index))
+            .filter(|tessera| tessera.bounds.intersects(visible_area))
+            .map(|tessera| match &tessera.polygon {
+                None => egui::epaint::Shape::rect_stroke(
+                    to_screen(tessera.bounds, start_position, zoom_factor),
+                    eframe::egui::Rounding::ZERO,
+                    stroke,
+                ),
+                Some(polygon) => egui::epaint::Shape::closed_line(
+                    points_to_screen(polygon, start_position, zoom_factor),
+                    stroke,
+                ),
+            })
+            .collect()
+    }
+
+    // The tesserae that would be laid with the settings, following the pattern.
+    // The layout is computed again only when the settings change.
+    pub(crate) fn grid_preview_shapes(
+        &mut self,
+        settings: &GenerationSettings,
+        visible_area: egui::Rect,
+        start_position: egui::Pos2,
+        zoom_factor: f32,
+    ) -> Vec<egui::Shape> {
+        if self
+            .grid_preview
+            .as_ref()
+            .is_none_or(|(built_for, _rectangles)| built_for != settings)
+        {
+            let rectangles = generator::get_layout(settings)
+                .iter()
+                .flatten()
+                .map(|(origin, size)| {
+                    egui::Rect::from_min_size(
+                        egui::pos2(origin[0] as f32, origin[1] as f32),
+                        egui::vec2(size.horizontal as f32, size.vertical as f32),
+                    )
+                })
+                .collect();
+            self.grid_preview = Some((*settings, rectangles));
+        }
+        let stroke = egui::epaint::Stroke::new(1.0, config::COLOR_FOR_GRID);
+        self.grid_preview
+            .iter()
+            .flat_map(|(_settings, rectangles)| rectangles)
+            .filter(|rectangle| rectangle.intersects(visible_area))
+            .map(|rectangle| {
+                egui::epaint::Shape::rect_stroke(
+                    to_screen(*rectangle, start_position, zoom_factor),
+                    eframe::egui::Rounding::ZERO,
+                    stroke,
+                )
+            })
+            .collect()
+    }
+
+    fn visible_tesserae(&self, visible_area: egui::Rect) -> impl Iterator<Item = &CachedTessera> {
+        self.tesserae
+            .iter()
+            .filter(move |tessera| tessera.bounds.intersects(visible_area))
+    }
+}
+
+// The part of the mosaic on the screen, in mm.
+pub(crate) fn get_visible_area(
+    clip_rect: egui::Rect,
+    start_position: egui::Pos2,
+    zoom_factor: f32,
+) -> egui::Rect {
+    egui::Rect::from_min_max(
+        ((clip_rect.min - start_position) / zoom_factor).to_pos2(),
+        ((clip_rect.max - start_position) / zoom_factor).to_pos2(),
+    )
+}
+
+fn to_screen(rectangle: egui::Rect, start_position: egui::Pos2, zoom_factor: f32) -> egui::Rect {
+    egui::Rect::from_min_max(
+        start_position + rectangle.min.to_vec2() * zoom_factor,
+        start_position + rectangle.max.to_vec2() * zoom_factor,
+    )
+}
+
+fn points_to_screen(
+    points: &[egui::Pos2],
+    start_position: egui::Pos2,
+    zoom_factor: f32,
+) -> Vec<egui::Pos2> {
+    points
+        .iter()
+        .map(|point| start_position + point.to_vec2() * zoom_factor)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mosaicneitor_core::{
+        dithering::DitheringSettings,
+        generator::LayoutPattern,
+        mosaic::{RectangleInMm, Tessera},
+        sampling::SamplingMode,
+    };
+
+    // 10x10 tesserae of 10 mm, all white.
+    fn a_white_mosaic() -> Mosaic {
+        generator::generate_blank_mosaic(
+            colors::oklch_from_srgb8([255, 255, 255]),
+            &GenerationSettings {
+                mosaic_dimensions_mm: [100, 100],
+                tessera_size: RectangleInMm {
+                    horizontal: 10,
+                    vertical: 10,
+                },
+                gap_between_tesserae: 0,
+                sampling_mode: SamplingMode::Average,
+                fit_to_edges: false,
+                pattern: LayoutPattern::Regulatum,
+                dithering: DitheringSettings::default(),
+            },
+        )
+    }
+
+    #[test]
+    fn only_the_tesserae_in_view_become_shapes() {
+        let mut cache = CanvasCache::default();
+        cache.update(0, &a_white_mosaic(), 0, &[]);
+        let everything = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(100.0, 100.0));
+        assert_eq!(
+            cache
+                .fill_shapes(everything, egui::Pos2::ZERO, 2.0, None)
+                .len(),
+            100
+        );
+        // Two columns and three rows, partly.
+        let corner = egui::Rect::from_min_max(egui::pos2(-5.0, -5.0), egui::pos2(15.0, 25.0));
+        assert_eq!(
+            cache.fill_shapes(corner, egui::Pos2::ZERO, 2.0, None).len(),
+            6
+        );
+        assert_eq!(cache.outline_shapes(corner, egui::Pos2::ZERO, 2.0).len(), 6);
+
+        let visible = get_visible_area(
+            egui::Rect::from_min_max(egui::pos2(110.0, 50.0), egui::pos2(150.0, 70.0)),
+            egui::pos2(100.0, 50.0),
+            2.0,
+        );
+        assert_eq!(
+            visible,
+            egui::Rect::from_min_max(egui::pos2(5.0, 0.0), egui::pos2(25.0, 10.0))
+        );
+    }
+
+    #[test]
+    fn the_geometry_is_rebuilt_only_for_a_new_revision() {
+        let mut cache = CanvasCache::default();
+        let mut mosaic = a_white_mosaic();
+        cache.update(7, &mosaic, 0, &[]);
+        mosaic.add_a_row_of_tesserae(Vec::from([Tessera {
+            color: colors::oklch_from_srgb8([0, 0, 0]),
+            size: *mosaic.get_general_tessera_size(),
+            shape: TesseraShape::Whole,
+        }]));
+        let everything = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(200.0, 200.0));
+        cache.update(7, &mosaic, 0, &[]);
+        assert_eq!(
+            cache
+                .fill_shapes(everything, egui::Pos2::ZERO, 1.0, None)
+                .len(),
+            100
+        );
+        cache.update(8, &mosaic, 0, &[]);
+        assert_eq!(
+            cache
+                .fill_shapes(everything, egui::Pos2::ZERO, 1.0, None)
+                .len(),
+            101
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use eframe::egui;
+
+use crate::{
+    user_interface_app::{CanvasTool, MosaicneitorApp},
+    user_interface_tessera::color_swatch,
+};
+
+impl MosaicneitorApp {
+    pub(crate) fn show_color_replacement_window(&mut self, ctx: &egui::Context) {
+        let mut is_open = self.show_color_replacement;
+        egui::Window::new(t!("replace_color"))
+            .open(&mut is_open)
+            .default_pos([60.0, 220.0])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}:", t!("replacement_source")));
+                    match self.replacement_source {
+                        Some(color) => color_swatch(ui, color),
+                        None => {
+                            ui.label("?");
+                        }
+                    }
+                    if ui
+                        .toggle_value(
+                            &mut self.picking_replacement_source,
+                            t!("btn_pick_on_canvas"),
+                        )
+                        .on_hover_text(t!("pick_on_canvas_help"))
+                        .changed()
+                    {
+                        // The other tools paint on click.
+                        self.tool = CanvasTool::Select;
+                    }
+                    if let Some(tessera) = self
+                        .selected_tessera
+                        .and_then(|position| self.mosaic.get_tessera(&position))
+                    {
+                        if ui.button(t!("btn_take_selected_tessera_color")).clicked() {
+                            self.replacement_source = Some(tessera.color);
+                        }
+                    }
+                });
+                ui.add(
+                    egui::Slider::new(&mut self.replacement_tolerance, 0.0..=0.3)
+                        .text(t!("fill_tolerance")),
+                )
+                .on_hover_text(t!("replacement_tolerance_help"));
+
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}:", t!("replacement_target")));
+                    match self.replacement_target {
+                        Some(color) => color_swatch(ui, color),
+                        None => {
+                            ui.label("?");
+                        }
+                    }
+                    if ui.button(t!("btn_take_color_to_apply")).clicked() {
+                        self.replacement_target = Some(self.color_to_apply);
+                    }
+                });
+                ui.horizontal_wrapped(|ui| {
+                    for entry in &self.settings.tile_palette.entries {
+                        let swatch = egui::Button::new("").fill(egui::Color32::from_rgb(
+                            entry.srgb[0],
+                            entry.srgb[1],
+                            entry.srgb[2],
+                        ));
+                        if ui
+                            .add_sized([24.0, 24.0], swatch)
+                            .on_hover_text(format!("{} {}", entry.name, entry.supplier_code))
+                            .clicked()
+                        {
+                            self.replacement_target = Some(entry.get_color());
+                        }
+                    }
+                });
+
+                ui.separator();
+                self.update_tesserae_to_replace();
+                ui.label(format!(
+                    "{}: {}",
+                    t!("tesserae_to_replace"),
+                    self.tesserae_to_replace.len()
+                ));
+                if ui
+                    .add_enabled(
+                        self.replacement_target.is_some() && !self.tesserae_to_replace.is_empty(),
+                        egui::Button::new(t!("btn_replace")),
+                    )
+                    .clicked()
+                {
+                    self.replace_color();
+                }
+            });
+        if !is_open {
+            self.picking_replacement_source = false;
+        }
+        self.show_color_replacement = is_open;
+    }
+}
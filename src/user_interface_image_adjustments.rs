@@ -0,0 +1,142 @@
+use eframe::egui;
+
+use mosaicneitor_core::preprocessing::ImageOperation;
+
+use crate::user_interface_app::MosaicneitorApp;
+
+impl MosaicneitorApp {
+    pub(crate) fn show_image_adjustments_window(&mut self, ctx: &egui::Context) {
+        let mut is_open = self.show_image_adjustments;
+        let mut changed = false;
+        egui::Window::new(t!("image_adjustments"))
+            .open(&mut is_open)
+            .default_pos([60.0, 160.0])
+            .show(ctx, |ui| {
+                ui.label(t!("image_adjustments_help"));
+                ui.menu_button(t!("btn_add_image_operation"), |ui| {
+                    for operation in ImageOperation::ALL {
+                        if ui.button(image_operation_name(&operation)).clicked() {
+                            self.image_operations.push(operation);
+                            changed = true;
+                            ui.close_menu();
+                        }
+                    }
+                });
+                ui.separator();
+                let mut to_remove = None;
+                let mut to_move_up = None;
+                egui::Grid::new("image_operations")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (index, operation) in self.image_operations.iter_mut().enumerate() {
+                            ui.label(image_operation_name(operation));
+                            ui.horizontal(|ui| {
+                                changed |= edit_image_operation(ui, operation);
+                            });
+                            ui.horizontal(|ui| {
+                                if ui.add_enabled(index > 0, egui::Button::new("⬆")).clicked() {
+                                    to_move_up = Some(index);
+                                }
+                                if ui.button("🗑").clicked() {
+                                    to_remove = Some(index);
+                                }
+                            });
+                            ui.end_row();
+                        }
+                    });
+                if let Some(index) = to_move_up {
+                    self.image_operations.swap(index - 1, index);
+                    changed = true;
+                }
+                if let Some(index) = to_remove {
+                    self.image_operations.remove(index);
+                    changed = true;
+                }
+                ui.separator();
+                if ui
+                    .add_enabled(
+                        !self.image_operations.is_empty(),
+                        egui::Button::new(t!("btn_reset_image")),
+                    )
+                    .clicked()
+                {
+                    self.image_operations.clear();
+                    changed = true;
+                }
+            });
+        if changed {
+            self.apply_image_operations();
+        }
+        self.show_image_adjustments = is_open;
+    }
+}
+
+// Yields whether the settings have changed.
+fn edit_image_operation(ui: &mut egui::Ui, operation: &mut ImageOperation) -> bool {
+    match operation {
+        ImageOperation::Crop {
+            left,
+            top,
+            right,
+            bottom,
+        } => {
+            let mut changed = false;
+            for (side, value) in [
+                (t!("left"), left),
+                (t!("top"), top),
+                (t!("right"), right),
+                (t!("bottom"), bottom),
+            ] {
+                ui.label(side);
+                changed |= ui
+                    .add(
+                        egui::Slider::new(value, 0.0..=0.45)
+                            .custom_formatter(|v, _| format!("{:.0}%", v * 100.0)),
+                    )
+                    .changed();
+            }
+            changed
+        }
+        ImageOperation::Rotate { quarter_turns } => {
+            let mut changed = false;
+            for (turns, text) in [(1, "90°"), (2, "180°"), (3, "270°")] {
+                changed |= ui.selectable_value(quarter_turns, turns, text).changed();
+            }
+            changed
+        }
+        ImageOperation::FlipHorizontal | ImageOperation::FlipVertical | ImageOperation::Denoise => {
+            false
+        }
+        ImageOperation::Straighten { degrees } => ui
+            .add(egui::Slider::new(degrees, -15.0..=15.0).suffix("°"))
+            .changed(),
+        ImageOperation::Brightness { amount }
+        | ImageOperation::Contrast { amount }
+        | ImageOperation::Saturation { amount } => {
+            ui.add(egui::Slider::new(amount, -1.0..=1.0)).changed()
+        }
+        ImageOperation::Posterize { levels } => ui.add(egui::Slider::new(levels, 2..=16)).changed(),
+        ImageOperation::Blur { sigma } => ui
+            .add(egui::Slider::new(sigma, 0.0..=10.0).suffix(" px"))
+            .changed(),
+        ImageOperation::Gamma { gamma } => ui.add(egui::Slider::new(gamma, 0.2..=3.0)).changed(),
+    }
+}
+
+fn image_operation_name(operation: &ImageOperation) -> String {
+    match operation {
+        ImageOperation::Crop { .. } => t!("crop"),
+        ImageOperation::Rotate { .. } => t!("rotate"),
+        ImageOperation::FlipHorizontal => t!("flip_horizontal"),
+        ImageOperation::FlipVertical => t!("flip_vertical"),
+        ImageOperation::Straighten { .. } => t!("straighten"),
+        ImageOperation::Brightness { .. } => t!("brightness"),
+        ImageOperation::Contrast { .. } => t!("contrast"),
+        ImageOperation::Saturation { .. } => t!("saturation"),
+        ImageOperation::Posterize { .. } => t!("posterize"),
+        ImageOperation::Blur { .. } => t!("blur"),
+        ImageOperation::Denoise => t!("denoise"),
+        ImageOperation::Gamma { .. } => t!("gamma"),
+    }
+    .to_string()
+}
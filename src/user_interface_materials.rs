@@ -0,0 +1,111 @@
+use eframe::egui;
+
+use mosaicneitor_core::materials;
+
+use crate::user_interface_app::MosaicneitorApp;
+
+impl MosaicneitorApp {
+    pub(crate) fn show_materials_report_panel(&mut self, ctx: &egui::Context) {
+        egui::SidePanel::right("materials_report")
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                ui.heading(t!("materials_report"));
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}:", t!("waste_percentage")));
+                    ui.add(
+                        egui::DragValue::new(&mut self.settings.materials.waste_percentage)
+                            .range(0.0..=100.0)
+                            .suffix(" %"),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}:", t!("default_kg_per_square_meter")));
+                    ui.add(
+                        egui::DragValue::new(
+                            &mut self.settings.materials.default_kg_per_square_meter,
+                        )
+                        .range(0.0..=1000.0)
+                        .speed(0.1),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}:", t!("default_price_per_square_meter")));
+                    ui.add(
+                        egui::DragValue::new(
+                            &mut self.settings.materials.default_price_per_square_meter,
+                        )
+                        .range(0.0..=100000.0)
+                        .speed(0.1),
+                    );
+                });
+                ui.separator();
+                let report = self.get_materials_report();
+                egui::ScrollArea::vertical()
+                    .max_height(ui.available_height() - 80.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("materials_report_lines")
+                            .striped(true)
+                            .show(ui, |ui| {
+                                ui.label("");
+                                ui.label(t!("tile_name"));
+                                ui.label(t!("tesserae"));
+                                ui.label(t!("with_waste"));
+                                ui.label("kg");
+                                ui.label(t!("cost"));
+                                ui.label(t!("cuts"));
+                                ui.end_row();
+                                for line in &report.lines {
+                                    let (rect, _response) = ui.allocate_exact_size(
+                                        egui::vec2(16.0, 16.0),
+                                        egui::Sense::hover(),
+                                    );
+                                    ui.painter().rect_filled(
+                                        rect,
+                                        egui::Rounding::ZERO,
+                                        egui::Color32::from_rgb(
+                                            line.srgb[0],
+                                            line.srgb[1],
+                                            line.srgb[2],
+                                        ),
+                                    );
+                                    ui.label(match &line.palette_entry_name {
+                                        Some(name) => name.clone(),
+                                        None => materials::hex_color(line.srgb),
+                                    });
+                                    ui.label(line.tesserae_count.to_string());
+                                    ui.label(line.tesserae_count_with_waste.to_string());
+                                    ui.label(format!("{:.2}", line.weight_kg));
+                                    ui.label(format!("{:.2}", line.cost));
+                                    ui.label(line.cuts.to_string());
+                                    ui.end_row();
+                                }
+                            });
+                    });
+                ui.separator();
+                ui.label(format!(
+                    "{}: {} ({} {})",
+                    t!("total_tesserae"),
+                    report.get_total_tesserae(),
+                    report.get_total_tesserae_with_waste(),
+                    t!("with_waste")
+                ));
+                ui.label(format!("{}: {}", t!("total_cuts"), report.get_total_cuts()))
+                    .on_hover_text(t!("total_cuts_help"));
+                ui.label(format!(
+                    "{}: {:.2} kg    {}: {:.2}",
+                    t!("total_weight"),
+                    report.get_total_weight_kg(),
+                    t!("total_cost"),
+                    report.get_total_cost()
+                ));
+                ui.horizontal(|ui| {
+                    if ui.button(t!("btn_export_csv")).clicked() {
+                        self.open_export_dialog("export_materials_report", "materials.csv");
+                    }
+                    if ui.button(t!("btn_save_settings")).clicked() {
+                        self.save_settings();
+                    }
+                });
+            });
+    }
+}
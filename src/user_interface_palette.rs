@@ -0,0 +1,75 @@
+use eframe::egui;
+
+use crate::user_interface_app::MosaicneitorApp;
+
+impl MosaicneitorApp {
+    pub(crate) fn show_palette_editor_window(&mut self, ctx: &egui::Context) {
+        let mut is_open = self.show_palette_editor;
+        egui::Window::new(t!("tile_palette"))
+            .open(&mut is_open)
+            .default_pos([40.0, 200.0])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}:", t!("palette_name")));
+                    ui.text_edit_singleline(&mut self.settings.tile_palette.name);
+                });
+                ui.separator();
+                let mut entry_to_remove = None;
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("palette_entries")
+                            .striped(true)
+                            .show(ui, |ui| {
+                                ui.label("");
+                                ui.label(t!("tile_name"));
+                                ui.label(t!("supplier_code"));
+                                ui.label(t!("kg_per_square_meter"));
+                                ui.label(t!("price_per_square_meter"));
+                                ui.label("");
+                                ui.end_row();
+                                for (index, entry) in
+                                    self.settings.tile_palette.entries.iter_mut().enumerate()
+                                {
+                                    ui.color_edit_button_srgb(&mut entry.srgb);
+                                    ui.add(
+                                        egui::TextEdit::singleline(&mut entry.name)
+                                            .desired_width(120.0),
+                                    );
+                                    ui.add(
+                                        egui::TextEdit::singleline(&mut entry.supplier_code)
+                                            .desired_width(80.0),
+                                    );
+                                    ui.add(
+                                        egui::DragValue::new(&mut entry.kg_per_square_meter)
+                                            .range(0.0..=1000.0)
+                                            .speed(0.1),
+                                    );
+                                    ui.add(
+                                        egui::DragValue::new(&mut entry.price_per_square_meter)
+                                            .range(0.0..=100000.0)
+                                            .speed(0.1),
+                                    );
+                                    if ui.button("🗑").clicked() {
+                                        entry_to_remove = Some(index);
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                    });
+                if let Some(index) = entry_to_remove {
+                    self.settings.tile_palette.entries.remove(index);
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button(t!("btn_add_tile_color")).clicked() {
+                        self.settings.tile_palette.add_entry([255, 255, 255]);
+                    }
+                    if ui.button(t!("btn_save_palette")).clicked() {
+                        self.save_settings();
+                    }
+                });
+            });
+        self.show_palette_editor = is_open;
+    }
+}
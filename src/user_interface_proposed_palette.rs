@@ -0,0 +1,98 @@
+use eframe::egui;
+
+use mosaicneitor_core::colors;
+
+use crate::user_interface_app::MosaicneitorApp;
+
+impl MosaicneitorApp {
+    pub(crate) fn show_proposed_palette_window(&mut self, ctx: &egui::Context) {
+        let mut is_open = self.show_proposed_palette;
+        egui::Window::new(t!("proposed_palette"))
+            .open(&mut is_open)
+            .default_pos([60.0, 220.0])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}:", t!("number_of_colors")));
+                    ui.add(egui::DragValue::new(&mut self.number_of_proposed_colors).range(1..=64));
+                    if ui
+                        .button(t!("btn_propose_palette"))
+                        .on_hover_text(t!("propose_palette_help"))
+                        .clicked()
+                    {
+                        self.propose_palette();
+                    }
+                });
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("proposed_palette_clusters")
+                            .striped(true)
+                            .show(ui, |ui| {
+                                ui.label("");
+                                ui.label("");
+                                ui.label(t!("tile_name"));
+                                ui.label(t!("tesserae"));
+                                ui.label(t!("locked"));
+                                ui.end_row();
+                                for (index, cluster) in
+                                    self.proposed_palette.clusters.iter_mut().enumerate()
+                                {
+                                    let mut is_selected = self.clusters_to_merge.contains(&index);
+                                    if ui.checkbox(&mut is_selected, "").changed() {
+                                        if is_selected {
+                                            self.clusters_to_merge.insert(index);
+                                        } else {
+                                            self.clusters_to_merge.remove(&index);
+                                        }
+                                    }
+                                    let (rect, _response) = ui.allocate_exact_size(
+                                        egui::vec2(16.0, 16.0),
+                                        egui::Sense::hover(),
+                                    );
+                                    let [r, g, b] = colors::srgb8_from_oklch(cluster.color);
+                                    ui.painter().rect_filled(
+                                        rect,
+                                        egui::Rounding::ZERO,
+                                        egui::Color32::from_rgb(r, g, b),
+                                    );
+                                    ui.add(
+                                        egui::TextEdit::singleline(&mut cluster.name)
+                                            .desired_width(120.0),
+                                    );
+                                    ui.label(cluster.count.to_string());
+                                    ui.checkbox(&mut cluster.locked, "");
+                                    ui.end_row();
+                                }
+                            });
+                    });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            self.clusters_to_merge.len() > 1,
+                            egui::Button::new(t!("btn_merge_selected")),
+                        )
+                        .clicked()
+                    {
+                        self.merge_selected_clusters();
+                    }
+                    if ui.button(t!("btn_apply_to_mosaic")).clicked() {
+                        self.apply_proposed_palette();
+                    }
+                    if ui
+                        .button(t!("btn_add_to_tile_palette"))
+                        .on_hover_text(t!("add_to_tile_palette_help"))
+                        .clicked()
+                    {
+                        let added = self
+                            .proposed_palette
+                            .add_to_tile_palette(&mut self.settings.tile_palette);
+                        self.status_message = format!("{}: {added}", t!("palette_entries_added"));
+                        self.show_palette_editor = true;
+                    }
+                });
+            });
+        self.show_proposed_palette = is_open;
+    }
+}
@@ -0,0 +1,228 @@
+use eframe::egui;
+
+use mosaicneitor_core::{
+    colors,
+    tessera_shape::{CutPreset, TesseraShape},
+};
+
+use crate::user_interface_app::MosaicneitorApp;
+
+impl MosaicneitorApp {
+    pub(crate) fn show_tessera_editor_window(&mut self, ctx: &egui::Context) {
+        let position = match self.selected_tessera {
+            Some(position) => position,
+            None => return,
+        };
+        let mut is_open = true;
+        egui::Window::new(t!("tessera"))
+            .open(&mut is_open)
+            .default_pos([700.0, 200.0])
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "{}: {}    {}: {}",
+                    t!("row"),
+                    position.row,
+                    t!("column"),
+                    position.column
+                ));
+                if let Some(tessera) = self.mosaic.get_tessera(&position) {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{}:", t!("current_color")));
+                        color_swatch(ui, tessera.color);
+                    });
+                    ui.label(format!(
+                        "{}: {} x {} mm",
+                        t!("current_size"),
+                        tessera.size.horizontal,
+                        tessera.size.vertical
+                    ));
+                    ui.label(format!(
+                        "{}: {}",
+                        t!("current_shape"),
+                        shape_name(&tessera.shape)
+                    ));
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}:", t!("color_to_apply")));
+                    color_swatch(ui, self.color_to_apply);
+                });
+
+                ui.collapsing(t!("from_palette"), |ui| {
+                    if self.settings.tile_palette.entries.is_empty() {
+                        ui.label(t!("palette_is_empty"));
+                    }
+                    ui.horizontal_wrapped(|ui| {
+                        for entry in &self.settings.tile_palette.entries {
+                            let swatch = egui::Button::new("").fill(egui::Color32::from_rgb(
+                                entry.srgb[0],
+                                entry.srgb[1],
+                                entry.srgb[2],
+                            ));
+                            if ui
+                                .add_sized([24.0, 24.0], swatch)
+                                .on_hover_text(format!("{} {}", entry.name, entry.supplier_code))
+                                .clicked()
+                            {
+                                self.color_to_apply = entry.get_color();
+                            }
+                        }
+                    });
+                });
+
+                ui.collapsing(t!("from_image"), |ui| {
+                    ui.toggle_value(&mut self.eyedropper_active, t!("eyedropper"))
+                        .on_hover_text(t!("eyedropper_help"));
+                });
+
+                ui.collapsing("HSV", |ui| {
+                    let srgb = colors::srgb8_from_oklch(self.color_to_apply);
+                    let mut color = egui::Color32::from_rgb(srgb[0], srgb[1], srgb[2]);
+                    if egui::color_picker::color_picker_color32(
+                        ui,
+                        &mut color,
+                        egui::color_picker::Alpha::Opaque,
+                    ) {
+                        self.color_to_apply =
+                            colors::oklch_from_srgb8([color.r(), color.g(), color.b()]);
+                    }
+                });
+
+                ui.collapsing("Oklch", |ui| {
+                    ui.add(egui::Slider::new(&mut self.color_to_apply.l, 0.0..=1.0).text("L"));
+                    ui.add(
+                        egui::Slider::new(&mut self.color_to_apply.chroma, 0.0..=0.37).text("C"),
+                    );
+                    let mut hue = self.color_to_apply.hue.into_positive_degrees();
+                    if ui
+                        .add(egui::Slider::new(&mut hue, 0.0..=360.0).text("h"))
+                        .changed()
+                    {
+                        self.color_to_apply.hue = palette::OklabHue::from_degrees(hue);
+                    }
+                });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button(t!("btn_apply_color")).clicked() {
+                        self.apply_color_to_tessera(&position);
+                    }
+                    if let Some(tessera) = self.mosaic.get_tessera(&position) {
+                        if ui.button(t!("btn_take_tessera_color")).clicked() {
+                            self.color_to_apply = tessera.color;
+                        }
+                    }
+                });
+                ui.label(t!("right_click_help"));
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}:", t!("size_to_apply")));
+                    ui.add(
+                        egui::DragValue::new(&mut self.size_to_apply.horizontal)
+                            .range(1..=1000)
+                            .suffix(" mm"),
+                    );
+                    ui.label("x");
+                    ui.add(
+                        egui::DragValue::new(&mut self.size_to_apply.vertical)
+                            .range(1..=1000)
+                            .suffix(" mm"),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    if ui.button(t!("btn_apply_size_to_tessera")).clicked() {
+                        self.apply_size_to_tesserae(&[position]);
+                    }
+                    if ui.button(t!("btn_apply_size_to_row")).clicked() {
+                        self.apply_size_to_row(position.row);
+                    }
+                    if let Some(tessera) = self.mosaic.get_tessera(&position) {
+                        if ui.button(t!("btn_take_tessera_size")).clicked() {
+                            self.size_to_apply = tessera.size;
+                        }
+                    }
+                });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_label(t!("shape_to_apply"))
+                        .selected_text(shape_name(&self.shape_to_apply))
+                        .show_ui(ui, |ui| {
+                            for shape in [TesseraShape::Whole, TesseraShape::Empty] {
+                                let name = shape_name(&shape);
+                                ui.selectable_value(&mut self.shape_to_apply, shape, name);
+                            }
+                            for preset in CutPreset::ALL {
+                                let shape = TesseraShape::Preset(preset);
+                                let name = shape_name(&shape);
+                                ui.selectable_value(&mut self.shape_to_apply, shape, name);
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} (mm):", t!("custom_polygon")));
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.custom_shape_text)
+                            .hint_text("0,0 10,0 5,10")
+                            .desired_width(140.0),
+                    )
+                    .on_hover_text(t!("custom_polygon_help"));
+                    if ui.button(t!("btn_use_custom_polygon")).clicked() {
+                        self.set_custom_shape_to_apply(&position);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button(t!("btn_apply_shape")).clicked() {
+                        self.apply_shape_to_tessera(&position);
+                    }
+                    if let Some(tessera) = self.mosaic.get_tessera(&position) {
+                        if ui.button(t!("btn_take_tessera_shape")).clicked() {
+                            self.shape_to_apply = tessera.shape.clone();
+                            self.custom_shape_text = tessera.shape.polygon_to_text(&tessera.size);
+                        }
+                    }
+                });
+            });
+        if !is_open {
+            self.selected_tessera = None;
+            self.eyedropper_active = false;
+        }
+    }
+}
+
+pub(crate) fn color_swatch(ui: &mut egui::Ui, color: palette::Oklch) {
+    let srgb = colors::srgb8_from_oklch(color);
+    let (rect, _response) = ui.allocate_exact_size(egui::vec2(40.0, 16.0), egui::Sense::hover());
+    ui.painter().rect_filled(
+        rect,
+        egui::Rounding::ZERO,
+        egui::Color32::from_rgb(srgb[0], srgb[1], srgb[2]),
+    );
+}
+
+fn shape_name(shape: &TesseraShape) -> String {
+    match shape {
+        TesseraShape::Whole => t!("shape_whole").to_string(),
+        TesseraShape::Polygon(_) => t!("custom_polygon").to_string(),
+        TesseraShape::Empty => t!("shape_empty").to_string(),
+        TesseraShape::Preset(preset) => match preset {
+            CutPreset::HalfLeft => t!("cut_half_left").to_string(),
+            CutPreset::HalfRight => t!("cut_half_right").to_string(),
+            CutPreset::HalfTop => t!("cut_half_top").to_string(),
+            CutPreset::HalfBottom => t!("cut_half_bottom").to_string(),
+            CutPreset::TriangleTopLeft => t!("cut_triangle_top_left").to_string(),
+            CutPreset::TriangleTopRight => t!("cut_triangle_top_right").to_string(),
+            CutPreset::TriangleBottomRight => t!("cut_triangle_bottom_right").to_string(),
+            CutPreset::TriangleBottomLeft => t!("cut_triangle_bottom_left").to_string(),
+            CutPreset::QuarterTopLeft => t!("cut_quarter_top_left").to_string(),
+            CutPreset::QuarterTopRight => t!("cut_quarter_top_right").to_string(),
+            CutPreset::QuarterBottomRight => t!("cut_quarter_bottom_right").to_string(),
+            CutPreset::QuarterBottomLeft => t!("cut_quarter_bottom_left").to_string(),
+            CutPreset::WedgeUp => t!("cut_wedge_up").to_string(),
+            CutPreset::WedgeRight => t!("cut_wedge_right").to_string(),
+            CutPreset::WedgeDown => t!("cut_wedge_down").to_string(),
+            CutPreset::WedgeLeft => t!("cut_wedge_left").to_string(),
+        },
+    }
+}